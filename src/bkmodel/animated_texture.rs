@@ -1,4 +1,18 @@
 use std::ops::{Deref, DerefMut};
+use std::error::Error;
+
+use super::{BKModel, BKTextureList};
+use super::f3dex::split_cmd;
+use super::super::bktexture::*;
+use super::super::pixels::*;
+use super::super::error::{*};
+
+/* Animated textures
+    the game points segment 0x0F - slot at the texture data, moved on by
+    frame*frame_size every frame, so the display list draws an animated texture
+    with G_SETTIMG into that segment at the texture's own offset.
+*/
+pub const ANIMATED_TEXTURE_SEGMENT : usize = 0x0F;
 
 #[derive(Debug, Clone)]
 #[repr(C)]
@@ -37,6 +51,61 @@ impl BKAnimatedTexture {
             self.framerate_fps.to_be_bytes().as_slice()
         ].concat().try_into().unwrap()
     }
+
+    fn frame_layout(&self)->Result<(usize, usize), Box<dyn Error>>{
+        if self.frame_size <= 0 { return Err(Box::new(FrameSizeError)) }
+        if self.frame_count < 0 { return Err(Box::new(FrameCountError)) }
+        Ok((self.frame_size as usize, self.frame_count as usize))
+    }
+
+    //frames are stored back to back from the texture's offset, each `frame_size` bytes
+    pub fn frames(&self, texture_list: &BKTextureList, texture_index: usize)->Result<Vec<BKTexture<RGBA32>>, Box<dyn Error>>{
+        let (frame_size, frame_count) = self.frame_layout()?;
+        let header = texture_list.texture_headers.get(texture_index).ok_or(TextureIndexError)?;
+        let size = header.format.byte_size(header.width, header.height).ok_or(TextureFormatError)?;
+        if size > frame_size { return Err(Box::new(FrameSizeError)) }
+        (0..frame_count)
+            .map(|i| {
+                let start = header.offset + i*frame_size;
+                let bytes = texture_list.texture_data.get(start .. start + size).ok_or(TextureDataError)?;
+                Ok(header.format.decode(header.width, header.height, bytes).ok_or(TextureDataError)?)
+            })
+            .collect()
+    }
+
+    //every frame is checked before any is written
+    pub fn set_frames(&self, texture_list: &mut BKTextureList, texture_index: usize, frames: &[BKTexture<RGBA32>])->Result<(), Box<dyn Error>>{
+        let (frame_size, frame_count) = self.frame_layout()?;
+        if frames.len() != frame_count { return Err(Box::new(FrameCountError)) }
+        let header = texture_list.texture_headers.get(texture_index).ok_or(TextureIndexError)?;
+        let mut encoded = Vec::with_capacity(frames.len());
+        for (i, frame) in frames.iter().enumerate() {
+            if frame.width() != header.width || frame.height() != header.height { return Err(Box::new(TextureDimensionsError)) }
+            let bytes = header.format.encode(frame, None).ok_or(TextureFormatError)?;
+            if bytes.len() > frame_size { return Err(Box::new(FrameSizeError)) }
+            let start = header.offset + i*frame_size;
+            if start + bytes.len() > texture_list.texture_data.len() { return Err(Box::new(TextureDataError)) }
+            encoded.push((start, bytes));
+        }
+        for (start, bytes) in encoded {
+            texture_list.texture_data[start .. start + bytes.len()].copy_from_slice(&bytes);
+        }
+        Ok(())
+    }
+
+    pub fn to_apng(&self, texture_list: &BKTextureList, texture_index: usize)->Result<Vec<u8>, Box<dyn Error>>{
+        frames_to_apng(&self.frames(texture_list, texture_index)?, self.framerate_fps)
+    }
+
+    //the frames replace the texture's, a framerate stored in the APNG replaces this one
+    pub fn import_apng(&mut self, texture_list: &mut BKTextureList, texture_index: usize, apng: &[u8])->Result<(), Box<dyn Error>>{
+        let apng = frames_from_apng(apng)?;
+        self.set_frames(texture_list, texture_index, &apng.frames)?;
+        if apng.framerate_fps > 0.0 {
+            self.framerate_fps = apng.framerate_fps;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -78,4 +147,100 @@ impl DerefMut for BKAnimatedTextureList{
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.values
     }
-}
\ No newline at end of file
+}
+
+impl BKModel{
+    //texture drawn through an animated texture slot
+    pub fn animated_texture_index(&self, slot: usize)->Option<usize>{
+        let segment = ANIMATED_TEXTURE_SEGMENT.checked_sub(slot)?;
+        let textures = self.texture_list.as_ref()?;
+        self.display_list.as_ref()?.gfx.iter().find_map(|cmd| {
+            let (w0, w1) = split_cmd(u64::from(cmd.clone()));
+            match w0 >> 24 == 0xFD && (w1 >> 24) as usize == segment { //G_SETTIMG
+                true => textures.texture_at((w1 & 0xFFFFFF) as usize),
+                false => None,
+            }
+        })
+    }
+
+    fn animated_texture(&self, slot: usize)->Result<(&BKAnimatedTexture, usize), AnimatedTextureSlotError>{
+        let animated = self.animated_texture_list.as_ref()
+            .and_then(|list| list.get(slot)?.as_ref())
+            .ok_or(AnimatedTextureSlotError)?;
+        Ok((animated, self.animated_texture_index(slot).ok_or(AnimatedTextureSlotError)?))
+    }
+
+    pub fn animated_texture_frames(&self, slot: usize)->Result<Vec<BKTexture<RGBA32>>, Box<dyn Error>>{
+        let (animated, texture_index) = self.animated_texture(slot)?;
+        animated.frames(self.texture_list.as_ref().ok_or(TextureIndexError)?, texture_index)
+    }
+
+    pub fn animated_texture_to_apng(&self, slot: usize)->Result<Vec<u8>, Box<dyn Error>>{
+        let (animated, texture_index) = self.animated_texture(slot)?;
+        animated.to_apng(self.texture_list.as_ref().ok_or(TextureIndexError)?, texture_index)
+    }
+
+    pub fn import_animated_texture_apng(&mut self, slot: usize, apng: &[u8])->Result<(), Box<dyn Error>>{
+        let (animated, texture_index) = self.animated_texture(slot)?;
+        let mut animated = animated.clone();
+        animated.import_apng(self.texture_list.as_mut().ok_or(TextureIndexError)?, texture_index, apng)?;
+        if let Some(list) = self.animated_texture_list.as_mut() {
+            list[slot] = Some(animated);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libultra::F3dex;
+    use super::super::BKGfxList;
+
+    #[test]
+    fn animated_texture_frames() {
+        //two 2x1 I8 frames in slot 1, then an unrelated texture
+        let header = |offset, width| BKTextureHeader{offset, format: BKTextureFormat::I8, flags: 0, level_count: 0, width, height: 1, padding: [0; 6]};
+        let mut model = BKModel{
+            texture_list: Some(BKTextureList{
+                texture_headers: vec![header(0, 2), header(8, 1)],
+                texture_data: vec![0x10, 0x20, 0, 0, 0x30, 0x40, 0, 0, 0x55, 0, 0, 0, 0, 0, 0, 0],
+            }),
+            display_list: Some(BKGfxList::new(vec![F3dex::from(0xFD100000_0E000000u64)])), //G_SETTIMG segment 0x0E
+            animated_texture_list: Some(BKAnimatedTextureList{values: [
+                None,
+                Some(BKAnimatedTexture{frame_size: 4, frame_count: 2, framerate_fps: 10.0}),
+                None,
+                None,
+            ]}),
+            ..Default::default()
+        };
+        assert_eq!(model.animated_texture_index(1), Some(0));
+        let frames = model.animated_texture_frames(1).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].tmem[0][0].r, 0x30);
+        assert!(model.animated_texture_frames(0).unwrap_err().is::<AnimatedTextureSlotError>());
+
+        //edited frames round trip, the APNG's framerate is kept
+        let mut edited = frames.clone();
+        edited[0].tmem[0][1] = RGBA32{r: 0x77, g: 0x77, b: 0x77, a: 0xFF};
+        let apng = frames_to_apng(&edited, 5.0).unwrap();
+        model.import_animated_texture_apng(1, &apng).unwrap();
+        let list = model.texture_list.as_ref().unwrap();
+        assert_eq!(list.texture_data[..8], [0x10, 0x77, 0, 0, 0x30, 0x40, 0, 0]);
+        assert_eq!(list.texture_data[8], 0x55);
+        assert_eq!(model.animated_texture_list.as_ref().unwrap()[1].as_ref().unwrap().framerate_fps, 5.0);
+
+        //rejected without writing anything
+        let animated = model.animated_texture_list.as_ref().unwrap()[1].clone().unwrap();
+        let list = model.texture_list.as_mut().unwrap();
+        let too_few = animated.set_frames(list, 0, &edited[..1]).unwrap_err();
+        assert!(too_few.is::<FrameCountError>());
+        assert!(animated.frames(list, 5).unwrap_err().is::<TextureIndexError>());
+        let negative = BKAnimatedTexture{frame_size: -4, ..animated.clone()};
+        assert!(negative.frames(list, 0).unwrap_err().is::<FrameSizeError>());
+        let wide = vec![BKTexture::<RGBA32>{palette: None, tmem: vec![vec![RGBA32{r: 0, g: 0, b: 0, a: 0}; 3]]}; 2];
+        assert!(animated.set_frames(list, 0, &wide).unwrap_err().is::<TextureDimensionsError>());
+        assert_eq!(list.texture_data[1], 0x77);
+    }
+}
@@ -4,9 +4,9 @@ use super::super::error::{*};
 #[derive(Debug)]
 #[repr(C)]
 pub struct BKAnimation{
-    unk_0: [f32; 3], //position
-    bone_id: i16, //bone_id
-    mtx_id: i16, //parent_transform
+    pub unk_0: [f32; 3], //position
+    pub bone_id: i16, //bone_id
+    pub mtx_id: i16, //parent_transform
 }
 
 impl BKAnimation{
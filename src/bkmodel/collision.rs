@@ -41,8 +41,8 @@ impl fmt::Debug for BKCollisionTri{
 #[repr(C)]
 
 pub struct BKCollisionMesh{
-    tri_start: i16,
-    size: i16,
+    pub tri_start: i16, //first BKCollisionTri of the grid cell
+    pub size: i16,      //triangle count
 }
 
 impl BKCollisionMesh{
@@ -69,15 +69,20 @@ impl fmt::Debug for BKCollisionMesh{
     }
 }
 
+/* a grid of cells `scale` units wide, min and max are the first and last cell
+    on each axis (position/scale rounded down). cells are stored x first, then y,
+    then z: index = (x - min.x) + (y - min.y)*y_stride + (z - min.z)*z_stride.
+    every cell lists the triangles touching it, a triangle can be in several.
+*/
 #[derive(Debug)]
 pub struct BKCollisionList{
-    unk_0: [i16; 3], //min
-    unk_6: [i16; 3], //max
-    unk_c: i16, //y_stride
-    unk_e: i16, //z_stride
-    unk_12: i16, //scale
-    geo: Vec<BKCollisionMesh>,
-    tri: Vec<BKCollisionTri>,
+    pub min: [i16; 3],
+    pub max: [i16; 3],
+    pub y_stride: i16,
+    pub z_stride: i16,
+    pub scale: i16,
+    pub geo: Vec<BKCollisionMesh>, //one per cell
+    pub tri: Vec<BKCollisionTri>,  //vtx index into the model's BKVertexList
 }
 
 impl BKCollisionList{
@@ -94,11 +99,11 @@ impl BKCollisionList{
         let _size = tri_offset + tri_cnt*0xC;
                 
         let this = BKCollisionList{
-            unk_0  : [shorts[0], shorts[1], shorts[2]],
-            unk_6  : [shorts[3], shorts[4], shorts[5]],
-            unk_c  : shorts[6],
-            unk_e  : shorts[7],
-            unk_12 : shorts[9],
+            min      : [shorts[0], shorts[1], shorts[2]],
+            max      : [shorts[3], shorts[4], shorts[5]],
+            y_stride : shorts[6],
+            z_stride : shorts[7],
+            scale    : shorts[9],
             geo: bytes[geo_offset..].chunks_exact(4)
                 .map(|b| b.try_into().map(|b| BKCollisionMesh::from_be_bytes(b)))
                 .take(geo_cnt as usize)
@@ -118,12 +123,12 @@ impl BKCollisionList{
 
     pub fn to_be_bytes(&self)->Vec<u8>{
         let mut bytes = [
-            self.unk_0.iter().flat_map(|val| val.to_be_bytes()).collect::<Vec<_>>().as_slice(), //min
-            self.unk_6.iter().flat_map(|val| val.to_be_bytes()).collect::<Vec<_>>().as_slice(), //max
-            self.unk_c.to_be_bytes().as_slice(), //y_stride
-            self.unk_e.to_be_bytes().as_slice(), //z_stride
+            self.min.iter().flat_map(|val| val.to_be_bytes()).collect::<Vec<_>>().as_slice(),
+            self.max.iter().flat_map(|val| val.to_be_bytes()).collect::<Vec<_>>().as_slice(),
+            self.y_stride.to_be_bytes().as_slice(),
+            self.z_stride.to_be_bytes().as_slice(),
             (self.geo.len() as i16).to_be_bytes().as_slice(), //geo_cnt
-            self.unk_12.to_be_bytes().as_slice(), //scale
+            self.scale.to_be_bytes().as_slice(),
             (self.tri.len() as i16).to_be_bytes().as_slice(), //geo_cnt
             &[0,0],
             self.geo.iter().flat_map(|val| val.to_be_bytes()).collect::<Vec<_>>().as_slice(),
@@ -0,0 +1,224 @@
+use std::collections::BTreeSet;
+
+use super::{BKCollisionList, BKCollisionTri, BKVertexList};
+
+/* Collision queries
+    a query only tests the triangles listed in the grid cells its bounding box
+    touches, points outside the grid use the nearest cell on each axis.
+    triangle corners are the model's vertex positions, triangles with a corner
+    outside the vertex list are skipped. hits are two sided, the normal follows
+    the winding, (b - a) x (c - a), except for floor_height which only takes
+    triangles whose normal points up, so ceilings and overhangs are looked through.
+*/
+
+type Vec3 = [f32; 3];
+
+fn sub(a: Vec3, b: Vec3)->Vec3{ [a[0] - b[0], a[1] - b[1], a[2] - b[2]] }
+fn add(a: Vec3, b: Vec3)->Vec3{ [a[0] + b[0], a[1] + b[1], a[2] + b[2]] }
+fn scale(a: Vec3, s: f32)->Vec3{ [a[0]*s, a[1]*s, a[2]*s] }
+fn dot(a: Vec3, b: Vec3)->f32{ a[0]*b[0] + a[1]*b[1] + a[2]*b[2] }
+fn cross(a: Vec3, b: Vec3)->Vec3{ [a[1]*b[2] - a[2]*b[1], a[2]*b[0] - a[0]*b[2], a[0]*b[1] - a[1]*b[0]] }
+
+//closest point of triangle abc to p (Ericson, Real-Time Collision Detection 5.1.5)
+fn closest_point(p: Vec3, [a, b, c]: [Vec3; 3])->Vec3{
+    let (ab, ac) = (sub(b, a), sub(c, a));
+    let ap = sub(p, a);
+    let (d1, d2) = (dot(ab, ap), dot(ac, ap));
+    if d1 <= 0.0 && d2 <= 0.0 { return a }
+    let bp = sub(p, b);
+    let (d3, d4) = (dot(ab, bp), dot(ac, bp));
+    if d3 >= 0.0 && d4 <= d3 { return b }
+    let vc = d1*d4 - d3*d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 { return add(a, scale(ab, d1/(d1 - d3))) }
+    let cp = sub(p, c);
+    let (d5, d6) = (dot(ab, cp), dot(ac, cp));
+    if d6 >= 0.0 && d5 <= d6 { return c }
+    let vb = d5*d2 - d1*d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 { return add(a, scale(ac, d2/(d2 - d6))) }
+    let va = d3*d6 - d5*d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return add(b, scale(sub(c, b), (d4 - d3)/((d4 - d3) + (d5 - d6))))
+    }
+    let denom = 1.0/(va + vb + vc);
+    add(a, add(scale(ab, vb*denom), scale(ac, vc*denom)))
+}
+
+//fraction along from..to where the segment crosses triangle abc (Möller-Trumbore)
+fn segment_hit(from: Vec3, to: Vec3, [a, b, c]: [Vec3; 3])->Option<f32>{
+    const EPSILON : f32 = 1e-6;
+    let dir = sub(to, from);
+    let (e1, e2) = (sub(b, a), sub(c, a));
+    let p = cross(dir, e2);
+    let det = dot(e1, p);
+    if det.abs() < EPSILON { return None }
+    let inv = 1.0/det;
+    let s = sub(from, a);
+    let u = dot(s, p)*inv;
+    if !(-EPSILON ..= 1.0 + EPSILON).contains(&u) { return None }
+    let q = cross(s, e1);
+    let v = dot(dir, q)*inv;
+    if v < -EPSILON || u + v > 1.0 + EPSILON { return None }
+    let t = dot(e2, q)*inv;
+    (-EPSILON ..= 1.0 + EPSILON).contains(&t).then_some(t.clamp(0.0, 1.0))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BKCollisionHit<'a>{
+    pub tri_index: usize,
+    pub tri: &'a BKCollisionTri,
+    pub point: [f32; 3],
+    pub normal: [f32; 3],
+    pub distance: f32, //from the query's start or centre to `point`
+}
+
+impl BKCollisionList{
+    //grid cell containing a position
+    pub fn cell(&self, point: [f32; 3])->[i32; 3]{
+        let scale = self.scale.max(1) as f32;
+        point.map(|p| (p/scale).floor() as i32)
+    }
+
+    pub fn cell_index(&self, cell: [i32; 3])->Option<usize>{
+        if (0..3).any(|i| cell[i] < self.min[i] as i32 || cell[i] > self.max[i] as i32) { return None }
+        let index = (cell[0] - self.min[0] as i32)
+            + (cell[1] - self.min[1] as i32)*self.y_stride as i32
+            + (cell[2] - self.min[2] as i32)*self.z_stride as i32;
+        usize::try_from(index).ok().filter(|&i| i < self.geo.len())
+    }
+
+    //indices into `tri` of every triangle in the cells touching the box
+    pub fn tris_in_box(&self, min: [f32; 3], max: [f32; 3])->Vec<usize>{
+        let clamp = |cell: [i32; 3]| [0, 1, 2].map(|i| cell[i].clamp(self.min[i] as i32, self.max[i] as i32));
+        let (lo, hi) = (clamp(self.cell(min)), clamp(self.cell(max)));
+        let mut tris = BTreeSet::new();
+        for z in lo[2] ..= hi[2] {
+            for y in lo[1] ..= hi[1] {
+                for x in lo[0] ..= hi[0] {
+                    if let Some(mesh) = self.cell_index([x, y, z]).map(|i| &self.geo[i]) {
+                        let start = mesh.tri_start.max(0) as usize;
+                        tris.extend((start .. start + mesh.size.max(0) as usize).filter(|&i| i < self.tri.len()));
+                    }
+                }
+            }
+        }
+        tris.into_iter().collect()
+    }
+
+    fn corners(&self, vertices: &BKVertexList, tri_index: usize)->Option<[Vec3; 3]>{
+        let mut corners = [[0.0; 3]; 3];
+        for (corner, &v) in corners.iter_mut().zip(self.tri[tri_index].vtx.iter()) {
+            *corner = vertices.get(usize::try_from(v).ok()?)?.ob.map(f32::from);
+        }
+        Some(corners)
+    }
+
+    fn hit(&self, vertices: &BKVertexList, tri_index: usize, point: Vec3, distance: f32)->Option<BKCollisionHit<'_>>{
+        let [a, b, c] = self.corners(vertices, tri_index)?;
+        let normal = cross(sub(b, a), sub(c, a));
+        let length = dot(normal, normal).sqrt();
+        (length > 0.0).then(|| BKCollisionHit{tri_index, tri: &self.tri[tri_index], point, normal: scale(normal, 1.0/length), distance})
+    }
+
+    //every triangle the segment from..to crosses
+    fn segment_hits(&self, vertices: &BKVertexList, from: Vec3, to: Vec3)->Vec<BKCollisionHit<'_>>{
+        let min = [0, 1, 2].map(|i| from[i].min(to[i]));
+        let max = [0, 1, 2].map(|i| from[i].max(to[i]));
+        let length = dot(sub(to, from), sub(to, from)).sqrt();
+        self.tris_in_box(min, max).into_iter()
+            .filter_map(|i| Some((i, segment_hit(from, to, self.corners(vertices, i)?)?)))
+            .filter_map(|(i, t)| self.hit(vertices, i, add(from, scale(sub(to, from), t)), t*length))
+            .collect()
+    }
+
+    //first triangle the segment from..to crosses
+    pub fn raycast(&self, vertices: &BKVertexList, from: [f32; 3], to: [f32; 3])->Option<BKCollisionHit<'_>>{
+        self.segment_hits(vertices, from, to).into_iter().min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    //highest upward facing triangle at or below the point, `point[1]` of the hit is the floor height
+    pub fn floor_height(&self, vertices: &BKVertexList, point: [f32; 3])->Option<BKCollisionHit<'_>>{
+        let bottom = (self.min[1] as f32 - 1.0)*self.scale.max(1) as f32;
+        if point[1] < bottom { return None }
+        self.segment_hits(vertices, point, [point[0], bottom, point[2]]).into_iter()
+            .filter(|hit| hit.normal[1] > 0.0)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    //every triangle within `radius` of the centre, nearest first
+    pub fn sphere_overlaps(&self, vertices: &BKVertexList, center: [f32; 3], radius: f32)->Vec<BKCollisionHit<'_>>{
+        let mut hits : Vec<_> = self.tris_in_box(center.map(|c| c - radius), center.map(|c| c + radius)).into_iter()
+            .filter_map(|i| {
+                let point = closest_point(center, self.corners(vertices, i)?);
+                let distance = dot(sub(point, center), sub(point, center)).sqrt();
+                (distance <= radius).then(|| self.hit(vertices, i, point, distance)).flatten()
+            })
+            .collect();
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libultra::Vtx;
+    use super::super::BKCollisionMesh;
+
+    #[test]
+    fn collision_queries() {
+        let vertices = BKVertexList::new([
+            [0, 0, 0], [0, 0, 100], [100, 0, 0], [100, 0, 100], //floor
+            [200, 100, 0], [200, 100, 100],                     //ramp up from x = 100
+            [0, 50, 0], [100, 50, 0], [0, 50, 100],             //overhang, wound to face down
+        ].iter().map(|ob: &[i16; 3]| {
+            let mut bytes = [0u8; 16];
+            for (i, c) in ob.iter().enumerate() { bytes[2*i .. 2*i + 2].copy_from_slice(&c.to_be_bytes()) }
+            Vtx::from_be_bytes(&bytes)
+        }).collect());
+        let tri = |vtx: [i16; 3], flags: u32| BKCollisionTri{vtx, unk_6: 0, flags};
+        let list = BKCollisionList{
+            min: [0, 0, 0],
+            max: [1, 0, 0],
+            y_stride: 2,
+            z_stride: 2,
+            scale: 100,
+            geo: vec![BKCollisionMesh{tri_start: 0, size: 2}, BKCollisionMesh{tri_start: 2, size: 2}],
+            tri: vec![tri([0, 1, 2], 0), tri([1, 3, 2], 0), tri([2, 3, 4], 0x8000), tri([3, 5, 4], 0x8000)],
+        };
+        assert_eq!(list.cell([150.0, 20.0, 50.0]), [1, 0, 0]);
+        assert_eq!(list.cell_index([1, 0, 0]), Some(1));
+        assert_eq!(list.tris_in_box([-50.0; 3], [50.0; 3]), [0, 1]);
+
+        let floor = list.floor_height(&vertices, [25.0, 50.0, 25.0]).unwrap();
+        assert_eq!((floor.tri_index, floor.point[1], floor.distance), (0, 0.0, 50.0));
+        assert_eq!(floor.normal, [0.0, 1.0, 0.0]);
+        let ramp = list.floor_height(&vertices, [150.0, 200.0, 25.0]).unwrap();
+        assert_eq!((ramp.tri_index, ramp.point[1]), (2, 50.0));
+        assert!(list.floor_height(&vertices, [25.0, -10.0, 25.0]).is_none());
+
+        let hit = list.raycast(&vertices, [25.0, 50.0, 25.0], [175.0, 50.0, 25.0]).unwrap();
+        assert_eq!(hit.tri.flags, 0x8000);
+        assert!((hit.point[0] - 150.0).abs() < 1e-3 && (hit.distance - 125.0).abs() < 1e-3);
+        assert!(list.raycast(&vertices, [25.0, 50.0, 25.0], [75.0, 50.0, 25.0]).is_none());
+
+        let touching = list.sphere_overlaps(&vertices, [50.0, 5.0, 50.0], 10.0);
+        assert_eq!(touching.iter().map(|hit| hit.tri_index).collect::<Vec<_>>().len(), 2);
+        assert_eq!(touching[0].distance, 5.0);
+        assert!(list.sphere_overlaps(&vertices, [50.0, 20.0, 50.0], 10.0).is_empty());
+
+        //the floor under a downward facing overhang is still found, a raycast hits the overhang
+        let overhang = BKCollisionList{
+            min: [0, 0, 0],
+            max: [0, 0, 0],
+            y_stride: 1,
+            z_stride: 1,
+            scale: 100,
+            geo: vec![BKCollisionMesh{tri_start: 0, size: 3}],
+            tri: vec![tri([6, 7, 8], 0), tri([0, 1, 2], 0), tri([1, 3, 2], 0)],
+        };
+        let floor = overhang.floor_height(&vertices, [25.0, 80.0, 25.0]).unwrap();
+        assert_eq!((floor.tri_index, floor.point[1]), (1, 0.0));
+        let hit = overhang.raycast(&vertices, [25.0, 80.0, 25.0], [25.0, -100.0, 25.0]).unwrap();
+        assert_eq!((hit.tri_index, hit.normal), (0, [0.0, -1.0, 0.0]));
+    }
+}
@@ -3,13 +3,18 @@ use std::ops::{Deref, DerefMut};
 use std::fmt;
 use std::error::Error;
 use super::super::error::{*};
+use super::super::bktexture::{TmemDiagnostic, TmemIssue, TMEM_SIZE, TMEM_TLUT_OFFSET};
 
 pub struct BKGfxList{
     pub gfx:Vec<F3dex>,
-    header_filler: Option<[u8; 4]>, //used to preserve byte matching, maybe [0x00; 4] OR [0x0F; 4]
+    pub(crate) header_filler: Option<[u8; 4]>, //used to preserve byte matching, maybe [0x00; 4] OR [0x0F; 4]
 }
 
 impl BKGfxList{
+    pub fn new(gfx: Vec<F3dex>)->BKGfxList{
+        BKGfxList{gfx, header_filler: None}
+    }
+
     pub fn try_from_be_bytes(bytes: &[u8])->Result<BKGfxList, Box<dyn Error>>{
         let count = u32::from_be_bytes(bytes[0..4].try_into()?) as usize;
         let bytes = &bytes[0..8+8*count];
@@ -50,6 +55,68 @@ impl BKGfxList{
     }
 }
 
+/* TMEM loads
+    tracks SETTIMG/SETTILE state and checks every LOADBLOCK, LOADTILE and LOADTLUT
+    against the 4KB of TMEM. while a TLUT mode is set, texel loads must stay below
+    the palette half. sub display lists (G_DL) are not followed.
+*/
+impl BKGfxList{
+    pub fn validate_tmem(&self)->Vec<TmemDiagnostic>{
+        let mut diagnostics = Vec::new();
+        let mut timg_siz = 0;
+        let mut tile_tmem = [0usize; 8];
+        let mut tile_line = [0usize; 8];
+        let mut tlut = false;
+
+        for (i, cmd) in self.gfx.iter().enumerate() {
+            let cmd = u64::from(cmd.clone());
+            let (w0, w1) = ((cmd >> 32) as usize, (cmd & 0xFFFFFFFF) as usize);
+            let tile = (w1 >> 24) & 7;
+            let load = match w0 >> 24 {
+                0xFD => { timg_siz = (w0 >> 19) & 3; None }, //G_SETTIMG
+                0xF5 => { //G_SETTILE
+                    tile_tmem[tile] = 8*(w0 & 0x1FF);
+                    tile_line[tile] = 8*((w0 >> 9) & 0x1FF);
+                    None
+                },
+                0xBA => { //G_SETOTHERMODE_H, G_MDSFT_TEXTLUT = 14
+                    let (shift, len) = ((w0 >> 8) & 0xFF, w0 & 0xFF);
+                    if shift <= 14 && shift + len >= 16 { tlut = (w1 >> 14) & 3 != 0 }
+                    None
+                },
+                0xF3 => { //G_LOADBLOCK
+                    let texels = ((w1 >> 12) & 0xFFF) + 1;
+                    Some((tile_tmem[tile], (texels*(4 << timg_siz)).div_ceil(64)*8, false))
+                },
+                0xF4 => { //G_LOADTILE, coordinates are 10.2 fixed point
+                    let rows = ((w1 & 0xFFF).saturating_sub(w0 & 0xFFF) >> 2) + 1;
+                    Some((tile_tmem[tile], tile_line[tile]*rows, false))
+                },
+                0xF0 => { //G_LOADTLUT, every entry is quadrupled
+                    let count = ((w1 >> 14) & 0x3FF) + 1;
+                    Some((tile_tmem[tile], 8*count, true))
+                },
+                _ => None,
+            };
+
+            if let Some((start, size, is_tlut)) = load {
+                let end = start + size;
+                let issue = if end > TMEM_SIZE {
+                    Some(TmemIssue::OverBudget{used: end, limit: TMEM_SIZE})
+                } else if tlut && !is_tlut && end > TMEM_TLUT_OFFSET {
+                    Some(TmemIssue::OverlapsPalette{end})
+                } else {
+                    None
+                };
+                if let Some(issue) = issue {
+                    diagnostics.push(TmemDiagnostic{texture_index: None, gfx_index: Some(i), issue});
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
 impl Deref for BKGfxList{
     type Target = Vec<F3dex>;
     fn deref(&self) -> &Vec<F3dex> { &self.gfx }
@@ -66,3 +133,26 @@ impl fmt::Debug for BKGfxList {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gfx_list(cmds: &[u64])->BKGfxList{
+        BKGfxList{gfx: cmds.iter().map(|&cmd| F3dex::from(cmd)).collect(), header_filler: None}
+    }
+
+    #[test]
+    fn gfx_validate_tmem() {
+        //RGBA16 64x32 LoadBlock fills TMEM, 64x64 doesn't fit
+        let fits = gfx_list(&[0xFD100000_02000000, 0xF5100000_07000000, 0xF3000000_077FF100]);
+        assert!(fits.validate_tmem().is_empty());
+        let too_big = gfx_list(&[0xFD100000_02000000, 0xF5100000_07000000, 0xF3000000_07FFF100]);
+        assert_eq!(too_big.validate_tmem()[0].issue, TmemIssue::OverBudget{used: 0x2000, limit: TMEM_SIZE});
+        assert_eq!(too_big.validate_tmem()[0].gfx_index, Some(2));
+
+        //with TLUT mode set, a CI8 64x48 block runs into the palette
+        let ci = gfx_list(&[0xBA000E02_00008000, 0xFD100000_02000000, 0xF5100000_07000000, 0xF3000000_075FF100]);
+        assert_eq!(ci.validate_tmem()[0].issue, TmemIssue::OverlapsPalette{end: 0xC00});
+    }
+}
@@ -0,0 +1,367 @@
+use super::{BKGfxList, BKTextureList, BKVertexList};
+
+/* F3DEX display list interpreter
+    segment 0x01 points at the vertices of the model's BKVertexList,
+    segment 0x02 at the texture data of its BKTextureList,
+    segment 0x03 at the display list itself, G_DL into it is followed.
+    the vertex cache holds 32 entries, G_VTX/G_TRI index it in steps of 2.
+    texel and palette loads remember which texture they read from and where in
+    TMEM they went, a triangle's texture is the one loaded where its render tile
+    points (or the last one loaded when nothing was loaded there).
+    G_MTX, G_MODIFYVTX and G_CULLDL don't change what is drawn and are ignored.
+*/
+
+pub const VTX_SEGMENT : usize = 0x01;
+pub const TEXTURE_SEGMENT : usize = 0x02;
+pub const GFX_SEGMENT : usize = 0x03;
+pub const VTX_CACHE_SIZE : usize = 32;
+pub const GFX_STACK_SIZE : usize = 10; //nested G_DL calls
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BKTile{
+    pub format: u8,    //G_IM_FMT
+    pub size: u8,      //G_IM_SIZ
+    pub line: u16,     //in 64 bit words
+    pub tmem: u16,     //in 64 bit words
+    pub palette: u8,
+    pub cm: [u8; 2],   //clamp and mirror bits for s, t
+    pub mask: [u8; 2],
+    pub shift: [u8; 2],
+    pub ul: [u16; 2],  //10.2 fixed point, from G_SETTILESIZE
+    pub lr: [u16; 2],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BKMaterialState{
+    pub texture_index: Option<usize>, //into the BKTextureList, None while texturing is off
+    pub palette_index: Option<usize>, //texture whose palette the last G_LOADTLUT read
+    pub texture_scale: [f32; 2],      //from G_TEXTURE
+    pub tile: BKTile,                 //the render tile G_TEXTURE picked
+    pub geometry_mode: u32,
+    pub othermode_h: u32,
+    pub othermode_l: u32,
+    pub combine: u64,                 //G_SETCOMBINE without the opcode
+    pub prim_color: [u8; 4],
+    pub env_color: [u8; 4],
+}
+
+impl Default for BKMaterialState{
+    fn default()->Self{
+        BKMaterialState{
+            texture_index: None,
+            palette_index: None,
+            texture_scale: [1.0, 1.0],
+            tile: BKTile::default(),
+            geometry_mode: 0,
+            othermode_h: 0,
+            othermode_l: 0,
+            combine: 0,
+            prim_color: [0; 4],
+            env_color: [0; 4],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BKTriangle{
+    pub vertices: [usize; 3],      //indices into the BKVertexList
+    pub material: BKMaterialState, //state when the triangle was drawn
+    pub gfx_index: usize,          //the G_TRI command
+}
+
+impl BKTriangle{
+    //texture coordinates of one corner, normalised to the bound texture's size
+    pub fn uv(&self, corner: usize, vertices: &BKVertexList, textures: Option<&BKTextureList>)->[f32; 2]{
+        let tc = vertices.tex_coords(self.vertices[corner]);
+        let scale = self.material.texture_scale;
+        let (width, height) = self.material.texture_index
+            .and_then(|i| textures?.texture_headers.get(i))
+            .map_or((32, 32), |header| (header.width.max(1), header.height.max(1)));
+        [
+            tc[0] as f32/32.0*scale[0]/width as f32,
+            tc[1] as f32/32.0*scale[1]/height as f32,
+        ]
+    }
+}
+
+pub(crate) fn split_cmd(cmd: u64)->(u32, u32){
+    ((cmd >> 32) as u32, cmd as u32)
+}
+
+pub(crate) fn segment_offset(address: u32, segment: usize)->Option<usize>{
+    match (address >> 24) as usize == segment {
+        true => Some((address & 0xFFFFFF) as usize),
+        false => None,
+    }
+}
+
+//bits a G_SETOTHERMODE_H/L command writes, None when shift and length run past 32 bits
+pub(crate) fn othermode_mask(w0: u32)->Option<u32>{
+    let (shift, len) = ((w0 >> 8) & 0xFF, w0 & 0xFF);
+    (shift + len <= 32).then(|| (((1u64 << len) - 1) << shift) as u32)
+}
+
+enum Step{
+    Next,
+    End,
+    Call(usize),
+    Branch(usize),
+}
+
+pub struct BKGfxInterpreter<'a>{
+    gfx: &'a BKGfxList,
+    vertices: Option<&'a BKVertexList>,
+    textures: Option<&'a BKTextureList>,
+    cache: [Option<usize>; VTX_CACHE_SIZE],
+    texture_on: bool,
+    render_tile: usize,
+    tiles: [BKTile; 8],
+    timg: Option<usize>,
+    loads: Vec<(u16, usize)>, //(tmem, texture index) of every texel load
+    state: BKMaterialState,
+    steps: usize,
+    triangles: Vec<BKTriangle>,
+}
+
+impl<'a> BKGfxInterpreter<'a>{
+    //vertices, when given, limit G_VTX loads to the vertices that exist
+    pub fn new(gfx: &'a BKGfxList, vertices: Option<&'a BKVertexList>, textures: Option<&'a BKTextureList>)->Self{
+        BKGfxInterpreter{
+            gfx,
+            vertices,
+            textures,
+            cache: [None; VTX_CACHE_SIZE],
+            texture_on: true, //until a G_TEXTURE turns it off
+            render_tile: 0,
+            tiles: [BKTile::default(); 8],
+            timg: None,
+            loads: Vec::new(),
+            state: BKMaterialState::default(),
+            steps: 0,
+            triangles: Vec::new(),
+        }
+    }
+
+    //runs from gfx[start] to its G_ENDDL, following G_DL
+    pub fn run(mut self, start: usize)->Vec<BKTriangle>{
+        self.run_list(start, 0);
+        self.triangles
+    }
+
+    //runs every command in order, G_ENDDL and G_DL don't change the flow
+    pub fn run_all(mut self)->Vec<BKTriangle>{
+        for i in 0..self.gfx.len() {
+            self.step(i);
+        }
+        self.triangles
+    }
+
+    fn run_list(&mut self, start: usize, depth: usize){
+        let limit = self.gfx.len()*(GFX_STACK_SIZE + 1); //stops branch loops
+        let mut i = start;
+        while i < self.gfx.len() && self.steps < limit {
+            self.steps += 1;
+            match self.step(i) {
+                Step::Next => i += 1,
+                Step::End => return,
+                Step::Call(target) => {
+                    if depth + 1 < GFX_STACK_SIZE { self.run_list(target, depth + 1) }
+                    i += 1;
+                },
+                Step::Branch(target) => i = target,
+            }
+        }
+    }
+
+    fn step(&mut self, gfx_index: usize)->Step{
+        let (w0, w1) = split_cmd(u64::from(self.gfx[gfx_index].clone()));
+        let tile = |w: u32| ((w >> 24) & 7) as usize;
+        match w0 >> 24 {
+            0x04 => { //G_VTX
+                let count = ((w0 >> 10) & 0x3F) as usize;
+                let v0 = ((w0 >> 16) & 0xFF) as usize / 2;
+                let len = self.vertices.map_or(usize::MAX, |v| v.len());
+                if let Some(offset) = segment_offset(w1, VTX_SEGMENT) {
+                    for i in 0..count {
+                        let index = offset/0x10 + i;
+                        if let Some(slot) = self.cache.get_mut(v0 + i) { *slot = (index < len).then_some(index) }
+                    }
+                }
+            },
+            0xBF => self.triangle(gfx_index, w1), //G_TRI1
+            0xB1 => { self.triangle(gfx_index, w0); self.triangle(gfx_index, w1) }, //G_TRI2
+            0xBB => { //G_TEXTURE, 0xFFFF is 1.0
+                self.texture_on = w0 & 0xFF != 0;
+                self.render_tile = ((w0 >> 8) & 7) as usize;
+                let scale = |s: u32| if s == 0xFFFF { 1.0 } else { s as f32 / 65536.0 };
+                self.state.texture_scale = [scale(w1 >> 16), scale(w1 & 0xFFFF)];
+            },
+            0xB7 => self.state.geometry_mode |= w1,  //G_SETGEOMETRYMODE
+            0xB6 => self.state.geometry_mode &= !w1, //G_CLEARGEOMETRYMODE
+            0xBA | 0xB9 => if let Some(mask) = othermode_mask(w0) { //G_SETOTHERMODE_H, G_SETOTHERMODE_L
+                let mode = if w0 >> 24 == 0xBA { &mut self.state.othermode_h } else { &mut self.state.othermode_l };
+                *mode = (*mode & !mask) | (w1 & mask);
+            },
+            0xFC => self.state.combine = ((w0 as u64 & 0xFFFFFF) << 32) | w1 as u64, //G_SETCOMBINE
+            0xFA => self.state.prim_color = w1.to_be_bytes(), //G_SETPRIMCOLOR
+            0xFB => self.state.env_color = w1.to_be_bytes(),  //G_SETENVCOLOR
+            0xFD => self.timg = segment_offset(w1, TEXTURE_SEGMENT), //G_SETTIMG
+            0xF5 => { //G_SETTILE
+                let t = &mut self.tiles[tile(w1)];
+                t.format = ((w0 >> 21) & 7) as u8;
+                t.size = ((w0 >> 19) & 3) as u8;
+                t.line = ((w0 >> 9) & 0x1FF) as u16;
+                t.tmem = (w0 & 0x1FF) as u16;
+                t.palette = ((w1 >> 20) & 0xF) as u8;
+                t.cm = [((w1 >> 8) & 3) as u8, ((w1 >> 18) & 3) as u8];
+                t.mask = [((w1 >> 4) & 0xF) as u8, ((w1 >> 14) & 0xF) as u8];
+                t.shift = [(w1 & 0xF) as u8, ((w1 >> 10) & 0xF) as u8];
+            },
+            0xF2 => { //G_SETTILESIZE
+                let t = &mut self.tiles[tile(w1)];
+                t.ul = [((w0 >> 12) & 0xFFF) as u16, (w0 & 0xFFF) as u16];
+                t.lr = [((w1 >> 12) & 0xFFF) as u16, (w1 & 0xFFF) as u16];
+            },
+            0xF3 | 0xF4 => { //G_LOADBLOCK, G_LOADTILE
+                if let Some(texture) = self.timg.and_then(|offset| self.textures?.texture_at(offset)) {
+                    self.loads.push((self.tiles[tile(w1)].tmem, texture));
+                }
+            },
+            0xF0 => self.state.palette_index = self.timg.and_then(|offset| self.textures?.texture_at(offset)), //G_LOADTLUT
+            0x06 => { //G_DL, 0x01 in the second byte doesn't push a return
+                if let Some(target) = segment_offset(w1, GFX_SEGMENT).map(|offset| offset/8) {
+                    return match (w0 >> 16) & 0xFF {
+                        0 => Step::Call(target),
+                        _ => Step::Branch(target),
+                    }
+                }
+            },
+            0xB8 => return Step::End, //G_ENDDL
+            _ => {},
+        }
+        Step::Next
+    }
+
+    fn material(&self)->BKMaterialState{
+        let tile = self.tiles[self.render_tile];
+        let texture_index = match self.texture_on {
+            true => self.loads.iter().rev()
+                .find(|(tmem, _)| *tmem == tile.tmem)
+                .or(self.loads.last())
+                .map(|&(_, texture)| texture),
+            false => None,
+        };
+        BKMaterialState{texture_index, tile, ..self.state.clone()}
+    }
+
+    fn triangle(&mut self, gfx_index: usize, w: u32){
+        let slot = |shift: u32| self.cache.get(((w >> shift) & 0xFF) as usize / 2).cloned().flatten();
+        if let (Some(a), Some(b), Some(c)) = (slot(16), slot(8), slot(0)) {
+            self.triangles.push(BKTriangle{vertices: [a, b, c], material: self.material(), gfx_index});
+        }
+    }
+}
+
+impl BKGfxList{
+    //every triangle in the list, G_ENDDL doesn't stop the walk
+    pub fn triangles(&self, textures: Option<&BKTextureList>)->Vec<BKTriangle>{
+        BKGfxInterpreter::new(self, None, textures).run_all()
+    }
+
+    //triangles from gfx[start] up to the next G_ENDDL
+    pub fn triangles_from(&self, start: usize, textures: Option<&BKTextureList>)->Vec<BKTriangle>{
+        BKGfxInterpreter::new(self, None, textures).run(start)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use libultra::{F3dex, Vtx};
+    use super::super::BKModel;
+    use super::super::super::bktexture::{BKTextureFormat, BKTextureHeader};
+
+    //2x2 I8 texture on a quad of two triangles
+    pub(crate) fn test_model()->BKModel{
+        let vtx = |x: i16, y: i16, s: i16, t: i16| {
+            let mut bytes = [0u8; 16];
+            bytes[0..2].copy_from_slice(&x.to_be_bytes());
+            bytes[2..4].copy_from_slice(&y.to_be_bytes());
+            bytes[8..10].copy_from_slice(&s.to_be_bytes());
+            bytes[10..12].copy_from_slice(&t.to_be_bytes());
+            bytes[12..16].copy_from_slice(&[0xFF, 0x80, 0x40, 0xFF]);
+            Vtx::from_be_bytes(&bytes)
+        };
+        BKModel{
+            texture_list: Some(BKTextureList{
+                texture_headers: vec![BKTextureHeader{offset: 0, format: BKTextureFormat::I8, flags: 0, level_count: 0, width: 2, height: 2, padding: [0; 6]}],
+                texture_data: vec![0x00, 0x40, 0x80, 0xFF],
+            }),
+            vertices: Some(BKVertexList::new(vec![vtx(0, 0, 0, 0), vtx(100, 0, 64, 0), vtx(0, 100, 0, 64), vtx(100, 100, 64, 64)])),
+            display_list: Some(BKGfxList::new([
+                0xFD500000_02000000u64, //G_SETTIMG
+                0xF3000000_07003000,    //G_LOADBLOCK
+                0x0400103F_01000000,    //G_VTX 4
+                0xB1000204_00020604,    //G_TRI2
+                0xB8000000_00000000,    //G_ENDDL
+            ].into_iter().map(F3dex::from).collect())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn f3dex_triangles() {
+        let model = test_model();
+        let triangles = model.triangles();
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles[0].0.vertices, [0, 1, 2]);
+        assert_eq!(triangles[1].0.vertices, [1, 3, 2]);
+        assert_eq!(triangles[0].0.material.texture_index, Some(0));
+        assert_eq!(triangles[0].0.uv(1, model.vertices.as_ref().unwrap(), model.texture_list.as_ref()), [1.0, 0.0]);
+        assert_eq!(model.display_list.as_ref().unwrap().triangles_from(4, None), Vec::new());
+    }
+
+    #[test]
+    fn f3dex_interpreter_state() {
+        let model = test_model();
+        let source = model.vertices.as_ref().unwrap();
+        let three = BKVertexList::new((0..3).map(|i| Vtx::from_be_bytes(&source[i].to_bytes())).collect());
+        let gfx = BKGfxList::new([
+            0x06000000_03000038u64, //G_DL 7
+            0xB7000000_00000204,    //G_SETGEOMETRYMODE
+            0x0400103F_01000000,    //G_VTX 4
+            0xB1000204_00020604,    //G_TRI2, vertex 3 isn't in the list
+            0xBB000000_FFFFFFFF,    //G_TEXTURE off
+            0xBF000000_00000204,    //G_TRI1
+            0xB8000000_00000000,    //G_ENDDL
+            0xFC121824_FF33FFFF,    //G_SETCOMBINE
+            0xFA000000_11223344,    //G_SETPRIMCOLOR
+            0xFD500000_02000000,    //G_SETTIMG
+            0xF3000000_07003000,    //G_LOADBLOCK
+            0xBA000E02_00008000,    //G_SETOTHERMODE_H TEXTLUT
+            0xB8000000_00000000,    //G_ENDDL
+        ].into_iter().map(F3dex::from).collect());
+
+        let triangles = BKGfxInterpreter::new(&gfx, Some(&three), model.texture_list.as_ref()).run(0);
+        assert_eq!(triangles.len(), 2);
+        let material = &triangles[0].material;
+        assert_eq!(material.texture_index, Some(0));
+        assert_eq!(material.geometry_mode, 0x204);
+        assert_eq!(material.othermode_h, 0x8000);
+        assert_eq!(material.combine, 0x121824_FF33FFFF);
+        assert_eq!(material.prim_color, [0x11, 0x22, 0x33, 0x44]);
+        assert_eq!(triangles[1].material.texture_index, None);
+        assert_eq!(triangles[1].gfx_index, 5);
+
+        //a branch back to the start stops once the step limit is hit
+        let gfx = BKGfxList::new([0x0400103F_01000000u64, 0xBF000000_00000204, 0x06010000_03000000].into_iter().map(F3dex::from).collect());
+        assert!(!BKGfxInterpreter::new(&gfx, None, None).run(0).is_empty());
+
+        //othermode shifts past 32 bits are skipped like unknown commands
+        assert_eq!(othermode_mask(0xBA000E02), Some(0xC000));
+        assert_eq!(othermode_mask(0xB9000020), Some(u32::MAX));
+        assert_eq!(othermode_mask(0xBA0000FF), None);
+        assert_eq!(othermode_mask(0xBA00FF01), None);
+        assert!(BKGfxList::new([0xBA0000FF_00000000u64, 0xB8000000_00000000].into_iter().map(F3dex::from).collect()).triangles(None).is_empty());
+    }
+}
@@ -0,0 +1,420 @@
+use std::fmt::Write;
+
+use libultra::F3dex;
+
+use super::BKGfxList;
+use super::f3dex::{split_cmd, VTX_SEGMENT, TEXTURE_SEGMENT, GFX_SEGMENT};
+use super::render_mode::{render_preset, BKCombiner, BKRenderMode};
+use super::super::error::GbiSyntaxError;
+
+/* GBI text
+    one F3DEX command per line as its gbi.h static macro, e.g.
+        gsSPVertex(VTX(0), 4, 0)
+        gsSP2Triangles(0, 1, 2, 0, 1, 3, 2, 0)
+    addresses in the vertex, texture and display list segments are written as
+    VTX(vertex index), TEX(byte offset) and GFX(command index).
+    a command that doesn't assemble back to the same 64 bits is written as
+    gsRaw(w0, w1), so text always assembles to the original list.
+    a non zero list header filler is kept as gsHeaderFiller(bytes).
+    // comments, blank lines and trailing commas are ignored by the assembler.
+*/
+
+const SYMBOLS : [(&str, u64); 43] = [
+    ("G_ON", 1), ("G_OFF", 0),
+    ("G_TX_RENDERTILE", 0), ("G_TX_LOADTILE", 7),
+    ("G_TX_WRAP", 0), ("G_TX_MIRROR", 1), ("G_TX_CLAMP", 2), ("G_TX_NOMASK", 0), ("G_TX_NOLOD", 0),
+    ("G_IM_FMT_RGBA", 0), ("G_IM_FMT_YUV", 1), ("G_IM_FMT_CI", 2), ("G_IM_FMT_IA", 3), ("G_IM_FMT_I", 4),
+    ("G_IM_SIZ_4b", 0), ("G_IM_SIZ_8b", 1), ("G_IM_SIZ_16b", 2), ("G_IM_SIZ_32b", 3),
+    ("G_SETOTHERMODE_H", 0xBA), ("G_SETOTHERMODE_L", 0xB9),
+    ("G_MTX_MODELVIEW", 0), ("G_MTX_PROJECTION", 1), ("G_MTX_MUL", 0), ("G_MTX_LOAD", 2), ("G_MTX_NOPUSH", 0), ("G_MTX_PUSH", 4),
+    ("G_ZBUFFER", 0x1), ("G_SHADE", 0x4), ("G_SHADING_SMOOTH", 0x200), ("G_CULL_FRONT", 0x1000), ("G_CULL_BACK", 0x2000),
+    ("G_FOG", 0x10000), ("G_LIGHTING", 0x20000), ("G_TEXTURE_GEN", 0x40000), ("G_TEXTURE_GEN_LINEAR", 0x80000),
+    ("G_LOD", 0x100000), ("G_CLIPPING", 0x800000),
+    ("G_TT_NONE", 0), ("G_TT_RGBA16", 0x8000), ("G_TT_IA16", 0xC000),
+    ("G_AC_NONE", 0), ("G_AC_THRESHOLD", 1), ("G_AC_DITHER", 3),
+];
+
+const GEOMETRY_FLAGS : [&str; 11] = [
+    "G_ZBUFFER", "G_SHADE", "G_SHADING_SMOOTH", "G_CULL_FRONT", "G_CULL_BACK", "G_FOG",
+    "G_LIGHTING", "G_TEXTURE_GEN", "G_TEXTURE_GEN_LINEAR", "G_LOD", "G_CLIPPING",
+];
+const IMAGE_FORMATS : [&str; 5] = ["G_IM_FMT_RGBA", "G_IM_FMT_YUV", "G_IM_FMT_CI", "G_IM_FMT_IA", "G_IM_FMT_I"];
+const IMAGE_SIZES : [&str; 4] = ["G_IM_SIZ_4b", "G_IM_SIZ_8b", "G_IM_SIZ_16b", "G_IM_SIZ_32b"];
+const CLAMP_MODES : [&str; 4] = ["G_TX_WRAP", "G_TX_MIRROR", "G_TX_CLAMP", "G_TX_MIRROR | G_TX_CLAMP"];
+
+//single field G_SETOTHERMODE_H/L macros: (opcode, name, shift, len, named values)
+type OtherMode = (u32, &'static str, u32, u32, &'static [(&'static str, u32)]);
+const OTHER_MODES : [OtherMode; 6] = [
+    (0xBA, "gsDPSetCycleType", 20, 2, &[("G_CYC_1CYCLE", 0), ("G_CYC_2CYCLE", 0x100000), ("G_CYC_COPY", 0x200000), ("G_CYC_FILL", 0x300000)]),
+    (0xBA, "gsDPSetTexturePersp", 19, 1, &[("G_TP_NONE", 0), ("G_TP_PERSP", 0x80000)]),
+    (0xBA, "gsDPSetTextureLUT", 14, 2, &[("G_TT_NONE", 0), ("G_TT_RGBA16", 0x8000), ("G_TT_IA16", 0xC000)]),
+    (0xBA, "gsDPSetTextureFilter", 12, 2, &[("G_TF_POINT", 0), ("G_TF_BILERP", 0x2000), ("G_TF_AVERAGE", 0x3000)]),
+    (0xB9, "gsDPSetAlphaCompare", 0, 2, &[("G_AC_NONE", 0), ("G_AC_THRESHOLD", 1), ("G_AC_DITHER", 3)]),
+    (0xB9, "gsDPSetDepthSource", 2, 1, &[("G_ZS_PIXEL", 0), ("G_ZS_PRIM", 4)]),
+];
+
+/* colour combiner inputs by slot, gbi.h G_CCMUX_ and G_ACMUX_ names without the prefix.
+    several codes mean 0, the last one is the one the assembler writes.
+*/
+pub(crate) const CC_A : [&str; 16] = ["COMBINED", "TEXEL0", "TEXEL1", "PRIMITIVE", "SHADE", "ENVIRONMENT", "1", "NOISE", "0", "0", "0", "0", "0", "0", "0", "0"];
+pub(crate) const CC_B : [&str; 16] = ["COMBINED", "TEXEL0", "TEXEL1", "PRIMITIVE", "SHADE", "ENVIRONMENT", "CENTER", "K4", "0", "0", "0", "0", "0", "0", "0", "0"];
+pub(crate) const CC_C : [&str; 32] = [
+    "COMBINED", "TEXEL0", "TEXEL1", "PRIMITIVE", "SHADE", "ENVIRONMENT", "SCALE", "COMBINED_ALPHA",
+    "TEXEL0_ALPHA", "TEXEL1_ALPHA", "PRIMITIVE_ALPHA", "SHADE_ALPHA", "ENV_ALPHA", "LOD_FRACTION", "PRIM_LOD_FRAC", "K5",
+    "0", "0", "0", "0", "0", "0", "0", "0", "0", "0", "0", "0", "0", "0", "0", "0",
+];
+pub(crate) const CC_D : [&str; 8] = ["COMBINED", "TEXEL0", "TEXEL1", "PRIMITIVE", "SHADE", "ENVIRONMENT", "1", "0"];
+pub(crate) const AC_ABD : [&str; 8] = ["COMBINED", "TEXEL0", "TEXEL1", "PRIMITIVE", "SHADE", "ENVIRONMENT", "1", "0"];
+pub(crate) const AC_C : [&str; 8] = ["LOD_FRACTION", "TEXEL0", "TEXEL1", "PRIMITIVE", "SHADE", "ENVIRONMENT", "PRIM_LOD_FRAC", "0"];
+
+//gsDPSetCombineLERP argument order: (table, word, shift)
+const COMBINE_FIELDS : [(&[&str], u8, u32); 16] = [
+    (&CC_A, 0, 20), (&CC_B, 1, 28), (&CC_C, 0, 15), (&CC_D, 1, 15),
+    (&AC_ABD, 0, 12), (&AC_ABD, 1, 12), (&AC_C, 0, 9), (&AC_ABD, 1, 9),
+    (&CC_A, 0, 5), (&CC_B, 1, 24), (&CC_C, 0, 0), (&CC_D, 1, 6),
+    (&AC_ABD, 1, 21), (&AC_ABD, 1, 3), (&AC_C, 1, 18), (&AC_ABD, 1, 0),
+];
+
+//the 16 combiner inputs of both cycles, in gsDPSetCombineLERP order
+pub(crate) fn combine_fields(w0: u32, w1: u32)->[usize; 16]{
+    COMBINE_FIELDS.map(|(table, word, shift)| ((if word == 0 { w0 } else { w1 } >> shift) as usize) & (table.len() - 1))
+}
+
+//w0, w1 of a G_SETCOMBINE from its 16 inputs
+pub(crate) fn combine_words(fields: &[usize; 16])->(u32, u32){
+    let mut words = [0xFC000000u32, 0];
+    for (&(table, word, shift), &value) in COMBINE_FIELDS.iter().zip(fields.iter()) {
+        words[word as usize] |= ((value & (table.len() - 1)) as u32) << shift;
+    }
+    (words[0], words[1])
+}
+
+//code of an input name, the last code for names with several
+pub(crate) fn combine_input(table: &[&str], name: &str)->Option<usize>{
+    table.iter().rposition(|&n| n == name)
+}
+
+fn address(w1: u32)->String{
+    let offset = (w1 & 0xFFFFFF) as usize;
+    match (w1 >> 24) as usize {
+        VTX_SEGMENT if offset & 0xF == 0 => format!("VTX({})", offset/0x10),
+        TEXTURE_SEGMENT => format!("TEX(0x{:X})", offset),
+        GFX_SEGMENT if offset & 7 == 0 => format!("GFX({})", offset/8),
+        _ => format!("0x{:08X}", w1),
+    }
+}
+
+fn flags(value: u32, names: &[&str])->String{
+    let mut rest = value;
+    let mut parts : Vec<String> = Vec::new();
+    for name in names {
+        let bits = symbol(name).unwrap_or(0) as u32;
+        if bits != 0 && rest & bits == bits {
+            parts.push(name.to_string());
+            rest &= !bits;
+        }
+    }
+    if rest != 0 || parts.is_empty() { parts.push(format!("0x{:X}", rest)) }
+    parts.join(" | ")
+}
+
+fn tile(t: u32)->String{
+    match t {
+        0 => "G_TX_RENDERTILE".to_string(),
+        7 => "G_TX_LOADTILE".to_string(),
+        _ => t.to_string(),
+    }
+}
+
+fn symbol(name: &str)->Option<u64>{
+    SYMBOLS.iter().find(|(n, _)| *n == name).map(|&(_, v)| v)
+        .or_else(|| OTHER_MODES.iter().flat_map(|m| m.4.iter()).find(|(n, _)| *n == name).map(|&(_, v)| v as u64))
+        .or_else(|| render_preset(name).map(u64::from))
+}
+
+fn number(text: &str)->Option<u64>{
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+//an argument, terms joined with | are or'd
+fn value(arg: &str)->Option<u64>{
+    arg.split('|').map(str::trim).try_fold(0u64, |acc, term| {
+        let inner = |prefix: &str| term.strip_prefix(prefix).and_then(|t| t.strip_suffix(')')).and_then(|t| number(t.trim()));
+        let v = if let Some(i) = inner("VTX(") {
+            ((VTX_SEGMENT as u64) << 24) + 0x10*i
+        } else if let Some(offset) = inner("TEX(") {
+            ((TEXTURE_SEGMENT as u64) << 24) + offset
+        } else if let Some(i) = inner("GFX(") {
+            ((GFX_SEGMENT as u64) << 24) + 8*i
+        } else {
+            number(term).or_else(|| symbol(term))?
+        };
+        Some(acc | v)
+    })
+}
+
+//gbi macro text of one command, None for anything without one
+fn disassemble(cmd: u64)->Option<String>{
+    let (w0, w1) = split_cmd(cmd);
+    let tri = |w: u32| format!("{}, {}, {}", ((w >> 16) & 0xFF)/2, ((w >> 8) & 0xFF)/2, (w & 0xFF)/2);
+    let color = |w: u32| { let c = w.to_be_bytes(); format!("0x{:02X}, 0x{:02X}, 0x{:02X}, 0x{:02X}", c[0], c[1], c[2], c[3]) };
+    let coords = |w: u32| format!("{}, {}", (w >> 12) & 0xFFF, w & 0xFFF);
+    Some(match w0 >> 24 {
+        0x00 => "gsSPNoOp()".to_string(),
+        0x01 => format!("gsSPMatrix({}, 0x{:X})", address(w1), (w0 >> 16) & 0xFF),
+        0x04 => format!("gsSPVertex({}, {}, {})", address(w1), (w0 >> 10) & 0x3F, ((w0 >> 16) & 0xFF)/2),
+        0x06 => match (w0 >> 16) & 0xFF {
+            0 => format!("gsSPDisplayList({})", address(w1)),
+            _ => format!("gsSPBranchList({})", address(w1)),
+        },
+        0xB1 => format!("gsSP2Triangles({}, 0, {}, 0)", tri(w0), tri(w1)),
+        0xB2 => format!("gsSPModifyVertex({}, 0x{:X}, 0x{:08X})", (w0 & 0xFFFF)/2, (w0 >> 16) & 0xFF, w1),
+        0xB6 => format!("gsSPClearGeometryMode({})", flags(w1, &GEOMETRY_FLAGS)),
+        0xB7 => format!("gsSPSetGeometryMode({})", flags(w1, &GEOMETRY_FLAGS)),
+        0xB8 => "gsSPEndDisplayList()".to_string(),
+        0xB9 | 0xBA => {
+            let (shift, len) = ((w0 >> 8) & 0xFF, w0 & 0xFF);
+            let named = OTHER_MODES.iter()
+                .find(|m| m.0 == w0 >> 24 && m.2 == shift && m.3 == len)
+                .and_then(|m| m.4.iter().find(|(_, v)| *v == w1).map(|(n, _)| format!("{}({})", m.1, n)));
+            let render_mode = (w0 == 0xB900031D).then(|| BKRenderMode::from_othermode_l(w1).presets()).flatten()
+                .map(|(a, b)| format!("gsDPSetRenderMode({}, {})", a, b));
+            let cmd = if w0 >> 24 == 0xBA { "G_SETOTHERMODE_H" } else { "G_SETOTHERMODE_L" };
+            named.or(render_mode).unwrap_or_else(|| format!("gsSPSetOtherMode({}, {}, {}, 0x{:08X})", cmd, shift, len, w1))
+        },
+        0xBB => format!("gsSPTexture(0x{:04X}, 0x{:04X}, {}, {}, {})",
+            w1 >> 16, w1 & 0xFFFF, (w0 >> 11) & 7, tile((w0 >> 8) & 7), if w0 & 0xFF == 0 { "G_OFF" } else { "G_ON" }),
+        0xBC => format!("gsMoveWd(0x{:X}, 0x{:X}, 0x{:08X})", w0 & 0xFF, (w0 >> 8) & 0xFFFF, w1),
+        0xBD => format!("gsSPPopMatrix({})", w1),
+        0xBE => format!("gsSPCullDisplayList({}, {})", (w0 & 0xFFFF)/2, (w1 & 0xFFFF)/2),
+        0xBF => format!("gsSP1Triangle({}, 0)", tri(w1)),
+        0xC0 => "gsDPNoOp()".to_string(),
+        0xE6 => "gsDPLoadSync()".to_string(),
+        0xE7 => "gsDPPipeSync()".to_string(),
+        0xE8 => "gsDPTileSync()".to_string(),
+        0xE9 => "gsDPFullSync()".to_string(),
+        0xF0 => format!("gsDPLoadTLUTCmd({}, {})", tile((w1 >> 24) & 7), (w1 >> 14) & 0x3FF),
+        0xF2 => format!("gsDPSetTileSize({}, {}, {})", tile((w1 >> 24) & 7), coords(w0), coords(w1)),
+        0xF3 => format!("gsDPLoadBlock({}, {}, {}, {})", tile((w1 >> 24) & 7), coords(w0), (w1 >> 12) & 0xFFF, w1 & 0xFFF),
+        0xF4 => format!("gsDPLoadTile({}, {}, {})", tile((w1 >> 24) & 7), coords(w0), coords(w1)),
+        0xF5 => format!("gsDPSetTile({}, {}, {}, 0x{:X}, {}, {}, {}, {}, {}, {}, {}, {})",
+            IMAGE_FORMATS.get(((w0 >> 21) & 7) as usize)?, IMAGE_SIZES[((w0 >> 19) & 3) as usize], (w0 >> 9) & 0x1FF, w0 & 0x1FF,
+            tile((w1 >> 24) & 7), (w1 >> 20) & 0xF,
+            CLAMP_MODES[((w1 >> 18) & 3) as usize], (w1 >> 14) & 0xF, (w1 >> 10) & 0xF,
+            CLAMP_MODES[((w1 >> 8) & 3) as usize], (w1 >> 4) & 0xF, w1 & 0xF),
+        0xF7 => format!("gsDPSetFillColor(0x{:08X})", w1),
+        0xF8 => format!("gsDPSetFogColor({})", color(w1)),
+        0xF9 => format!("gsDPSetBlendColor({})", color(w1)),
+        0xFA => format!("gsDPSetPrimColor({}, {}, {})", (w0 >> 8) & 0xFF, w0 & 0xFF, color(w1)),
+        0xFB => format!("gsDPSetEnvColor({})", color(w1)),
+        0xFC => {
+            if let Some((a, b)) = BKCombiner::from_cmd(cmd).and_then(|c| c.presets()) {
+                return Some(format!("gsDPSetCombineMode({}, {})", a, b))
+            }
+            let fields = combine_fields(w0, w1);
+            let names : Vec<&str> = COMBINE_FIELDS.iter().zip(fields.iter()).map(|(&(table, _, _), &v)| table[v]).collect();
+            format!("gsDPSetCombineLERP({})", names.join(", "))
+        },
+        0xFD => format!("gsDPSetTextureImage({}, {}, {}, {})",
+            IMAGE_FORMATS.get(((w0 >> 21) & 7) as usize)?, IMAGE_SIZES[((w0 >> 19) & 3) as usize], (w0 & 0xFFF) + 1, address(w1)),
+        _ => return None,
+    })
+}
+
+//64 bit command of one macro
+fn assemble(name: &str, args: &[&str])->Option<u64>{
+    if name == "gsDPSetCombineLERP" {
+        if args.len() != 16 { return None }
+        let mut fields = [0; 16];
+        for (i, (&(table, _, _), arg)) in COMBINE_FIELDS.iter().zip(args.iter()).enumerate() {
+            fields[i] = combine_input(table, arg)?;
+        }
+        let (w0, w1) = combine_words(&fields);
+        return Some(((w0 as u64) << 32) | w1 as u64)
+    }
+    if name == "gsDPSetCombineMode" {
+        return match args {
+            [a, b] => BKCombiner::from_presets(a, b)?.to_cmd(),
+            _ => None,
+        }
+    }
+    if let Some(mode) = OTHER_MODES.iter().find(|m| m.1 == name) {
+        let data = value(args.first()?)?;
+        return Some((mode.0 as u64) << 56 | (mode.2 as u64) << 40 | (mode.3 as u64) << 32 | data)
+    }
+
+    let a = args.iter().map(|arg| value(arg)).collect::<Option<Vec<u64>>>()?;
+    let words = |w0: u64, w1: u64| Some((w0 & 0xFFFFFFFF) << 32 | (w1 & 0xFFFFFFFF));
+    let tri = |a: &[u64]| ((a[0]*2) & 0xFF) << 16 | ((a[1]*2) & 0xFF) << 8 | ((a[2]*2) & 0xFF);
+    let color = |a: &[u64]| (a[0] & 0xFF) << 24 | (a[1] & 0xFF) << 16 | (a[2] & 0xFF) << 8 | (a[3] & 0xFF);
+    let coords = |s: u64, t: u64| (s & 0xFFF) << 12 | (t & 0xFFF);
+    let count = match name {
+        "gsSPNoOp" | "gsSPEndDisplayList" | "gsDPNoOp" | "gsDPLoadSync" | "gsDPPipeSync" | "gsDPTileSync" | "gsDPFullSync" => 0,
+        "gsSPDisplayList" | "gsSPBranchList" | "gsSPClearGeometryMode" | "gsSPSetGeometryMode" | "gsSPPopMatrix"
+            | "gsDPSetFillColor" | "gsHeaderFiller" => 1,
+        "gsRaw" | "gsSPMatrix" | "gsSPCullDisplayList" | "gsDPLoadTLUTCmd" | "gsDPSetRenderMode" => 2,
+        "gsSPVertex" | "gsSPModifyVertex" | "gsMoveWd" => 3,
+        "gsSP1Triangle" | "gsDPSetFogColor" | "gsDPSetBlendColor" | "gsDPSetEnvColor" | "gsSPSetOtherMode" | "gsDPSetTextureImage" => 4,
+        "gsSPTexture" | "gsDPSetTileSize" | "gsDPLoadBlock" | "gsDPLoadTile" => 5,
+        "gsDPSetPrimColor" => 6,
+        "gsSP2Triangles" => 8,
+        "gsDPSetTile" => 12,
+        _ => return None,
+    };
+    if a.len() != count { return None }
+    match name {
+        "gsRaw" => words(a[0], a[1]),
+        "gsSPNoOp" => words(0, 0),
+        "gsSPMatrix" => words(0x01000040 | (a[1] & 0xFF) << 16, a[0]),
+        "gsSPVertex" => words(0x04000000 | ((a[2]*2) & 0xFF) << 16 | (a[1] & 0x3F) << 10 | (0x10*a[1]).wrapping_sub(1) & 0x3FF, a[0]),
+        "gsSPDisplayList" => words(0x06000000, a[0]),
+        "gsSPBranchList" => words(0x06010000, a[0]),
+        "gsSP2Triangles" => words(0xB1000000 | tri(&a[0..3]), tri(&a[4..7])),
+        "gsSPModifyVertex" => words(0xB2000000 | (a[1] & 0xFF) << 16 | ((a[0]*2) & 0xFFFF), a[2]),
+        "gsSPClearGeometryMode" => words(0xB6000000, a[0]),
+        "gsSPSetGeometryMode" => words(0xB7000000, a[0]),
+        "gsSPEndDisplayList" => words(0xB8000000, 0),
+        "gsDPSetRenderMode" => words(0xB900031D, a[0] | a[1]),
+        "gsSPSetOtherMode" => words((a[0] & 0xFF) << 24 | (a[1] & 0xFF) << 8 | (a[2] & 0xFF), a[3]),
+        "gsSPTexture" => words(0xBB000000 | (a[2] & 7) << 11 | (a[3] & 7) << 8 | (a[4] & 0xFF), (a[0] & 0xFFFF) << 16 | (a[1] & 0xFFFF)),
+        "gsMoveWd" => words(0xBC000000 | (a[1] & 0xFFFF) << 8 | (a[0] & 0xFF), a[2]),
+        "gsSPPopMatrix" => words(0xBD000000, a[0]),
+        "gsSPCullDisplayList" => words(0xBE000000 | ((a[0]*2) & 0xFFFF), (a[1]*2) & 0xFFFF),
+        "gsSP1Triangle" => words(0xBF000000, tri(&a[0..3])),
+        "gsDPNoOp" => words(0xC0000000, 0),
+        "gsDPLoadSync" => words(0xE6000000, 0),
+        "gsDPPipeSync" => words(0xE7000000, 0),
+        "gsDPTileSync" => words(0xE8000000, 0),
+        "gsDPFullSync" => words(0xE9000000, 0),
+        "gsDPLoadTLUTCmd" => words(0xF0000000, (a[0] & 7) << 24 | (a[1] & 0x3FF) << 14),
+        "gsDPSetTileSize" => words(0xF2000000 | coords(a[1], a[2]), (a[0] & 7) << 24 | coords(a[3], a[4])),
+        "gsDPLoadBlock" => words(0xF3000000 | coords(a[1], a[2]), (a[0] & 7) << 24 | coords(a[3], a[4])),
+        "gsDPLoadTile" => words(0xF4000000 | coords(a[1], a[2]), (a[0] & 7) << 24 | coords(a[3], a[4])),
+        "gsDPSetTile" => words(
+            0xF5000000 | (a[0] & 7) << 21 | (a[1] & 3) << 19 | (a[2] & 0x1FF) << 9 | (a[3] & 0x1FF),
+            (a[4] & 7) << 24 | (a[5] & 0xF) << 20 | (a[6] & 3) << 18 | (a[7] & 0xF) << 14 | (a[8] & 0xF) << 10
+                | (a[9] & 3) << 8 | (a[10] & 0xF) << 4 | (a[11] & 0xF),
+        ),
+        "gsDPSetFillColor" => words(0xF7000000, a[0]),
+        "gsDPSetFogColor" => words(0xF8000000, color(&a)),
+        "gsDPSetBlendColor" => words(0xF9000000, color(&a)),
+        "gsDPSetPrimColor" => words(0xFA000000 | (a[0] & 0xFF) << 8 | (a[1] & 0xFF), color(&a[2..6])),
+        "gsDPSetEnvColor" => words(0xFB000000, color(&a)),
+        "gsDPSetTextureImage" => words(0xFD000000 | (a[0] & 7) << 21 | (a[1] & 3) << 19 | (a[2].wrapping_sub(1) & 0xFFF), a[3]),
+        _ => None,
+    }
+}
+
+//(macro name, arguments) of a line, None for lines without a command
+fn split_line(line: &str)->Result<Option<(&str, Vec<&str>)>, ()>{
+    let line = line.split("//").next().unwrap_or("").trim().trim_end_matches(',').trim_end();
+    if line.is_empty() { return Ok(None) }
+    let open = line.find('(').ok_or(())?;
+    let inner = line[open + 1 ..].strip_suffix(')').ok_or(())?;
+    let mut args = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => { args.push(inner[start .. i].trim()); start = i + 1 },
+            _ => {},
+        }
+    }
+    if !inner.trim().is_empty() { args.push(inner[start ..].trim()) }
+    Ok(Some((line[..open].trim(), args)))
+}
+
+impl BKGfxList{
+    pub fn to_gbi_text(&self)->String{
+        let mut out = String::new();
+        if let Some(filler) = self.header_filler.filter(|f| *f != [0; 4]) {
+            writeln!(out, "gsHeaderFiller(0x{:08X})", u32::from_be_bytes(filler)).unwrap();
+        }
+        for cmd in self.gfx.iter() {
+            let cmd = u64::from(cmd.clone());
+            let text = disassemble(cmd).filter(|text| {
+                split_line(text).ok().flatten().and_then(|(name, args)| assemble(name, &args)) == Some(cmd)
+            });
+            match text {
+                Some(text) => writeln!(out, "{}", text).unwrap(),
+                None => writeln!(out, "gsRaw(0x{:08X}, 0x{:08X})", cmd >> 32, cmd & 0xFFFFFFFF).unwrap(),
+            }
+        }
+        out
+    }
+
+    pub fn from_gbi_text(text: &str)->Result<BKGfxList, GbiSyntaxError>{
+        let mut gfx = BKGfxList::new(Vec::new());
+        for (i, line) in text.lines().enumerate() {
+            let error = GbiSyntaxError{line: i + 1};
+            let (name, args) = match split_line(line).map_err(|_| error)? {
+                Some(command) => command,
+                None => continue,
+            };
+            if name == "gsHeaderFiller" {
+                let filler = args.first().and_then(|arg| value(arg)).ok_or(error)?;
+                gfx.header_filler = Some((filler as u32).to_be_bytes());
+                continue
+            }
+            gfx.gfx.push(F3dex::from(assemble(name, &args).ok_or(error)?));
+        }
+        Ok(gfx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gbi_text_round_trip() {
+        let cmds = [
+            0xB6000000_00020000u64, //G_CLEARGEOMETRYMODE
+            0xB7000000_00002205,    //G_SETGEOMETRYMODE
+            0xE7000000_00000000,    //G_RDPPIPESYNC
+            0xFC121824_FF33FFFF,    //G_SETCOMBINE
+            0xBB000001_FFFFFFFF,    //G_TEXTURE
+            0xBA000E02_00008000,    //G_SETOTHERMODE_H TEXTLUT
+            0xB900031D_00552078,    //G_SETOTHERMODE_L render mode
+            0xFD100000_02000000,    //G_SETTIMG
+            0xF5000100_07000000,    //G_SETTILE
+            0xF0000000_073C0000,    //G_LOADTLUT
+            0xF5480200_00094250,    //G_SETTILE
+            0xF2000000_0007C07C,    //G_SETTILESIZE
+            0xF3000000_073FF100,    //G_LOADBLOCK
+            0xFA000080_FFFFFFFF,    //G_SETPRIMCOLOR
+            0x0400103F_01000040,    //G_VTX
+            0xB1000204_00020604,    //G_TRI2
+            0xBF000000_00020406,    //G_TRI1
+            0xBF000000_01020406,    //G_TRI1 with a flag, raw
+            0x06000000_03000010,    //G_DL
+            0x12345678_9ABCDEF0,    //unknown
+            0xB8000000_00000000,    //G_ENDDL
+        ];
+        let gfx = BKGfxList::new(cmds.iter().cloned().map(F3dex::from).collect());
+        let text = gfx.to_gbi_text();
+        let lines : Vec<&str> = text.lines().collect();
+        assert_eq!(lines[1], "gsSPSetGeometryMode(G_ZBUFFER | G_SHADE | G_SHADING_SMOOTH | G_CULL_BACK)");
+        assert_eq!(lines[3], "gsDPSetCombineMode(G_CC_MODULATERGBA, G_CC_MODULATERGBA)");
+        assert_eq!(lines[5], "gsDPSetTextureLUT(G_TT_RGBA16)");
+        assert_eq!(lines[6], "gsDPSetRenderMode(G_RM_AA_ZB_OPA_SURF, G_RM_AA_ZB_OPA_SURF2)");
+        assert_eq!(disassemble(0xFC721824_FF33FFFF).unwrap(), "gsDPSetCombineLERP(NOISE, 0, SHADE, 0, TEXEL0, 0, SHADE, 0, TEXEL0, 0, SHADE, 0, TEXEL0, 0, SHADE, 0)");
+        assert_eq!(lines[14], "gsSPVertex(VTX(4), 4, 0)");
+        assert_eq!(lines[15], "gsSP2Triangles(0, 1, 2, 0, 1, 3, 2, 0)");
+        assert_eq!(lines[17], "gsRaw(0xBF000000, 0x01020406)");
+        assert_eq!(lines[18], "gsSPDisplayList(GFX(2))");
+        assert_eq!(lines[19], "gsRaw(0x12345678, 0x9ABCDEF0)");
+
+        let mut reassembled = BKGfxList::from_gbi_text(&text).unwrap();
+        assert_eq!(reassembled.to_be_bytes(), gfx.to_be_bytes());
+
+        reassembled.header_filler = Some([0x0F; 4]);
+        let text = format!("// edited\n{}\n", reassembled.to_gbi_text().replace('\n', ",\n"));
+        assert_eq!(BKGfxList::from_gbi_text(&text).unwrap().to_be_bytes(), reassembled.to_be_bytes());
+        assert_eq!(BKGfxList::from_gbi_text("gsDPPipeSync()\ngsSPVertex(VTX(0), 4)").unwrap_err().line, 2);
+    }
+}
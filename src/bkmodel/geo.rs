@@ -0,0 +1,389 @@
+use std::error::Error;
+
+use super::super::error::TryFromBEBytesError;
+
+/* Geo list
+    every command starts with {u32 cmd, u32 size}, size is the offset to the next
+    sibling or 0 for the last one, which then runs to the end of its parent.
+    child lists sit behind a command's header at offsets stored in the header,
+    and run up to the next child or the end of the command.
+
+    commands keep their original header bytes so padding and fields that aren't
+    understood survive a round trip, typed fields and child offsets are written
+    over them. commands with child offsets that don't make sense are kept whole
+    as Unknown.
+*/
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BKGeoCmd{
+    Billboard{unk_a: i16, position: [f32; 3]},                     //0x0, child i16 @0x8
+    Sort{unk_8: [f32; 3], unk_14: [f32; 3]},                       //0x1, children u16 @0x20, @0x22
+    Bone{bone_index: u8},                                          //0x2, child u8 @0x8
+    LoadDL{gfx_index: usize, unk_a: i16},                          //0x3, gfx_index in 8 byte commands
+    Unk4{unk_8: i32},                                              //0x4
+    Skinning{gfx_indices: Vec<usize>},                             //0x5, 0 terminated after the first
+    Branch,                                                        //0x6, child s32 @0x8
+    Unk7{unk_8: i16, unk_a: i16},                                  //0x7
+    LOD{max_distance: f32, min_distance: f32, position: [f32; 3]}, //0x8, child s32 @0x1C
+    Unk9{unk_8: i16, unk_a: i16},                                  //0x9
+    ReferencePoint{index: i16, bone_index: i16, position: [f32; 3]},//0xA
+    UnkB{unk_8: i16, unk_a: i16},                                  //0xB
+    Selector{selector_index: i16},                                 //0xC, i16 count @0x8, s32 children @0xC
+    DrawDistance{min: [i16; 3], max: [i16; 3]},                    //0xD, child s16 @0x14
+    UnkE{unk_8: [i16; 3], unk_e: i16, unk_10: i16},                //0xE, child s16 @0x12
+    UnkF{unk_a: u8, unk_b: u8},                                    //0xF, child s16 @0x8
+    Unk10{unk_c: i32},                                             //0x10, child s32 @0x8
+    Unknown(u32),
+}
+
+impl BKGeoCmd{
+    pub fn id(&self)->u32{
+        match self {
+            BKGeoCmd::Billboard{..} => 0x0,
+            BKGeoCmd::Sort{..} => 0x1,
+            BKGeoCmd::Bone{..} => 0x2,
+            BKGeoCmd::LoadDL{..} => 0x3,
+            BKGeoCmd::Unk4{..} => 0x4,
+            BKGeoCmd::Skinning{..} => 0x5,
+            BKGeoCmd::Branch => 0x6,
+            BKGeoCmd::Unk7{..} => 0x7,
+            BKGeoCmd::LOD{..} => 0x8,
+            BKGeoCmd::Unk9{..} => 0x9,
+            BKGeoCmd::ReferencePoint{..} => 0xA,
+            BKGeoCmd::UnkB{..} => 0xB,
+            BKGeoCmd::Selector{..} => 0xC,
+            BKGeoCmd::DrawDistance{..} => 0xD,
+            BKGeoCmd::UnkE{..} => 0xE,
+            BKGeoCmd::UnkF{..} => 0xF,
+            BKGeoCmd::Unk10{..} => 0x10,
+            BKGeoCmd::Unknown(id) => *id,
+        }
+    }
+
+    //(offset in header, width in bytes) of every child slot
+    fn child_slots(id: u32, header: &[u8])->Vec<(usize, usize)>{
+        match id {
+            0x0 => vec![(0x8, 2)],
+            0x1 => vec![(0x20, 2), (0x22, 2)],
+            0x2 => vec![(0x8, 1)],
+            0x6 => vec![(0x8, 4)],
+            0x8 => vec![(0x1C, 4)],
+            0xC => {
+                let count = header.get(8..10).map_or(0, |b| i16::from_be_bytes([b[0], b[1]]).max(0) as usize);
+                (0..count).map(|i| (0xC + 4*i, 4)).collect()
+            },
+            0xD => vec![(0x14, 2)],
+            0xE => vec![(0x12, 2)],
+            0xF => vec![(0x8, 2)],
+            0x10 => vec![(0x8, 4)],
+            _ => Vec::new(),
+        }
+    }
+
+    //bytes of the header that hold fields, child offsets included
+    fn header_size(id: u32, header: &[u8])->usize{
+        match id {
+            0x0 => 0x18,
+            0x1 => 0x24,
+            0x2 => 0xA,
+            0x3 => 0xC,
+            0x4 => 0xC,
+            0x5 => {
+                let count = 1 + header.get(0xA..).unwrap_or(&[]).chunks_exact(2).take_while(|b| *b != [0, 0]).count();
+                8 + 2*count + 2
+            },
+            0x6 => 0xC,
+            0x7 => 0xC,
+            0x8 => 0x20,
+            0x9 => 0xC,
+            0xA => 0x18,
+            0xB => 0xC,
+            0xC => 0xC + 4*Self::child_slots(id, header).len(),
+            0xD => 0x16,
+            0xE => 0x14,
+            0xF => 0xC,
+            0x10 => 0x10,
+            _ => 8,
+        }
+    }
+
+    fn from_header(id: u32, h: &[u8])->BKGeoCmd{
+        let i16_at = |o: usize| i16::from_be_bytes([h[o], h[o + 1]]);
+        let u16_at = |o: usize| u16::from_be_bytes([h[o], h[o + 1]]);
+        let i32_at = |o: usize| i32::from_be_bytes(h[o .. o + 4].try_into().unwrap());
+        let f32_at = |o: usize| f32::from_be_bytes(h[o .. o + 4].try_into().unwrap());
+        let vec3_at = |o: usize| [f32_at(o), f32_at(o + 4), f32_at(o + 8)];
+        match id {
+            0x0 => BKGeoCmd::Billboard{unk_a: i16_at(0xA), position: vec3_at(0xC)},
+            0x1 => BKGeoCmd::Sort{unk_8: vec3_at(0x8), unk_14: vec3_at(0x14)},
+            0x2 => BKGeoCmd::Bone{bone_index: h[9]},
+            0x3 => BKGeoCmd::LoadDL{gfx_index: u16_at(0x8) as usize, unk_a: i16_at(0xA)},
+            0x4 => BKGeoCmd::Unk4{unk_8: i32_at(0x8)},
+            0x5 => BKGeoCmd::Skinning{gfx_indices: h[8 .. h.len() - 2].chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+                .collect()
+            },
+            0x6 => BKGeoCmd::Branch,
+            0x7 => BKGeoCmd::Unk7{unk_8: i16_at(0x8), unk_a: i16_at(0xA)},
+            0x8 => BKGeoCmd::LOD{max_distance: f32_at(0x8), min_distance: f32_at(0xC), position: vec3_at(0x10)},
+            0x9 => BKGeoCmd::Unk9{unk_8: i16_at(0x8), unk_a: i16_at(0xA)},
+            0xA => BKGeoCmd::ReferencePoint{index: i16_at(0x8), bone_index: i16_at(0xA), position: vec3_at(0xC)},
+            0xB => BKGeoCmd::UnkB{unk_8: i16_at(0x8), unk_a: i16_at(0xA)},
+            0xC => BKGeoCmd::Selector{selector_index: i16_at(0xA)},
+            0xD => BKGeoCmd::DrawDistance{min: [i16_at(0x8), i16_at(0xA), i16_at(0xC)], max: [i16_at(0xE), i16_at(0x10), i16_at(0x12)]},
+            0xE => BKGeoCmd::UnkE{unk_8: [i16_at(0x8), i16_at(0xA), i16_at(0xC)], unk_e: i16_at(0xE), unk_10: i16_at(0x10)},
+            0xF => BKGeoCmd::UnkF{unk_a: h[0xA], unk_b: h[0xB]},
+            0x10 => BKGeoCmd::Unk10{unk_c: i32_at(0xC)},
+            _ => BKGeoCmd::Unknown(id),
+        }
+    }
+
+    //fields written over the header, child offsets and size are filled in later
+    fn write_header(&self, h: &mut Vec<u8>, child_count: usize){
+        let size = match self {
+            BKGeoCmd::Skinning{gfx_indices} => 8 + 2*gfx_indices.len() + 2,
+            BKGeoCmd::Selector{..} => 0xC + 4*child_count,
+            BKGeoCmd::Unknown(_) => 8,
+            _ => Self::header_size(self.id(), &[]),
+        };
+        if h.len() < size { h.resize((size + 7) & !7, 0) }
+
+        h[0..4].copy_from_slice(&self.id().to_be_bytes());
+        let mut put = |o: usize, bytes: &[u8]| h[o .. o + bytes.len()].copy_from_slice(bytes);
+        let vec3 = |v: &[f32; 3]| v.iter().flat_map(|f| f.to_be_bytes()).collect::<Vec<u8>>();
+        match self {
+            BKGeoCmd::Billboard{unk_a, position} => { put(0xA, &unk_a.to_be_bytes()); put(0xC, &vec3(position)); },
+            BKGeoCmd::Sort{unk_8, unk_14} => { put(0x8, &vec3(unk_8)); put(0x14, &vec3(unk_14)); },
+            BKGeoCmd::Bone{bone_index} => put(0x9, &[*bone_index]),
+            BKGeoCmd::LoadDL{gfx_index, unk_a} => { put(0x8, &(*gfx_index as u16).to_be_bytes()); put(0xA, &unk_a.to_be_bytes()); },
+            BKGeoCmd::Unk4{unk_8} => put(0x8, &unk_8.to_be_bytes()),
+            BKGeoCmd::Skinning{gfx_indices} => {
+                let bytes : Vec<u8> = gfx_indices.iter().flat_map(|&i| (i as u16).to_be_bytes()).chain([0, 0]).collect();
+                put(0x8, &bytes);
+            },
+            BKGeoCmd::Branch => {},
+            BKGeoCmd::Unk7{unk_8, unk_a} | BKGeoCmd::Unk9{unk_8, unk_a} | BKGeoCmd::UnkB{unk_8, unk_a} => {
+                put(0x8, &unk_8.to_be_bytes());
+                put(0xA, &unk_a.to_be_bytes());
+            },
+            BKGeoCmd::LOD{max_distance, min_distance, position} => {
+                put(0x8, &max_distance.to_be_bytes());
+                put(0xC, &min_distance.to_be_bytes());
+                put(0x10, &vec3(position));
+            },
+            BKGeoCmd::ReferencePoint{index, bone_index, position} => {
+                put(0x8, &index.to_be_bytes());
+                put(0xA, &bone_index.to_be_bytes());
+                put(0xC, &vec3(position));
+            },
+            BKGeoCmd::Selector{selector_index} => {
+                put(0x8, &(child_count as i16).to_be_bytes());
+                put(0xA, &selector_index.to_be_bytes());
+            },
+            BKGeoCmd::DrawDistance{min, max} => {
+                put(0x8, &min.iter().chain(max.iter()).flat_map(|v| v.to_be_bytes()).collect::<Vec<u8>>());
+            },
+            BKGeoCmd::UnkE{unk_8, unk_e, unk_10} => {
+                put(0x8, &unk_8.iter().chain([unk_e, unk_10]).flat_map(|v| v.to_be_bytes()).collect::<Vec<u8>>());
+            },
+            BKGeoCmd::UnkF{unk_a, unk_b} => put(0xA, &[*unk_a, *unk_b]),
+            BKGeoCmd::Unk10{unk_c} => put(0xC, &unk_c.to_be_bytes()),
+            BKGeoCmd::Unknown(_) => {},
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BKGeoCommand{
+    pub cmd: BKGeoCmd,
+    pub children: Vec<Option<BKGeoList>>, //one slot per child offset, None for offset 0
+    header: Vec<u8>, //original bytes up to the first child
+    last_has_size: bool, //last command of its list but stored with a size instead of 0
+}
+
+impl BKGeoCommand{
+    pub fn new(cmd: BKGeoCmd, children: Vec<Option<BKGeoList>>)->BKGeoCommand{
+        BKGeoCommand{cmd, children, header: Vec::new(), last_has_size: false}
+    }
+
+    fn from_be_bytes(bytes: &[u8])->BKGeoCommand{
+        let id = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+        let unknown = || BKGeoCommand::new(BKGeoCmd::Unknown(id), Vec::new()).with_header(bytes);
+
+        let header_size = BKGeoCmd::header_size(id, bytes);
+        if header_size > bytes.len() { return unknown() }
+        let offsets : Vec<usize> = BKGeoCmd::child_slots(id, bytes).iter()
+            .map(|&(o, width)| bytes[o .. o + width].iter().fold(0, |acc, &b| (acc << 8) | b as usize))
+            .collect();
+
+        //children have to follow the header in the order of their slots
+        let present : Vec<usize> = offsets.iter().cloned().filter(|&o| o != 0).collect();
+        let in_order = present.windows(2).all(|w| w[0] < w[1]);
+        let in_range = present.iter().all(|&o| o >= header_size && o < bytes.len());
+        if !in_order || !in_range { return unknown() }
+
+        let first_child = present.first().cloned().unwrap_or(bytes.len());
+        let children = offsets.iter().map(|&o| match o {
+                0 => None,
+                _ => {
+                    let end = present.iter().cloned().find(|&next| next > o).unwrap_or(bytes.len());
+                    Some(BKGeoList::from_region(&bytes[o .. end]))
+                },
+            })
+            .collect();
+
+        let header = &bytes[..first_child];
+        BKGeoCommand::new(BKGeoCmd::from_header(id, &bytes[..header_size]), children).with_header(header)
+    }
+
+    fn with_header(mut self, header: &[u8])->BKGeoCommand{
+        self.header = header.to_vec();
+        self
+    }
+
+    fn to_be_bytes(&self, size_zero: bool)->Vec<u8>{
+        let mut out = self.header.clone();
+        self.cmd.write_header(&mut out, self.children.len());
+
+        let slots = BKGeoCmd::child_slots(self.cmd.id(), &out);
+        for (child, (slot, width)) in self.children.iter().zip(slots) {
+            let offset = match child {
+                Some(list) => {
+                    let offset = out.len();
+                    out.extend(list.to_be_bytes());
+                    offset
+                },
+                None => 0,
+            };
+            out[slot .. slot + width].copy_from_slice(&offset.to_be_bytes()[8 - width ..]);
+        }
+
+        let size = if size_zero { 0 } else { out.len() as u32 };
+        out[4..8].copy_from_slice(&size.to_be_bytes());
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BKGeoList{
+    pub commands: Vec<BKGeoCommand>,
+    tail: Vec<u8>, //bytes that aren't part of any command
+}
+
+impl BKGeoList{
+    pub fn new(commands: Vec<BKGeoCommand>)->BKGeoList{
+        BKGeoList{commands, tail: Vec::new()}
+    }
+
+    //the geo list is the last section, it runs to the end of the file
+    pub fn try_from_be_bytes(bytes: &[u8])->Result<BKGeoList, Box<dyn Error>>{
+        if bytes.len() < 8 { return Err(Box::new(TryFromBEBytesError)) }
+        let this = BKGeoList::from_region(bytes);
+        #[cfg(feature = "test_byte_matching")]assert_eq!(bytes, this.to_be_bytes(), "\n{:#?}", this);
+        Ok(this)
+    }
+
+    fn from_region(bytes: &[u8])->BKGeoList{
+        let mut commands = Vec::new();
+        let mut offset = 0;
+        while offset + 8 <= bytes.len() {
+            let size = u32::from_be_bytes(bytes[offset + 4 .. offset + 8].try_into().unwrap()) as usize;
+            let end = if size == 0 { bytes.len() } else { offset + size };
+            if size != 0 && (size < 8 || end > bytes.len()) { break }
+
+            let mut command = BKGeoCommand::from_be_bytes(&bytes[offset .. end]);
+            offset = end;
+            if size == 0 || offset == bytes.len() {
+                command.last_has_size = size != 0;
+                commands.push(command);
+                break
+            }
+            commands.push(command);
+        }
+        BKGeoList{commands, tail: bytes[offset..].to_vec()}
+    }
+
+    pub fn to_be_bytes(&self)->Vec<u8>{
+        let last = self.commands.len().saturating_sub(1);
+        self.commands.iter().enumerate()
+            .flat_map(|(i, cmd)| cmd.to_be_bytes(i == last && !cmd.last_has_size && self.tail.is_empty()))
+            .chain(self.tail.iter().cloned())
+            .collect()
+    }
+
+    pub fn size(&self)->usize{
+        self.to_be_bytes().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn be(words: &[u32])->Vec<u8>{
+        words.iter().flat_map(|w| w.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn geo_list_round_trip() {
+        //bone -> [load dl, selector -> [load dl, none]], unknown command last
+        let bytes = [
+            be(&[0x2, 0x48, 0x1003_0000, 0]),      //bone 3, child @0x10
+            be(&[0x3, 0x10, 0x0004_0001, 0]),      //load dl 4
+            be(&[0xC, 0, 0x0002_0001, 0x18, 0, 0]),//selector, 2 children, 1 padding word
+            be(&[0x3, 0, 0x0007_0000, 0]),         //load dl 7
+            be(&[0xC, 0x14, 0, 0, 0]),             //selector without children
+            be(&[0x42, 0, 0xDEAD_BEEF, 0]),
+        ].concat();
+        let geo = BKGeoList::try_from_be_bytes(&bytes).unwrap();
+        assert_eq!(geo.to_be_bytes(), bytes);
+
+        assert_eq!(geo.commands.len(), 3);
+        assert_eq!(geo.commands[0].cmd, BKGeoCmd::Bone{bone_index: 3});
+        let bone_children = geo.commands[0].children[0].as_ref().unwrap();
+        assert_eq!(bone_children.commands[0].cmd, BKGeoCmd::LoadDL{gfx_index: 4, unk_a: 1});
+        assert_eq!(bone_children.commands[1].cmd, BKGeoCmd::Selector{selector_index: 1});
+        assert_eq!(bone_children.commands[1].children.len(), 2);
+        assert!(bone_children.commands[1].children[1].is_none());
+        assert_eq!(geo.commands[2].cmd, BKGeoCmd::Unknown(0x42));
+
+        //edits are relaid out
+        let mut edited = geo.clone();
+        edited.commands[0].children[0].as_mut().unwrap().commands[0].cmd = BKGeoCmd::Skinning{gfx_indices: vec![1, 2, 3]};
+        let reparsed = BKGeoList::try_from_be_bytes(&edited.to_be_bytes()).unwrap();
+        assert_eq!(reparsed.commands[0].children[0].as_ref().unwrap().commands[0].cmd, BKGeoCmd::Skinning{gfx_indices: vec![1, 2, 3]});
+        assert_eq!(reparsed.commands[2].cmd, BKGeoCmd::Unknown(0x42));
+    }
+
+    #[test]
+    fn geo_cmd_round_trip() {
+        let load = be(&[0x3, 0, 0x0001_0000, 0]);
+        let with_child = |header: Vec<u8>| [header, load.clone()].concat();
+        let commands = [
+            (be(&[0x3, 0, 0x8001_0000, 0]), BKGeoCmd::LoadDL{gfx_index: 0x8001, unk_a: 0}), //unsigned
+            (be(&[0x4, 0, 0x1234_5678, 0]), BKGeoCmd::Unk4{unk_8: 0x1234_5678}),
+            (with_child(be(&[0x6, 0, 0x10, 0])), BKGeoCmd::Branch),
+            (be(&[0x7, 0, 0x0002_0003, 0]), BKGeoCmd::Unk7{unk_8: 2, unk_a: 3}),
+            (be(&[0x9, 0, 0x0004_FFFF, 0]), BKGeoCmd::Unk9{unk_8: 4, unk_a: -1}),
+            (be(&[0xB, 0, 0x0005_0006, 0]), BKGeoCmd::UnkB{unk_8: 5, unk_a: 6}),
+            (with_child(be(&[0xE, 0, 0x0001_0002, 0x0003_0004, 0x0005_0018, 0])), BKGeoCmd::UnkE{unk_8: [1, 2, 3], unk_e: 4, unk_10: 5}),
+            (with_child(be(&[0xF, 0, 0x0010_0102, 0])), BKGeoCmd::UnkF{unk_a: 1, unk_b: 2}),
+            (with_child(be(&[0x10, 0, 0x10, 0x7])), BKGeoCmd::Unk10{unk_c: 7}),
+        ];
+        for (bytes, cmd) in commands {
+            let geo = BKGeoList::try_from_be_bytes(&bytes).unwrap();
+            assert_eq!(geo.to_be_bytes(), bytes);
+            assert_eq!(geo.commands[0].cmd, cmd);
+            assert_eq!(geo.commands[0].children.len(), BKGeoCmd::child_slots(cmd.id(), &bytes).len());
+            if let Some(child) = geo.commands[0].children.first() {
+                assert_eq!(child.as_ref().unwrap().commands[0].cmd, BKGeoCmd::LoadDL{gfx_index: 1, unk_a: 0});
+            }
+
+            //built from the typed command alone
+            let children = geo.commands[0].children.clone();
+            let built = BKGeoList::new(vec![BKGeoCommand::new(cmd.clone(), children.clone())]).to_be_bytes();
+            let reparsed = BKGeoList::try_from_be_bytes(&built).unwrap();
+            assert_eq!((&reparsed.commands[0].cmd, &reparsed.commands[0].children), (&cmd, &children));
+        }
+    }
+}
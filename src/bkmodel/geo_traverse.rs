@@ -0,0 +1,221 @@
+use super::geo::{BKGeoCmd, BKGeoList};
+use super::{BKModel, BKTriangle, BKGfxInterpreter};
+
+/* Geo list traversal
+    walks the list the way the game renders it and collects display list draws.
+    matrices are row major and transform column vectors, translation in the last column.
+    - Billboard turns its children around Y to face the camera
+    - Sort draws the child on the far side of its plane first, the first child
+      is the one on the side unk_14 points to
+    - Bone multiplies in the matrix of its bone
+    - LOD draws its child while min_distance <= distance < max_distance
+    - Selector > 0 draws child n-1, < 0 is a mask of children to draw, 0 draws none
+    - DrawDistance boxes are not frustum culled here, their children are always drawn
+*/
+
+pub type BKMatrix = [[f32; 4]; 4];
+
+pub const MTX_IDENTITY : BKMatrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+pub(crate) fn mtx_mul(a: &BKMatrix, b: &BKMatrix)->BKMatrix{
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..4).map(|k| a[i][k]*b[k][j]).sum()))
+}
+
+fn mtx_translate(v: [f32; 3])->BKMatrix{
+    let mut m = MTX_IDENTITY;
+    for (row, v) in m.iter_mut().zip(v) { row[3] = v }
+    m
+}
+
+fn mtx_rotate_y(angle: f32)->BKMatrix{
+    let (sin, cos) = angle.sin_cos();
+    [
+        [cos, 0.0, sin, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [-sin, 0.0, cos, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+pub fn transform_point(m: &BKMatrix, p: [f32; 3])->[f32; 3]{
+    std::array::from_fn(|i| m[i][0]*p[0] + m[i][1]*p[1] + m[i][2]*p[2] + m[i][3])
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BKGeoContext{
+    pub camera_position: [f32; 3], //model space
+    pub selectors: Vec<i32>,       //indexed by a Selector's selector_index
+    pub bone_matrices: Vec<BKMatrix>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BKGeoDraw{
+    pub gfx_index: usize, //in 8 byte commands from the start of the display list
+    pub transform: BKMatrix,
+    pub bone_index: Option<usize>, //innermost Bone the draw sits under
+}
+
+impl BKGeoList{
+    pub fn traverse(&self, context: &BKGeoContext)->Vec<BKGeoDraw>{
+        let mut draws = Vec::new();
+        self.traverse_with(context, &MTX_IDENTITY, None, &mut draws);
+        draws
+    }
+
+    //every draw in the list whatever the camera and selectors, in list order
+    pub fn all_draws(&self)->Vec<BKGeoDraw>{
+        let mut draws = Vec::new();
+        self.all_draws_with(None, &mut draws);
+        draws
+    }
+
+    fn all_draws_with(&self, bone: Option<usize>, draws: &mut Vec<BKGeoDraw>){
+        for command in self.commands.iter() {
+            let bone = match command.cmd {
+                BKGeoCmd::Bone{bone_index} => Some(bone_index as usize),
+                _ => bone,
+            };
+            match &command.cmd {
+                BKGeoCmd::LoadDL{gfx_index, ..} => draws.push(BKGeoDraw{gfx_index: *gfx_index, transform: MTX_IDENTITY, bone_index: bone}),
+                BKGeoCmd::Skinning{gfx_indices} => draws.extend(gfx_indices.iter()
+                    .map(|&gfx_index| BKGeoDraw{gfx_index, transform: MTX_IDENTITY, bone_index: bone})
+                ),
+                _ => {},
+            }
+            for list in command.children.iter().flatten() {
+                list.all_draws_with(bone, draws);
+            }
+        }
+    }
+
+    fn traverse_with(&self, context: &BKGeoContext, transform: &BKMatrix, bone: Option<usize>, draws: &mut Vec<BKGeoDraw>){
+        for command in self.commands.iter() {
+            let child = |i: usize| command.children.get(i).and_then(Option::as_ref);
+            match &command.cmd {
+                BKGeoCmd::Billboard{position, ..} => {
+                    let pivot = transform_point(transform, *position);
+                    let angle = (context.camera_position[0] - pivot[0]).atan2(context.camera_position[2] - pivot[2]);
+                    let m = mtx_mul(&mtx_mul(&mtx_translate(*position), &mtx_rotate_y(angle)), &mtx_translate(position.map(|v| -v)));
+                    if let Some(list) = child(0) { list.traverse_with(context, &mtx_mul(transform, &m), bone, draws) }
+                },
+                BKGeoCmd::Sort{unk_8, unk_14} => {
+                    let point = transform_point(transform, *unk_8);
+                    let side : f32 = (0..3).map(|i| (context.camera_position[i] - point[i])*unk_14[i]).sum();
+                    let order = if side >= 0.0 { [1, 0] } else { [0, 1] };
+                    for i in order {
+                        if let Some(list) = child(i) { list.traverse_with(context, transform, bone, draws) }
+                    }
+                },
+                BKGeoCmd::Bone{bone_index} => {
+                    let index = *bone_index as usize;
+                    let m = context.bone_matrices.get(index).map_or(*transform, |b| mtx_mul(transform, b));
+                    if let Some(list) = child(0) { list.traverse_with(context, &m, Some(index), draws) }
+                },
+                BKGeoCmd::LoadDL{gfx_index, ..} => {
+                    draws.push(BKGeoDraw{gfx_index: *gfx_index, transform: *transform, bone_index: bone});
+                },
+                BKGeoCmd::Skinning{gfx_indices} => {
+                    draws.extend(gfx_indices.iter().map(|&gfx_index| BKGeoDraw{gfx_index, transform: *transform, bone_index: bone}));
+                },
+                BKGeoCmd::LOD{max_distance, min_distance, position} => {
+                    let p = transform_point(transform, *position);
+                    let distance = (0..3).map(|i| (context.camera_position[i] - p[i]).powi(2)).sum::<f32>().sqrt();
+                    if *min_distance <= distance && distance < *max_distance {
+                        if let Some(list) = child(0) { list.traverse_with(context, transform, bone, draws) }
+                    }
+                },
+                BKGeoCmd::Selector{selector_index} => {
+                    let value = usize::try_from(*selector_index).ok()
+                        .and_then(|i| context.selectors.get(i))
+                        .cloned()
+                        .unwrap_or(0);
+                    let selected : Vec<usize> = match value {
+                        0 => Vec::new(),
+                        v if v > 0 => vec![v as usize - 1],
+                        v => (0..32).filter(|i| (v.unsigned_abs() >> i) & 1 != 0).collect(),
+                    };
+                    for i in selected {
+                        if let Some(list) = child(i) { list.traverse_with(context, transform, bone, draws) }
+                    }
+                },
+                BKGeoCmd::DrawDistance{..} | BKGeoCmd::Branch | BKGeoCmd::UnkE{..} | BKGeoCmd::UnkF{..} | BKGeoCmd::Unk10{..} => {
+                    if let Some(list) = child(0) { list.traverse_with(context, transform, bone, draws) }
+                },
+                BKGeoCmd::Unk4{..} | BKGeoCmd::Unk7{..} | BKGeoCmd::Unk9{..} | BKGeoCmd::UnkB{..} => {},
+                BKGeoCmd::ReferencePoint{..} | BKGeoCmd::Unknown(_) => {},
+            }
+        }
+    }
+}
+
+impl BKModel{
+    /* every triangle of the model with the bone it hangs from,
+        display lists are walked from each geo list draw, or whole when there is no geo list
+    */
+    pub fn triangles(&self)->Vec<(BKTriangle, Option<usize>)>{
+        let gfx = match &self.display_list {
+            Some(gfx) => gfx,
+            None => return Vec::new(),
+        };
+        let (vertices, textures) = (self.vertices.as_ref(), self.texture_list.as_ref());
+        let draws = self.geo_list.as_ref().map(BKGeoList::all_draws).unwrap_or_default();
+        if draws.is_empty() {
+            return BKGfxInterpreter::new(gfx, vertices, textures).run_all().into_iter().map(|tri| (tri, None)).collect()
+        }
+
+        let mut walked = Vec::new();
+        let mut triangles = Vec::new();
+        for draw in draws {
+            if walked.contains(&draw.gfx_index) { continue }
+            walked.push(draw.gfx_index);
+            triangles.extend(BKGfxInterpreter::new(gfx, vertices, textures).run(draw.gfx_index).into_iter().map(|tri| (tri, draw.bone_index)));
+        }
+        triangles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::geo::BKGeoCommand;
+
+    fn load(gfx_index: usize)->BKGeoCommand{
+        BKGeoCommand::new(BKGeoCmd::LoadDL{gfx_index, unk_a: 0}, Vec::new())
+    }
+
+    fn list(commands: Vec<BKGeoCommand>)->Option<BKGeoList>{
+        Some(BKGeoList::new(commands))
+    }
+
+    #[test]
+    fn geo_traverse_draws() {
+        let geo = BKGeoList::new(vec![
+            load(1),
+            BKGeoCommand::new(BKGeoCmd::Bone{bone_index: 0}, vec![list(vec![
+                BKGeoCommand::new(BKGeoCmd::Selector{selector_index: 0}, vec![list(vec![load(2)]), list(vec![load(3)]), list(vec![load(4)])]),
+            ])]),
+            BKGeoCommand::new(BKGeoCmd::LOD{max_distance: 100.0, min_distance: 0.0, position: [0.0; 3]}, vec![list(vec![load(5)])]),
+        ]);
+        let mut context = BKGeoContext{
+            camera_position: [0.0, 0.0, 50.0],
+            selectors: vec![2],
+            bone_matrices: vec![mtx_translate([1.0, 2.0, 3.0])],
+        };
+
+        let draws = geo.traverse(&context);
+        assert_eq!(draws.iter().map(|d| d.gfx_index).collect::<Vec<_>>(), vec![1, 3, 5]);
+        assert_eq!(draws[1].bone_index, Some(0));
+        assert_eq!(transform_point(&draws[1].transform, [0.0; 3]), [1.0, 2.0, 3.0]);
+        assert_eq!(draws[2].transform, MTX_IDENTITY);
+
+        context.selectors = vec![-5]; //children 0 and 2
+        context.camera_position = [0.0, 0.0, 150.0];
+        let draws = geo.traverse(&context);
+        assert_eq!(draws.iter().map(|d| d.gfx_index).collect::<Vec<_>>(), vec![1, 2, 4]);
+    }
+}
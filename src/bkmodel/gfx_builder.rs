@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+
+use libultra::{F3dex, Vtx};
+
+use super::{BKGfxList, BKTextureList, BKVertexList, BKModel, BKGfxInterpreter, BKMaterialState, BKTile};
+use super::geo::{BKGeoCmd, BKGeoList};
+use super::f3dex::{VTX_SEGMENT, TEXTURE_SEGMENT, VTX_CACHE_SIZE};
+use super::super::bktexture::{BKTextureFormat, BKTextureHeader, tmem_line_size, TMEM_TLUT_OFFSET};
+
+/* Display list generation
+    triangles are drawn one material group after the other. every group sets up
+    its render state and texture (or turns texturing off) and then loads its
+    vertices in batches of up to 32. a batch is filled greedily, the next triangle
+    is the one sharing the most vertices with the batch so far, so connected
+    surfaces stay together and few vertices are loaded twice.
+    vertices are copied into the output vertex list in batch order, each G_VTX
+    loads one contiguous run starting at cache slot 0 and triangles are paired
+    into G_TRI2 with a G_TRI1 for an odd one out.
+    textures are loaded whole with G_LOADBLOCK into tile 7 and drawn from tile 0,
+    CI palettes are loaded with G_LOADTLUT to the upper half of TMEM.
+    groups without a material state get lighting off, smooth shading and a
+    texture * shade combiner. groups with one restore its geometry mode, other
+    modes, combiner, colours, texture scale and tile addressing.
+*/
+
+const G_CC_MODULATERGBA : u64 = 0xFC121824_FF33FFFF; //texture * shade, both cycles
+const G_CC_SHADE : u64 = 0xFCFFFFFF_FFFE793C;
+const G_RDPLOADSYNC : u64 = 0xE6000000_00000000;
+const G_RDPPIPESYNC : u64 = 0xE7000000_00000000;
+const G_ENDDL : u64 = 0xB8000000_00000000;
+const G_LIGHTING : u64 = 0x00020000;
+const G_SHADE : u64 = 0x00000004;
+const G_SHADING_SMOOTH : u64 = 0x00000200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BKMaterialGroup{
+    pub texture_index: Option<usize>,       //into the BKTextureList
+    pub material: Option<BKMaterialState>,  //render state to restore, its texture_index is ignored
+    pub triangles: Vec<[usize; 3]>,         //indices into the source vertices
+}
+
+//(G_IM_FMT, G_IM_SIZ) of a texture format
+fn image_format(format: &BKTextureFormat)->Option<(u64, u64)>{
+    Some(match format {
+        BKTextureFormat::CI4 => (2, 0),
+        BKTextureFormat::CI8 => (2, 1),
+        BKTextureFormat::I4 => (4, 0),
+        BKTextureFormat::I8 => (4, 1),
+        BKTextureFormat::IA4 => (3, 0),
+        BKTextureFormat::IA8 => (3, 1),
+        BKTextureFormat::IA16 => (3, 2),
+        BKTextureFormat::RGBA16 => (0, 2),
+        BKTextureFormat::RGBA32 => (0, 3),
+        BKTextureFormat::Unknown(_) => return None,
+    })
+}
+
+fn texture_address(offset: usize)->u64{
+    ((TEXTURE_SEGMENT << 24) | offset) as u64
+}
+
+//wrap and mask power of two sizes, clamp anything else, as (cm, mask, shift)
+fn tile_axis(size: usize)->(u64, u64, u64){
+    match size.is_power_of_two() {
+        true => (0, size.trailing_zeros() as u64, 0),
+        false => (2, 0, 0),
+    }
+}
+
+//G_TEXTURE scale, 0xFFFF is 1.0
+fn texture_scale(scale: f32)->u64{
+    match scale >= 1.0 {
+        true => 0xFFFF,
+        false => (scale.max(0.0)*65536.0) as u64,
+    }
+}
+
+fn texture_setup(header: &BKTextureHeader, material: Option<&BKMaterialState>)->Option<Vec<u64>>{
+    let (fmt, siz) = image_format(&header.format)?;
+    let bpp = header.format.bits_per_pixel()?;
+    let (width, height) = (header.width, header.height);
+    let scale = material.map_or([1.0; 2], |m| m.texture_scale);
+    let mut cmds = vec![
+        0xBB000001_00000000 | texture_scale(scale[0]) << 16 | texture_scale(scale[1]), //G_TEXTURE on
+        0xBA000E02_00000000 | if fmt == 2 { 0x8000 } else { 0 }, //G_SETOTHERMODE_H TEXTLUT, RGBA16 for CI
+    ];
+    let mut texels = header.offset;
+    if fmt == 2 {
+        let count = header.format.palette_size() as u64;
+        cmds.extend([
+            0xFD100000_00000000 | texture_address(header.offset), //G_SETTIMG RGBA 16b
+            0xF5000000_07000000 | ((TMEM_TLUT_OFFSET/8) as u64) << 32, //G_SETTILE 7
+            G_RDPLOADSYNC,
+            0xF0000000_07000000 | (count - 1) << 14, //G_LOADTLUT
+            G_RDPPIPESYNC,
+        ]);
+        texels += 2*header.format.palette_size();
+    }
+
+    //everything up to 16 bits is loaded as 16 bit texels
+    let load_siz = siz.max(2);
+    let load_bpp = bpp.max(16);
+    let bytes = (width*height*bpp).div_ceil(8);
+    let lrs = (bytes*8/load_bpp).saturating_sub(1).min(0x7FF) as u64;
+    let words = (width*bpp/64).max(1);
+    let dxt = 2048usize.div_ceil(words);
+    let line = match bpp {
+        32 => width*16/64, //split between the low and high halves of TMEM
+        _ => tmem_line_size(width, bpp)/8,
+    } as u64;
+    let [(cms, masks, shifts), (cmt, maskt, shiftt)] = match material {
+        Some(m) => {
+            let BKTile{cm, mask, shift, ..} = m.tile;
+            [0, 1].map(|i| (cm[i] as u64, mask[i] as u64, shift[i] as u64))
+        },
+        None => [tile_axis(width), tile_axis(height)],
+    };
+    let axes = cmt << 18 | maskt << 14 | shiftt << 10 | cms << 8 | masks << 4 | shifts;
+    cmds.extend([
+        0xFD000000_00000000 | fmt << 53 | load_siz << 51 | texture_address(texels), //G_SETTIMG
+        0xF5000000_07000000 | fmt << 53 | load_siz << 51, //G_SETTILE 7
+        G_RDPLOADSYNC,
+        0xF3000000_07000000 | lrs << 12 | dxt as u64, //G_LOADBLOCK
+        G_RDPPIPESYNC,
+        0xF5000000_00000000 | fmt << 53 | siz << 51 | line << 41 | axes, //G_SETTILE 0
+        0xF2000000_00000000 | ((width as u64 - 1) << 2) << 12 | (height as u64 - 1) << 2, //G_SETTILESIZE
+    ]);
+    Some(cmds)
+}
+
+fn state_setup(material: Option<&BKMaterialState>, textured: bool)->Vec<u64>{
+    match material {
+        Some(m) => vec![
+            G_RDPPIPESYNC,
+            0xB6000000_FFFFFFFF, //G_CLEARGEOMETRYMODE everything
+            0xB7000000_00000000 | m.geometry_mode as u64,
+            0xBA000020_00000000 | m.othermode_h as u64, //G_SETOTHERMODE_H, all 32 bits
+            0xB9000020_00000000 | m.othermode_l as u64, //G_SETOTHERMODE_L, all 32 bits
+            0xFC000000_00000000 | (m.combine & 0x00FFFFFF_FFFFFFFF),
+            0xFA000000_00000000 | u32::from_be_bytes(m.prim_color) as u64,
+            0xFB000000_00000000 | u32::from_be_bytes(m.env_color) as u64,
+        ],
+        None => vec![
+            G_RDPPIPESYNC,
+            0xB6000000_00000000 | G_LIGHTING, //G_CLEARGEOMETRYMODE
+            0xB7000000_00000000 | G_SHADE | G_SHADING_SMOOTH, //G_SETGEOMETRYMODE
+            if textured { G_CC_MODULATERGBA } else { G_CC_SHADE },
+        ],
+    }
+}
+
+fn tri_word(slots: [usize; 3])->u64{
+    (((slots[0]*2) << 16) | ((slots[1]*2) << 8) | (slots[2]*2)) as u64
+}
+
+//distinct vertices of tri that aren't in batch
+fn new_vertices(tri: &[usize; 3], batch: &[usize])->usize{
+    tri.iter().enumerate()
+        .filter(|&(i, v)| !batch.contains(v) && !tri[..i].contains(v))
+        .count()
+}
+
+//splits triangles into batches of at most VTX_CACHE_SIZE vertices, as (vertices, triangles by cache slot)
+fn batches(triangles: &[[usize; 3]])->Vec<(Vec<usize>, Vec<[usize; 3]>)>{
+    let mut by_vertex : HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, tri) in triangles.iter().enumerate() {
+        for v in tri.iter() { by_vertex.entry(*v).or_default().push(i) }
+    }
+
+    let mut done = vec![false; triangles.len()];
+    let mut next_unused = 0;
+    let mut out = Vec::new();
+    let mut batch : Vec<usize> = Vec::new();
+    let mut slots : Vec<[usize; 3]> = Vec::new();
+    let mut remaining = triangles.len();
+    while remaining > 0 {
+        //fewest new vertices among the triangles touching the batch, then list order
+        let room = VTX_CACHE_SIZE - batch.len();
+        let mut best : Option<(usize, usize)> = batch.iter()
+            .flat_map(|v| by_vertex[v].iter().cloned())
+            .filter(|&t| !done[t])
+            .map(|t| (new_vertices(&triangles[t], &batch), t))
+            .filter(|&(new, _)| new <= room)
+            .min();
+        if best.is_none() {
+            while done[next_unused] { next_unused += 1 }
+            let new = new_vertices(&triangles[next_unused], &batch);
+            if new <= room { best = Some((new, next_unused)) }
+        }
+
+        match best {
+            Some((_, t)) => {
+                done[t] = true;
+                remaining -= 1;
+                slots.push(triangles[t].map(|v| match batch.iter().position(|&b| b == v) {
+                    Some(slot) => slot,
+                    None => { batch.push(v); batch.len() - 1 },
+                }));
+            },
+            None => out.push((std::mem::take(&mut batch), std::mem::take(&mut slots))),
+        }
+    }
+    if !slots.is_empty() { out.push((batch, slots)) }
+    out
+}
+
+struct GfxBuilder<'a>{
+    vertices: &'a [Vtx],
+    textures: Option<&'a BKTextureList>,
+    cmds: Vec<u64>,
+    out: Vec<Vtx>,
+    sources: Vec<usize>, //index into vertices of every vertex in out
+}
+
+impl GfxBuilder<'_>{
+    //appends the groups and a G_ENDDL, returns the index of the first command
+    fn list(&mut self, groups: &[BKMaterialGroup])->usize{
+        let start = self.cmds.len();
+        for group in groups {
+            let texture = group.texture_index
+                .and_then(|i| self.textures?.texture_headers.get(i))
+                .and_then(|header| texture_setup(header, group.material.as_ref()));
+            self.cmds.extend(state_setup(group.material.as_ref(), texture.is_some()));
+            self.cmds.extend(texture.unwrap_or(vec![0xBB000000_FFFFFFFF])); //G_TEXTURE off
+
+            for (batch, slots) in batches(&group.triangles) {
+                let count = batch.len() as u64;
+                let address = ((VTX_SEGMENT << 24) + 0x10*self.out.len()) as u64;
+                self.cmds.push(0x04000000_00000000 | count << 42 | (0x10*count - 1) << 32 | address); //G_VTX
+                self.out.extend(batch.iter().map(|&v| Vtx::from_be_bytes(&self.vertices[v].to_bytes())));
+                self.sources.extend(batch.iter());
+                for pair in slots.chunks(2) {
+                    self.cmds.push(match pair {
+                        [a, b] => 0xB1000000_00000000 | tri_word(*a) << 32 | tri_word(*b), //G_TRI2
+                        [a] => 0xBF000000_00000000 | tri_word(*a), //G_TRI1
+                        _ => unreachable!(),
+                    });
+                }
+            }
+        }
+        self.cmds.push(G_ENDDL);
+        start
+    }
+
+    fn finish(self)->(BKGfxList, BKVertexList){
+        (BKGfxList::new(self.cmds.into_iter().map(F3dex::from).collect()), BKVertexList::new(self.out))
+    }
+}
+
+impl BKGfxList{
+    /* builds a display list drawing each group in order, returns it with the
+        vertex list it loads from. groups with an unknown texture are drawn untextured.
+    */
+    pub fn from_material_groups(groups: &[BKMaterialGroup], vertices: &[Vtx], textures: Option<&BKTextureList>)->(BKGfxList, BKVertexList){
+        let mut builder = GfxBuilder{vertices, textures, cmds: Vec::new(), out: Vec::new(), sources: Vec::new()};
+        builder.list(groups);
+        builder.finish()
+    }
+}
+
+fn has_skinning(list: &BKGeoList)->bool{
+    list.commands.iter().any(|command| matches!(command.cmd, BKGeoCmd::Skinning{..})
+        || command.children.iter().flatten().any(has_skinning))
+}
+
+pub(crate) fn remap_draws(list: &mut BKGeoList, starts: &HashMap<usize, usize>){
+    for command in list.commands.iter_mut() {
+        match &mut command.cmd {
+            BKGeoCmd::LoadDL{gfx_index, ..} => if let Some(&start) = starts.get(gfx_index) { *gfx_index = start },
+            BKGeoCmd::Skinning{gfx_indices} => for gfx_index in gfx_indices.iter_mut() {
+                if let Some(&start) = starts.get(gfx_index) { *gfx_index = start }
+            },
+            _ => {},
+        }
+        for child in command.children.iter_mut().flatten() {
+            remap_draws(child, starts);
+        }
+    }
+}
+
+//old vertex index -> every copy of it in the rebuilt vertex list
+fn remap_vertices(indices: &mut Vec<usize>, copies: &[Vec<usize>]){
+    *indices = indices.iter().flat_map(|&v| copies[v].iter().cloned()).collect();
+}
+
+impl BKModel{
+    /* regenerates the display list and vertex list from what the model draws,
+        each geo list draw gets its own sub-list and is pointed at its new start.
+        vertices nothing draws (collision only ones, say) are kept after the drawn
+        ones. collision triangles point at the first copy of their vertices, mesh
+        and unk_28 lists at every copy so moving them moves everything drawn.
+        returns the new command count, None without a display list and vertices,
+        when skinning shares the vertex cache between draws, or when a collision,
+        mesh or unk_28 index is outside the vertex list or the result won't fit.
+    */
+    pub fn rebuild_display_list(&mut self)->Option<usize>{
+        let gfx = self.display_list.as_ref()?;
+        let vertices = self.vertices.as_ref()?;
+        let textures = self.texture_list.as_ref();
+        if self.geo_list.as_ref().is_some_and(has_skinning) { return None }
+        let in_range = |v: &usize| *v < vertices.len();
+        let collision_ok = self.collision_list.as_ref().is_none_or(|list| list.tri.iter()
+            .flat_map(|tri| tri.vtx.iter())
+            .all(|&v| usize::try_from(v).is_ok_and(|v| in_range(&v))));
+        let meshes_ok = self.mesh_list.as_ref().is_none_or(|list| list.meshes.iter().flat_map(|m| m.vtx_indices.iter()).all(in_range));
+        let unk_28_ok = self.unk_28_list.as_ref().is_none_or(|list| list.list.iter().flat_map(|u| u.vtx_index_list.iter()).all(in_range));
+        if !collision_ok || !meshes_ok || !unk_28_ok { return None }
+
+        let mut starts : Vec<usize> = Vec::new();
+        for draw in self.geo_list.as_ref().map(BKGeoList::all_draws).unwrap_or_default() {
+            if !starts.contains(&draw.gfx_index) { starts.push(draw.gfx_index) }
+        }
+        let sources : Vec<_> = match starts.is_empty() {
+            true => vec![BKGfxInterpreter::new(gfx, Some(vertices), textures).run_all()],
+            false => starts.iter().map(|&start| BKGfxInterpreter::new(gfx, Some(vertices), textures).run(start)).collect(),
+        };
+
+        let mut builder = GfxBuilder{vertices: &vertices.vertex, textures, cmds: Vec::new(), out: Vec::new(), sources: Vec::new()};
+        let mut new_starts = HashMap::new();
+        for (i, triangles) in sources.into_iter().enumerate() {
+            let mut groups : Vec<BKMaterialGroup> = Vec::new();
+            for tri in triangles {
+                match groups.iter_mut().find(|g| g.texture_index == tri.material.texture_index && g.material.as_ref() == Some(&tri.material)) {
+                    Some(group) => group.triangles.push(tri.vertices),
+                    None => groups.push(BKMaterialGroup{texture_index: tri.material.texture_index, material: Some(tri.material), triangles: vec![tri.vertices]}),
+                }
+            }
+            let start = builder.list(&groups);
+            if let Some(&old) = starts.get(i) { new_starts.insert(old, start); }
+        }
+
+        let mut copies : Vec<Vec<usize>> = vec![Vec::new(); vertices.len()];
+        for (new, &old) in builder.sources.iter().enumerate() { copies[old].push(new) }
+        for (old, copies) in copies.iter_mut().enumerate().filter(|(_, copies)| copies.is_empty()) {
+            copies.push(builder.out.len());
+            builder.out.push(Vtx::from_be_bytes(&vertices[old].to_bytes()));
+        }
+        let count = |indices: &[usize]| indices.iter().map(|&v| copies[v].len()).sum::<usize>();
+        let fits = builder.out.len() <= i16::MAX as usize
+            && self.mesh_list.as_ref().is_none_or(|list| list.meshes.iter().all(|m| count(&m.vtx_indices) <= u16::MAX as usize))
+            && self.unk_28_list.as_ref().is_none_or(|list| list.list.iter().all(|u| count(&u.vtx_index_list) <= u8::MAX as usize));
+        if !fits { return None }
+
+        let (gfx, vertices) = builder.finish();
+        let len = gfx.len();
+        self.display_list = Some(gfx);
+        self.vertices = Some(vertices);
+        if let Some(geo) = self.geo_list.as_mut() { remap_draws(geo, &new_starts) }
+        if let Some(list) = self.collision_list.as_mut() {
+            for tri in list.tri.iter_mut() { tri.vtx = tri.vtx.map(|v| copies[v as usize][0] as i16) }
+        }
+        if let Some(list) = self.mesh_list.as_mut() {
+            for mesh in list.meshes.iter_mut() { remap_vertices(&mut mesh.vtx_indices, &copies) }
+        }
+        if let Some(list) = self.unk_28_list.as_mut() {
+            for unk_28 in list.list.iter_mut() { remap_vertices(&mut unk_28.vtx_index_list, &copies) }
+        }
+        self.layout = Some(self.section_layout());
+        Some(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::f3dex::tests::test_model;
+    use super::super::geo::BKGeoCommand;
+    use super::super::{BKCollisionList, BKCollisionMesh, BKCollisionTri, BKMesh, BKMeshList};
+
+    #[test]
+    fn gfx_from_material_groups() {
+        let model = test_model();
+        let source = &model.vertices.as_ref().unwrap().vertex;
+        let textures = model.texture_list.as_ref();
+        //a fan over 40 vertices doesn't fit in one batch
+        let many : Vec<Vtx> = (0..40).map(|i| Vtx::from_be_bytes(&source[i % 4].to_bytes())).collect();
+        let groups = [
+            BKMaterialGroup{texture_index: Some(0), material: None, triangles: vec![[0, 1, 2], [1, 3, 2]]},
+            BKMaterialGroup{texture_index: None, material: None, triangles: (0..20).map(|i| [0, 2*i + 1, (2*i + 2) % 40]).collect()},
+        ];
+        let (gfx, vertices) = BKGfxList::from_material_groups(&groups, &many, textures);
+        assert_eq!(vertices.len(), 4 + 31 + 10);
+
+        //walking the result gives the same triangles back
+        let triangles = gfx.triangles(textures);
+        assert_eq!(triangles.len(), 2 + 20);
+        assert_eq!(triangles[0].material.texture_index, Some(0));
+        assert_eq!(triangles[2].material.texture_index, None);
+        let drawn = |tris: &mut dyn Iterator<Item = [[i16; 3]; 3]>| { let mut v : Vec<_> = tris.collect(); v.sort(); v };
+        assert_eq!(
+            drawn(&mut triangles.iter().map(|t| t.vertices.map(|v| vertices[v].ob))),
+            drawn(&mut groups.iter().flat_map(|g| g.triangles.iter()).map(|t| t.map(|v| many[v].ob))),
+        );
+        assert!(gfx.validate_tmem().iter().all(|d| !d.issue.is_error()));
+    }
+
+    #[test]
+    fn gfx_batches_grid() {
+        //10x10 quads over 11x11 vertices, quads listed in a scattered order
+        let quads : Vec<usize> = (0..100).map(|i| (i*37) % 100).collect();
+        let triangles : Vec<[usize; 3]> = quads.iter()
+            .flat_map(|&q| { let v = q/10*11 + q%10; [[v, v + 1, v + 11], [v + 1, v + 12, v + 11]] })
+            .collect();
+        let loaded : usize = batches(&triangles).iter().map(|(batch, slots)| {
+            assert!(batch.len() <= VTX_CACHE_SIZE);
+            assert!(slots.iter().flatten().all(|&slot| slot < batch.len()));
+            batch.len()
+        }).sum();
+        assert_eq!(batches(&triangles).iter().map(|(_, slots)| slots.len()).sum::<usize>(), 200);
+
+        //filling batches in list order instead
+        let (mut in_order, mut batch) = (0, Vec::new());
+        for tri in triangles.iter() {
+            if batch.len() + new_vertices(tri, &batch) > VTX_CACHE_SIZE { in_order += batch.len(); batch.clear() }
+            for v in tri { if !batch.contains(v) { batch.push(*v) } }
+        }
+        in_order += batch.len();
+        assert!(loaded*3 < in_order*2, "{} vertices loaded, {} in order", loaded, in_order);
+    }
+
+    #[test]
+    fn gfx_rebuild_display_list() {
+        let mut model = test_model();
+        model.geo_list = Some(BKGeoList::new(vec![
+            BKGeoCommand::new(BKGeoCmd::LoadDL{gfx_index: 0, unk_a: 0}, Vec::new()),
+            BKGeoCommand::new(BKGeoCmd::LoadDL{gfx_index: 2, unk_a: 0}, Vec::new()), //from G_VTX, nothing loaded
+        ]));
+        let drawn = |model: &BKModel| model.triangles().iter()
+            .map(|(t, _)| (t.vertices.map(|v| model.vertices.as_ref().unwrap()[v].ob), t.material.texture_index))
+            .collect::<Vec<_>>();
+        let before = drawn(&model);
+
+        let len = model.rebuild_display_list().unwrap();
+        assert_eq!(model.display_list.as_ref().unwrap().len(), len);
+        assert_eq!(drawn(&model), before);
+        let second = match model.geo_list.as_ref().unwrap().commands[1].cmd {
+            BKGeoCmd::LoadDL{gfx_index, ..} => gfx_index,
+            _ => unreachable!(),
+        };
+        assert!(second > 2);
+        assert_eq!(model.display_list.as_ref().unwrap().triangles_from(second, model.texture_list.as_ref())[0].material.texture_index, None);
+
+        //collision and meshes follow their vertices, undrawn ones included.
+        //the first triangle is drawn backwards so the vertices get reordered
+        let mut model = test_model();
+        let gfx = model.display_list.as_mut().unwrap();
+        let tri2 = gfx.iter().position(|cmd| u64::from(cmd.clone()) == 0xB1000204_00020604).unwrap();
+        gfx[tri2] = F3dex::from(0xB1040200_00020604u64);
+        let mut extra = [0u8; 16];
+        extra[0..6].copy_from_slice(&[0, 50, 0, 50, 0, 50]);
+        model.vertices.as_mut().unwrap().push(Vtx::from_be_bytes(&extra));
+        model.collision_list = Some(BKCollisionList{
+            min: [0; 3], max: [0; 3], y_stride: 1, z_stride: 1, scale: 100,
+            geo: vec![BKCollisionMesh{tri_start: 0, size: 1}],
+            tri: vec![BKCollisionTri{vtx: [3, 4, 0], unk_6: 0, flags: 0}],
+        });
+        model.mesh_list = Some(BKMeshList{meshes: vec![BKMesh{uid: 1, vtx_indices: vec![4, 2]}]});
+        let positions = |model: &BKModel, indices: &mut dyn Iterator<Item = usize>| indices
+            .map(|v| model.vertices.as_ref().unwrap()[v].ob)
+            .collect::<Vec<_>>();
+        let collision = |model: &BKModel| positions(model, &mut model.collision_list.as_ref().unwrap().tri[0].vtx.iter().map(|&v| v as usize));
+        let mesh = |model: &BKModel| positions(model, &mut model.mesh_list.as_ref().unwrap().meshes[0].vtx_indices.iter().cloned());
+        let (collision_before, mesh_before) = (collision(&model), mesh(&model));
+
+        model.rebuild_display_list().unwrap();
+        assert_eq!(model.collision_list.as_ref().unwrap().tri[0].vtx, [3, 4, 2]);
+        assert_eq!(collision(&model), collision_before);
+        assert_eq!(mesh(&model), mesh_before);
+        assert_eq!(model.vertices.as_ref().unwrap().len(), 5);
+
+        model.collision_list.as_mut().unwrap().tri[0].vtx[0] = 5;
+        assert!(model.rebuild_display_list().is_none());
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::{HashMap, HashSet};
+
+use libultra::F3dex;
+
+use super::{BKGfxList, BKModel, BKTextureList, BKVertexList, BKGfxInterpreter, BKTriangle};
+use super::f3dex::{split_cmd, segment_offset, othermode_mask, GFX_SEGMENT};
+use super::gfx_builder::remap_draws;
+
+/* Redundant state elimination
+    walks the list once keeping what is known of the RDP/RSP state: geometry mode
+    bits, other mode bits, the last combiner, colours, G_TEXTURE, texture image,
+    tile descriptors and what each TMEM range was loaded from. a command that
+    doesn't change any of it is dropped, as are no-ops.
+    nothing is known at an entry point, a G_DL target, after a G_DL returns or
+    after a command the pass doesn't model.
+    syncs are dropped when nothing but draws follow them before the next
+    non-sync command, when the same sync follows, or for pipe syncs when nothing
+    was drawn since the last one. adjacent G_TRI1 are then paired into G_TRI2.
+    the result is run through the interpreter from every entry point and only
+    kept when it draws the same triangles with the same materials. the interpreter
+    doesn't model RDP timing, so that check can't catch a G_RDPPIPESYNC or
+    G_RDPLOADSYNC that was wrongly dropped, sync removal rests on the rules above alone.
+*/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BKGfxOptimizeReport{
+    pub removed: usize,          //commands dropped, not counting merged G_TRI1
+    pub merged_triangles: usize, //G_TRI1 pairs made into G_TRI2
+    pub bytes_saved: usize,
+    pub index_map: Vec<usize>,   //new index of every old command, dropped ones map to the next kept one
+}
+
+#[derive(Default)]
+struct KnownState{
+    geometry_set: u32,
+    geometry_clear: u32,
+    othermode: [(u32, u32); 2],  //(known bits, value) of H, L
+    commands: HashMap<u32, u64>, //last command per opcode, per opcode and tile for tile descriptors
+    loads: Vec<(usize, usize, [u64; 3])>, //TMEM byte range and the (G_SETTIMG, G_SETTILE, load) that filled it
+}
+
+fn is_draw(opcode: u32)->bool{
+    matches!(opcode, 0x04 | 0xB1 | 0xBF) //G_VTX, G_TRI2, G_TRI1
+}
+
+fn is_sync(opcode: u32)->bool{
+    (0xE6..=0xE8).contains(&opcode) //G_RDPLOADSYNC, G_RDPPIPESYNC, G_RDPTILESYNC
+}
+
+//TMEM bytes written by a load from the load tile's G_SETTILE, None for loads the pass doesn't size
+fn load_range(cmd: u64, settile: u64)->Option<(usize, usize)>{
+    let (w0, w1) = split_cmd(cmd);
+    let (t0, _) = split_cmd(settile);
+    let start = (t0 & 0x1FF) as usize*8;
+    let siz = (t0 >> 19) & 3;
+    let bytes = match w0 >> 24 {
+        0xF3 if siz < 3 => ((((w1 >> 12) & 0xFFF) as usize + 1) << siz)/2, //G_LOADBLOCK
+        0xF4 => (((w1 & 0xFFF) as usize).saturating_sub((w0 & 0xFFF) as usize)/4 + 1)*((t0 >> 9) & 0x1FF) as usize*8, //G_LOADTILE
+        0xF0 => (((w1 >> 14) & 0x3FF) as usize + 1)*8, //G_LOADTLUT, quadricated
+        _ => return None,
+    };
+    Some((start, start + bytes.div_ceil(8)*8))
+}
+
+impl KnownState{
+    //whether the command changes nothing, updates the state otherwise
+    fn redundant(&mut self, cmd: u64)->bool{
+        let (w0, w1) = split_cmd(cmd);
+        let opcode = w0 >> 24;
+        let tile = (w1 >> 24) & 7;
+        match opcode {
+            0x00 | 0xC0 => true, //G_SPNOOP, G_NOOP
+            0xB7 => { //G_SETGEOMETRYMODE
+                let redundant = w1 & !self.geometry_set == 0;
+                self.geometry_set |= w1;
+                self.geometry_clear &= !w1;
+                redundant
+            },
+            0xB6 => { //G_CLEARGEOMETRYMODE
+                let redundant = w1 & !self.geometry_clear == 0;
+                self.geometry_clear |= w1;
+                self.geometry_set &= !w1;
+                redundant
+            },
+            0xBA | 0xB9 => { //G_SETOTHERMODE_H, G_SETOTHERMODE_L
+                let mask = match othermode_mask(w0) {
+                    Some(mask) => mask,
+                    None => { *self = KnownState::default(); return false },
+                };
+                let (known, value) = &mut self.othermode[(opcode == 0xB9) as usize];
+                let redundant = *known & mask == mask && *value & mask == w1 & mask;
+                *known |= mask;
+                *value = (*value & !mask) | (w1 & mask);
+                redundant
+            },
+            0xFC | 0xFA | 0xFB | 0xF7 | 0xF8 | 0xF9 | 0xBB | 0xFD | 0xF5 | 0xF2 => {
+                let key = match opcode {
+                    0xF5 | 0xF2 => (opcode << 8) | tile,
+                    _ => opcode,
+                };
+                self.commands.insert(key, cmd) == Some(cmd)
+            },
+            0xF3 | 0xF4 | 0xF0 => { //G_LOADBLOCK, G_LOADTILE, G_LOADTLUT
+                let settile = self.commands.get(&((0xF5 << 8) | tile)).cloned();
+                let timg = self.commands.get(&0xFD).cloned();
+                self.commands.remove(&((0xF2 << 8) | tile)); //loads set the load tile's size
+                let (start, end) = match settile.and_then(|settile| load_range(cmd, settile)) {
+                    Some(range) => range,
+                    None => { self.loads.clear(); return false },
+                };
+                let key = timg.zip(settile).map(|(timg, settile)| [timg, settile, cmd]);
+                if key.is_some_and(|key| self.loads.contains(&(start, end, key))) { return true }
+                self.loads.retain(|&(s, e, _)| e <= start || end <= s);
+                if let Some(key) = key { self.loads.push((start, end, key)) }
+                false
+            },
+            0x01 | 0x03 | 0x04 | 0xB1 | 0xB2 | 0xBD | 0xBE | 0xBF | 0xE6 | 0xE7 | 0xE8 | 0xE9 => false,
+            _ => { *self = KnownState::default(); false },
+        }
+    }
+}
+
+//triangles without the commands that drew them
+fn drawn(triangles: Vec<BKTriangle>)->Vec<BKTriangle>{
+    triangles.into_iter().map(|tri| BKTriangle{gfx_index: 0, ..tri}).collect()
+}
+
+impl BKGfxList{
+    /* entry points are where the list is started from outside (the geo list draws),
+        index 0 when empty. None, and the list unchanged, if the interpreter draws
+        anything differently afterwards.
+    */
+    pub fn optimize(&mut self, entry_points: &[usize], vertices: Option<&BKVertexList>, textures: Option<&BKTextureList>)->Option<BKGfxOptimizeReport>{
+        let cmds : Vec<u64> = self.gfx.iter().cloned().map(u64::from).collect();
+        let mut targets : HashSet<usize> = entry_points.iter().cloned().collect();
+        targets.insert(0);
+        for &cmd in cmds.iter() {
+            let (w0, w1) = split_cmd(cmd);
+            if w0 >> 24 == 0x06 {
+                if let Some(offset) = segment_offset(w1, GFX_SEGMENT) { targets.insert(offset/8); }
+            }
+        }
+
+        //state changes
+        let mut keep = vec![true; cmds.len()];
+        let mut state = KnownState::default();
+        for (i, &cmd) in cmds.iter().enumerate() {
+            if targets.contains(&i) { state = KnownState::default() }
+            keep[i] = !state.redundant(cmd);
+            if matches!(cmd >> 56, 0x06 | 0xB8) { state = KnownState::default() } //G_DL, G_ENDDL
+        }
+
+        //syncs
+        let mut drawn_since_pipesync = true;
+        for i in 0..cmds.len() {
+            if targets.contains(&i) { drawn_since_pipesync = true }
+            if !keep[i] { continue }
+            let opcode = (cmds[i] >> 56) as u32;
+            if !is_sync(opcode) {
+                if is_draw(opcode) || matches!(opcode, 0x06 | 0xB8) { drawn_since_pipesync = true }
+                continue
+            }
+            let next = (i + 1 .. cmds.len()).filter(|&j| keep[j]).map(|j| (cmds[j] >> 56) as u32)
+                .find(|&next| next == opcode || !is_sync(next));
+            let pointless = match next {
+                Some(next) => next == opcode || is_draw(next),
+                None => false,
+            };
+            if pointless || (opcode == 0xE7 && !drawn_since_pipesync) {
+                keep[i] = false;
+            } else if opcode == 0xE7 {
+                drawn_since_pipesync = false;
+            }
+        }
+
+        //new list, pairing G_TRI1
+        let mut out : Vec<u64> = Vec::new();
+        let mut index_map = vec![0; cmds.len()];
+        let mut merged_triangles = 0;
+        for i in 0..cmds.len() {
+            index_map[i] = out.len();
+            if !keep[i] { continue }
+            let cmd = cmds[i];
+            let previous = out.last().cloned();
+            let single = |cmd: u64| cmd >> 56 == 0xBF && cmd & 0xFF000000 == 0; //G_TRI1 without a flag
+            match previous {
+                Some(prev) if single(cmd) && single(prev) && !targets.contains(&i) => {
+                    *out.last_mut().unwrap() = (0xB1 << 56) | ((prev & 0xFFFFFF) << 32) | (cmd & 0xFFFFFF);
+                    index_map[i] = out.len() - 1;
+                    merged_triangles += 1;
+                },
+                _ => out.push(cmd),
+            }
+        }
+        let len = out.len();
+        for index in index_map.iter_mut() { *index = (*index).min(len.saturating_sub(1)) }
+        for cmd in out.iter_mut() {
+            let (w0, w1) = split_cmd(*cmd);
+            if w0 >> 24 == 0x06 {
+                if let Some(offset) = segment_offset(w1, GFX_SEGMENT) {
+                    let target = index_map.get(offset/8).cloned().unwrap_or(len);
+                    *cmd = ((w0 as u64) << 32) | (((GFX_SEGMENT as u32) << 24) | (target*8) as u32) as u64;
+                }
+            }
+        }
+
+        //same drawing before and after
+        let optimized = BKGfxList{gfx: out.into_iter().map(F3dex::from).collect(), header_filler: self.header_filler};
+        let run = |gfx: &BKGfxList, starts: &[usize]| match starts.is_empty() {
+            true => vec![drawn(BKGfxInterpreter::new(gfx, vertices, textures).run(0))],
+            false => starts.iter().map(|&start| drawn(BKGfxInterpreter::new(gfx, vertices, textures).run(start))).collect::<Vec<_>>(),
+        };
+        let new_entry_points : Vec<usize> = entry_points.iter().map(|&i| index_map.get(i).cloned().unwrap_or(i)).collect();
+        if run(self, entry_points) != run(&optimized, &new_entry_points) { return None }
+
+        let report = BKGfxOptimizeReport{
+            removed: keep.iter().filter(|&&k| !k).count(),
+            merged_triangles,
+            bytes_saved: self.size() - optimized.size(),
+            index_map,
+        };
+        self.gfx = optimized.gfx;
+        Some(report)
+    }
+}
+
+impl BKModel{
+    //optimizes the display list and points the geo list draws at the moved commands
+    pub fn optimize_display_list(&mut self)->Option<BKGfxOptimizeReport>{
+        let draws = self.geo_list.as_ref().map(|geo| geo.all_draws()).unwrap_or_default();
+        let mut entry_points : Vec<usize> = draws.iter().map(|draw| draw.gfx_index).collect();
+        entry_points.dedup();
+        let report = self.display_list.as_mut()?.optimize(&entry_points, self.vertices.as_ref(), self.texture_list.as_ref())?;
+        let starts = entry_points.iter().map(|&i| (i, report.index_map.get(i).cloned().unwrap_or(i))).collect();
+        if let Some(geo) = self.geo_list.as_mut() { remap_draws(geo, &starts) }
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::f3dex::tests::test_model;
+    use super::super::geo::{BKGeoCmd, BKGeoCommand, BKGeoList};
+
+    #[test]
+    fn gfx_optimize() {
+        let mut model = test_model();
+        let setup = [
+            0xE7000000_00000000u64, //G_RDPPIPESYNC
+            0xB7000000_00000204,    //G_SETGEOMETRYMODE shade, smooth
+            0xFC121824_FF33FFFF,    //G_SETCOMBINE
+            0xFD500000_02000000,    //G_SETTIMG
+            0xF5500000_07000000,    //G_SETTILE
+            0xE6000000_00000000,    //G_RDPLOADSYNC
+            0xF3000000_07003800,    //G_LOADBLOCK
+            0xE7000000_00000000,    //G_RDPPIPESYNC
+        ];
+        let mut cmds = setup.to_vec();
+        cmds.extend([0x0400103F_01000000, 0xBF000000_00000204, 0xBF000000_00020604]);
+        cmds.extend(setup); //all of it again
+        cmds.extend([0xB7000000_00000004, 0x00000000_00000000]); //already set, no-op
+        cmds.extend([0xBF000000_00000402, 0xB8000000_00000000]);
+        cmds.extend([0x06000000_03000000 + 8*(cmds.len() as u64 + 2), 0xB8000000_00000000]); //G_DL to the next list
+        cmds.extend(setup); //nothing known after a G_DL target
+        cmds.extend([0xBF000000_00000204, 0xB8000000_00000000]);
+        let gfx = BKGfxList::new(cmds.iter().cloned().map(F3dex::from).collect());
+        let before = gfx.size();
+        let call = cmds.len() - 12;
+        model.display_list = Some(gfx);
+        model.geo_list = Some(BKGeoList::new(vec![
+            BKGeoCommand::new(BKGeoCmd::LoadDL{gfx_index: 0, unk_a: 0}, Vec::new()),
+            BKGeoCommand::new(BKGeoCmd::LoadDL{gfx_index: call, unk_a: 0}, Vec::new()),
+        ]));
+        let triangles = |model: &BKModel| model.triangles().into_iter().map(|(tri, _)| (tri.vertices, tri.material)).collect::<Vec<_>>();
+        let drawn = triangles(&model);
+
+        let report = model.optimize_display_list().unwrap();
+        let gfx = model.display_list.as_ref().unwrap();
+        assert_eq!(report.removed, 12);
+        assert_eq!(report.merged_triangles, 1);
+        assert_eq!(report.bytes_saved, before - gfx.size());
+        assert_eq!(report.bytes_saved, 8*13);
+        assert_eq!(u64::from(gfx[8].clone()), 0xB1000204_00020604);
+        assert_eq!(triangles(&model), drawn);
+        let call = match model.geo_list.as_ref().unwrap().commands[1].cmd {
+            BKGeoCmd::LoadDL{gfx_index, ..} => gfx_index,
+            _ => unreachable!(),
+        };
+        assert_eq!(u64::from(gfx[call].clone()), 0x06000000_03000000 + 8*(call as u64 + 2));
+        assert_eq!(u64::from(gfx[call + 2].clone()), setup[0]);
+
+        //nothing left to do
+        let again = model.optimize_display_list().unwrap();
+        assert_eq!(again.bytes_saved, 0);
+
+        //othermode shifts past 32 bits are unknown commands, everything known is forgotten
+        let mut state = KnownState::default();
+        assert!(!state.redundant(0xBA000E02_00008000));
+        assert!(state.redundant(0xBA000E02_00008000));
+        assert!(!state.redundant(0xBA0000FF_00000000));
+        assert!(!state.redundant(0xBA000E02_00008000));
+    }
+}
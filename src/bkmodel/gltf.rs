@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_json::{json, Value};
+
+use super::BKModel;
+use super::super::bktexture::BKTextureFormat;
+use super::super::error::{GltfFloatError, VertexIndexError};
+
+/* glTF 2.0 export
+    one mesh, one primitive per bound texture (plus one untextured), positions
+    in model units. vertices are split per texture since UVs depend on its size.
+    bones come from BKAnimationList, each joint sits at its bone's position and
+    the mesh is skinned rigidly to the bone its geo list draw hangs from.
+    materials carry the texture format in extras and pick the alpha mode from it.
+    glTF has no NaN or infinity, a model holding one (bone positions are raw
+    floats) is rejected rather than written out as null.
+*/
+
+const ARRAY_BUFFER : u32 = 34962;
+const ELEMENT_ARRAY_BUFFER : u32 = 34963;
+const UNSIGNED_BYTE : u32 = 5121;
+const UNSIGNED_SHORT : u32 = 5123;
+const UNSIGNED_INT : u32 = 5125;
+const FLOAT : u32 = 5126;
+
+fn finite(values: &[f32])->Result<&[f32], GltfFloatError>{
+    values.iter().all(|v| v.is_finite()).then_some(values).ok_or(GltfFloatError)
+}
+
+fn json_floats(values: &[f32])->Result<Value, GltfFloatError>{
+    Ok(json!(finite(values)?))
+}
+
+fn float_bytes(values: &[f32])->Result<Vec<u8>, GltfFloatError>{
+    Ok(finite(values)?.iter().flat_map(|f| f.to_le_bytes()).collect())
+}
+
+fn alpha_mode(format: &BKTextureFormat)->&'static str{
+    match format {
+        BKTextureFormat::I4 | BKTextureFormat::I8 => "OPAQUE",
+        BKTextureFormat::IA8 | BKTextureFormat::IA16 | BKTextureFormat::RGBA32 => "BLEND",
+        _ => "MASK", //1 bit alpha
+    }
+}
+
+//adds the fields of `extra`, an object, to `object`
+fn extend(mut object: Value, extra: Value)->Value{
+    if let (Some(object), Value::Object(extra)) = (object.as_object_mut(), extra) { object.extend(extra) }
+    object
+}
+
+#[derive(Default)]
+struct GltfWriter{
+    buffer: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GltfWriter{
+    fn view(&mut self, bytes: &[u8], target: Option<u32>)->usize{
+        self.buffer.resize(self.buffer.len().div_ceil(4)*4, 0);
+        let view = json!({"buffer": 0, "byteOffset": self.buffer.len(), "byteLength": bytes.len()});
+        self.views.push(extend(view, target.map_or(Value::Null, |t| json!({"target": t}))));
+        self.buffer.extend_from_slice(bytes);
+        self.views.len() - 1
+    }
+
+    fn accessor(&mut self, bytes: &[u8], target: Option<u32>, component: u32, count: usize, kind: &str, extra: Value)->usize{
+        let view = self.view(bytes, target);
+        self.accessors.push(extend(json!({"bufferView": view, "componentType": component, "count": count, "type": kind}), extra));
+        self.accessors.len() - 1
+    }
+}
+
+#[derive(Default)]
+struct Primitive{
+    keys: BTreeMap<(usize, Option<usize>), u32>, //(vertex, bone) -> index
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[u8; 4]>,
+    joints: Vec<u16>,
+    indices: Vec<u32>,
+}
+
+impl BKModel{
+    //the document without its buffers
+    fn gltf_json(&self, writer: &mut GltfWriter)->Result<Value, Box<dyn Error>>{
+        let vertices = self.vertices.as_ref().ok_or(VertexIndexError)?;
+        let textures = self.texture_list.as_ref();
+        let bones = self.animation_list.as_ref().map(|list| &list.animations[..]).unwrap_or(&[]);
+        let joint_of = |bone: Option<usize>| bone.and_then(|b| bones.iter().position(|a| a.bone_id as usize == b).or((b < bones.len()).then_some(b)));
+
+        //images and materials, one per decodable texture
+        let mut images = Vec::new();
+        let mut materials = Vec::new();
+        let mut material_of = BTreeMap::new();
+        if let Some(list) = textures {
+            for (i, header) in list.texture_headers.iter().enumerate() {
+                let png = match list.texture(i, 0) {
+                    Some(texture) => texture.to_png_bytes()?,
+                    None => continue,
+                };
+                let view = writer.view(&png, None);
+                images.push(json!({"bufferView": view, "mimeType": "image/png"}));
+                let format = format!("{:?}", header.format);
+                material_of.insert(i, materials.len());
+                materials.push(json!({
+                    "name": format!("texture_{}_{}", i, format),
+                    "pbrMetallicRoughness": {"baseColorTexture": {"index": images.len() - 1}, "metallicFactor": 0, "roughnessFactor": 1},
+                    "alphaMode": alpha_mode(&header.format),
+                    "doubleSided": true,
+                    "extras": {"format": format, "width": header.width, "height": header.height},
+                }));
+            }
+        }
+        let textures_json : Vec<Value> = (0..images.len()).map(|i| json!({"sampler": 0, "source": i})).collect();
+        materials.push(json!({"name": "untextured", "pbrMetallicRoughness": {"metallicFactor": 0, "roughnessFactor": 1}, "doubleSided": true}));
+        let untextured = materials.len() - 1;
+
+        //primitives
+        let mut primitives : BTreeMap<usize, Primitive> = BTreeMap::new();
+        for (tri, bone) in self.triangles() {
+            let material = tri.material.texture_index.and_then(|i| material_of.get(&i).cloned()).unwrap_or(untextured);
+            if tri.vertices.iter().any(|&v| v >= vertices.len()) { return Err(Box::new(VertexIndexError)) }
+            let primitive = primitives.entry(material).or_default();
+            for corner in 0..3 {
+                let v = tri.vertices[corner];
+                let next = primitive.positions.len() as u32;
+                let index = *primitive.keys.entry((v, bone)).or_insert(next);
+                if index == next {
+                    primitive.positions.push(vertices[v].ob.map(|c| c as f32));
+                    primitive.uvs.push(tri.uv(corner, vertices, textures));
+                    primitive.colors.push(vertices.color(v));
+                    primitive.joints.push(joint_of(bone).unwrap_or(0) as u16);
+                }
+                primitive.indices.push(index);
+            }
+        }
+
+        let skinned = !bones.is_empty();
+        let mut primitives_json = Vec::new();
+        for (material, p) in primitives.iter() {
+            let count = p.positions.len();
+            let min : Vec<f32> = (0..3).map(|i| p.positions.iter().map(|v| v[i]).fold(f32::MAX, f32::min)).collect();
+            let max : Vec<f32> = (0..3).map(|i| p.positions.iter().map(|v| v[i]).fold(f32::MIN, f32::max)).collect();
+            let bounds = json!({"min": json_floats(&min)?, "max": json_floats(&max)?});
+            let position = writer.accessor(&float_bytes(p.positions.as_flattened())?, Some(ARRAY_BUFFER), FLOAT, count, "VEC3", bounds);
+            let uv = writer.accessor(&float_bytes(p.uvs.as_flattened())?, Some(ARRAY_BUFFER), FLOAT, count, "VEC2", Value::Null);
+            let color = writer.accessor(&p.colors.concat(), Some(ARRAY_BUFFER), UNSIGNED_BYTE, count, "VEC4", json!({"normalized": true}));
+            let indices = writer.accessor(&p.indices.iter().flat_map(|i| i.to_le_bytes()).collect::<Vec<u8>>(), Some(ELEMENT_ARRAY_BUFFER), UNSIGNED_INT, p.indices.len(), "SCALAR", Value::Null);
+
+            let mut attributes = json!({"POSITION": position, "TEXCOORD_0": uv, "COLOR_0": color});
+            if skinned {
+                let joints = writer.accessor(&p.joints.iter().flat_map(|&j| [j, 0, 0, 0]).flat_map(u16::to_le_bytes).collect::<Vec<u8>>(), Some(ARRAY_BUFFER), UNSIGNED_SHORT, count, "VEC4", Value::Null);
+                let weights = writer.accessor(&p.joints.iter().flat_map(|_| [1.0f32, 0.0, 0.0, 0.0]).flat_map(f32::to_le_bytes).collect::<Vec<u8>>(), Some(ARRAY_BUFFER), FLOAT, count, "VEC4", Value::Null);
+                attributes = extend(attributes, json!({"JOINTS_0": joints, "WEIGHTS_0": weights}));
+            }
+            primitives_json.push(json!({"attributes": attributes, "indices": indices, "material": material, "mode": 4}));
+        }
+
+        //nodes: the mesh, then one joint per bone
+        let mut nodes = vec![match skinned {
+            true => json!({"name": "model", "mesh": 0, "skin": 0}),
+            false => json!({"name": "model", "mesh": 0}),
+        }];
+        let mut scene_nodes = vec![0];
+        let mut skins = Vec::new();
+        if skinned {
+            let parent_of = |bone: &super::BKAnimation| bones.iter().position(|b| b.bone_id == bone.mtx_id && bone.mtx_id != bone.bone_id);
+            for (i, bone) in bones.iter().enumerate() {
+                let parent_position = parent_of(bone).map_or([0.0; 3], |p| bones[p].unk_0);
+                let translation : Vec<f32> = (0..3).map(|k| bone.unk_0[k] - parent_position[k]).collect();
+                let children : Vec<usize> = (0..bones.len()).filter(|&c| c != i && parent_of(&bones[c]) == Some(i)).map(|c| c + 1).collect();
+                let node = json!({"name": format!("bone_{}", bone.bone_id), "translation": json_floats(&translation)?});
+                nodes.push(extend(node, if children.is_empty() { Value::Null } else { json!({"children": children}) }));
+                if parent_of(bone).is_none() { scene_nodes.push(i + 1) }
+            }
+            let inverse_bind : Vec<f32> = bones.iter()
+                .flat_map(|b| [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, -b.unk_0[0], -b.unk_0[1], -b.unk_0[2], 1.0f32])
+                .collect();
+            let inverse_bind = writer.accessor(&float_bytes(&inverse_bind)?, None, FLOAT, bones.len(), "MAT4", Value::Null);
+            skins.push(json!({"inverseBindMatrices": inverse_bind, "joints": (1..=bones.len()).collect::<Vec<_>>()}));
+        }
+
+        let mut json = json!({
+            "asset": {"version": "2.0", "generator": "bkasset"},
+            "scene": 0,
+            "scenes": [{"nodes": scene_nodes}],
+            "nodes": nodes,
+            "meshes": [{"name": "model", "primitives": primitives_json}],
+            "materials": materials,
+        });
+        if !images.is_empty() {
+            json = extend(json, json!({"images": images, "textures": textures_json, "samplers": [{"magFilter": 9729, "minFilter": 9729}]}));
+        }
+        if !skins.is_empty() {
+            json = extend(json, json!({"skins": skins}));
+        }
+        Ok(extend(json, json!({"accessors": writer.accessors, "bufferViews": writer.views})))
+    }
+
+    //.gltf with the buffer embedded as a data uri
+    pub fn to_gltf(&self)->Result<String, Box<dyn Error>>{
+        let mut writer = GltfWriter::default();
+        let json = self.gltf_json(&mut writer)?;
+        let uri = format!("data:application/octet-stream;base64,{}", BASE64.encode(&writer.buffer));
+        Ok(extend(json, json!({"buffers": [{"byteLength": writer.buffer.len(), "uri": uri}]})).to_string())
+    }
+
+    pub fn to_glb(&self)->Result<Vec<u8>, Box<dyn Error>>{
+        let mut writer = GltfWriter::default();
+        let json = self.gltf_json(&mut writer)?;
+        let mut json = extend(json, json!({"buffers": [{"byteLength": writer.buffer.len()}]})).to_string().into_bytes();
+        json.resize(json.len().div_ceil(4)*4, b' ');
+        let mut bin = writer.buffer;
+        bin.resize(bin.len().div_ceil(4)*4, 0);
+
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        Ok([
+            b"glTF".as_slice(), &2u32.to_le_bytes(), &(length as u32).to_le_bytes(),
+            &(json.len() as u32).to_le_bytes(), b"JSON", &json,
+            &(bin.len() as u32).to_le_bytes(), b"BIN\0", &bin,
+        ].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::f3dex::tests::test_model;
+
+    #[test]
+    fn gltf_export() {
+        let model = test_model();
+        let gltf : Value = serde_json::from_str(&model.to_gltf().unwrap()).unwrap();
+        assert_eq!(gltf["asset"]["version"], "2.0");
+        assert_eq!(gltf["materials"][0]["name"], "texture_0_I8");
+        assert_eq!(gltf["materials"][0]["alphaMode"], "OPAQUE");
+        let position = &gltf["accessors"][gltf["meshes"][0]["primitives"][0]["attributes"]["POSITION"].as_u64().unwrap() as usize];
+        assert_eq!((&position["min"], &position["max"]), (&json!([0.0, 0.0, 0.0]), &json!([100.0, 100.0, 0.0])));
+        assert!(gltf["buffers"][0]["uri"].as_str().unwrap().starts_with("data:application/octet-stream;base64,"));
+
+        let glb = model.to_glb().unwrap();
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize, glb.len());
+
+        let mut broken = test_model();
+        broken.vertices = None;
+        assert!(broken.to_gltf().unwrap_err().is::<VertexIndexError>());
+
+        let mut nan_bone = test_model();
+        nan_bone.animation_list = Some(super::super::BKAnimationList{unk_0: 0.0, animations: vec![
+            super::super::BKAnimation{unk_0: [0.0, f32::NAN, 0.0], bone_id: 0, mtx_id: 0},
+        ]});
+        assert!(nan_bone.to_gltf().unwrap_err().is::<GltfFloatError>());
+        assert!(nan_bone.to_glb().unwrap_err().is::<GltfFloatError>());
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use libultra::Vtx;
+use serde_json::Value;
+
+use super::{BKModel, BKGfxList, BKTextureList, BKMaterialGroup, BKGeoList, BKGeoCommand, BKGeoCmd};
+use super::geo_traverse::{BKMatrix, MTX_IDENTITY, mtx_mul, transform_point};
+use super::super::bktexture::{BKTexture, BKTextureHeader};
+use super::super::pixels::RGBA32;
+use super::super::error::{GltfFormatError, TextureSizeError, VertexCountError, VertexRangeError};
+
+/* glTF 2.0 import
+    every mesh node of the default scene is baked into model space with its world
+    transform. triangles (strips and fans are unrolled) are grouped by the image of
+    their material's base colour texture, everything else is drawn untextured.
+    textures are encoded in the smallest format within max_texture_error of the
+    source image and must fit TMEM whole, no mipmaps are generated.
+    vertex positions and S10.5 texture coordinates are rounded to 16 bits,
+    COLOR_0 becomes the vertex colour, white when missing.
+    the geo list is a single LoadDL of the generated display list.
+*/
+
+const MAX_VERTICES : usize = i16::MAX as usize; //the vertex list header holds an i16 count
+const MAX_TEXTURE_SIZE : usize = 0xFF;         //width and height are bytes in the texture header
+
+type ViewBytes<'a> = (&'a [u8], Option<usize>); //bytes and byte stride
+
+struct GltfDocument<'a>{
+    json: Value,
+    buffers: Vec<Vec<u8>>,
+    dir: Option<&'a Path>,
+}
+
+fn format_error()->Box<dyn Error>{
+    Box::new(GltfFormatError)
+}
+
+fn as_usize(value: &Value)->Option<usize>{
+    usize::try_from(value.as_u64()?).ok()
+}
+
+fn as_list(value: Option<&Value>)->&[Value]{
+    value.and_then(Value::as_array).map(Vec::as_slice).unwrap_or(&[])
+}
+
+impl GltfDocument<'_>{
+    fn new<'a>(json: &str, bin: Option<&[u8]>, dir: Option<&'a Path>)->Result<GltfDocument<'a>, Box<dyn Error>>{
+        let json : Value = serde_json::from_str(json).map_err(|_| format_error())?;
+        let mut doc = GltfDocument{json, buffers: Vec::new(), dir};
+        let buffers : Vec<Option<String>> = doc.list("buffers").iter()
+            .map(|buffer| buffer.get("uri").and_then(Value::as_str).map(String::from))
+            .collect();
+        for (i, uri) in buffers.into_iter().enumerate() {
+            let bytes = match (uri, i, bin) {
+                (Some(uri), _, _) => doc.read_uri(&uri)?,
+                (None, 0, Some(bin)) => bin.to_vec(), //the GLB binary chunk
+                _ => return Err(format_error()),
+            };
+            doc.buffers.push(bytes);
+        }
+        Ok(doc)
+    }
+
+    fn list(&self, key: &str)->&[Value]{
+        as_list(self.json.get(key))
+    }
+
+    fn item(&self, key: &str, index: Option<usize>)->Result<&Value, Box<dyn Error>>{
+        index.and_then(|i| self.list(key).get(i)).ok_or_else(format_error)
+    }
+
+    fn read_uri(&self, uri: &str)->Result<Vec<u8>, Box<dyn Error>>{
+        if let Some(data) = uri.strip_prefix("data:") {
+            let (_, encoded) = data.split_once(";base64,").ok_or_else(format_error)?;
+            return BASE64.decode(encoded).map_err(|_| format_error())
+        }
+        let dir = self.dir.ok_or_else(format_error)?;
+        Ok(fs::read(dir.join(uri))?)
+    }
+
+    fn buffer_view(&self, index: Option<usize>)->Result<ViewBytes<'_>, Box<dyn Error>>{
+        let view = self.item("bufferViews", index)?;
+        let field = |key: &str| view.get(key).and_then(as_usize);
+        let buffer = field("buffer").and_then(|b| self.buffers.get(b)).ok_or_else(format_error)?;
+        let offset = field("byteOffset").unwrap_or(0);
+        let length = field("byteLength").ok_or_else(format_error)?;
+        let bytes = buffer.get(offset .. offset + length).ok_or_else(format_error)?;
+        Ok((bytes, field("byteStride")))
+    }
+
+    //every element of an accessor as floats, normalized integers are mapped to 0.0-1.0 (-1.0-1.0 signed)
+    fn accessor(&self, index: Option<usize>)->Result<Vec<Vec<f64>>, Box<dyn Error>>{
+        let accessor = self.item("accessors", index)?;
+        if accessor.get("sparse").is_some() { return Err(format_error()) }
+        let count = accessor.get("count").and_then(as_usize).ok_or_else(format_error)?;
+        let components = match accessor.get("type").and_then(Value::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            _ => return Err(format_error()),
+        };
+        let component_type = accessor.get("componentType").and_then(as_usize).ok_or_else(format_error)?;
+        let size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err(format_error()),
+        };
+        let normalized = accessor.get("normalized").and_then(Value::as_bool).unwrap_or(false);
+        let view = accessor.get("bufferView").and_then(as_usize);
+        if view.is_none() { return Ok(vec![vec![0.0; components]; count]) }
+
+        let (bytes, stride) = self.buffer_view(view)?;
+        let stride = stride.unwrap_or(components*size);
+        let offset = accessor.get("byteOffset").and_then(as_usize).unwrap_or(0);
+        let read = |at: usize|->Option<f64>{
+            let b = bytes.get(at .. at + size)?;
+            Some(match (component_type, normalized) {
+                (5120, false) => b[0] as i8 as f64,
+                (5120, true) => (b[0] as i8 as f64/127.0).max(-1.0),
+                (5121, false) => b[0] as f64,
+                (5121, true) => b[0] as f64/255.0,
+                (5122, false) => i16::from_le_bytes([b[0], b[1]]) as f64,
+                (5122, true) => (i16::from_le_bytes([b[0], b[1]]) as f64/32767.0).max(-1.0),
+                (5123, false) => u16::from_le_bytes([b[0], b[1]]) as f64,
+                (5123, true) => u16::from_le_bytes([b[0], b[1]]) as f64/65535.0,
+                (5125, _) => u32::from_le_bytes(b.try_into().ok()?) as f64,
+                _ => f32::from_le_bytes(b.try_into().ok()?) as f64,
+            })
+        };
+        (0..count)
+            .map(|i| (0..components).map(|c| read(offset + i*stride + c*size)).collect::<Option<Vec<f64>>>())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(format_error)
+    }
+
+    fn image_bytes(&self, index: Option<usize>)->Result<Vec<u8>, Box<dyn Error>>{
+        let image = self.item("images", index)?;
+        match image.get("uri").and_then(Value::as_str) {
+            Some(uri) => self.read_uri(uri),
+            None => Ok(self.buffer_view(image.get("bufferView").and_then(as_usize))?.0.to_vec()),
+        }
+    }
+
+    //image of a material's base colour texture
+    fn material_image(&self, material: Option<usize>)->Option<usize>{
+        let texture = self.list("materials").get(material?)?
+            .get("pbrMetallicRoughness")?
+            .get("baseColorTexture")?
+            .get("index")
+            .and_then(as_usize)?;
+        self.list("textures").get(texture)?.get("source").and_then(as_usize)
+    }
+
+    //(mesh, world transform) of every mesh node in the default scene
+    fn mesh_nodes(&self)->Vec<(usize, BKMatrix)>{
+        let nodes = self.list("nodes");
+        let roots : Vec<usize> = match self.list("scenes").get(self.json.get("scene").and_then(as_usize).unwrap_or(0)) {
+            Some(scene) => as_list(scene.get("nodes")).iter().filter_map(as_usize).collect(),
+            None => (0..nodes.len())
+                .filter(|i| !nodes.iter().any(|n| as_list(n.get("children")).iter().any(|c| as_usize(c) == Some(*i))))
+                .collect(),
+        };
+
+        let mut out = Vec::new();
+        let mut stack : Vec<(usize, BKMatrix, usize)> = roots.into_iter().map(|i| (i, MTX_IDENTITY, 0)).collect();
+        while let Some((index, parent, depth)) = stack.pop() {
+            let node = match nodes.get(index) {
+                Some(node) if depth < nodes.len() => node, //depth guards against cycles
+                _ => continue,
+            };
+            let transform = mtx_mul(&parent, &node_matrix(node));
+            if let Some(mesh) = node.get("mesh").and_then(as_usize) {
+                out.push((mesh, transform));
+            }
+            for child in as_list(node.get("children")).iter().rev() {
+                if let Some(child) = as_usize(child) { stack.push((child, transform, depth + 1)) }
+            }
+        }
+        out
+    }
+}
+
+fn node_matrix(node: &Value)->BKMatrix{
+    let floats = |key: &str| node.get(key)
+        .and_then(Value::as_array)
+        .map(|a| a.iter().map(|v| v.as_f64().unwrap_or(0.0) as f32).collect::<Vec<f32>>());
+    if let Some(m) = floats("matrix").filter(|m| m.len() == 16) {
+        return std::array::from_fn(|row| std::array::from_fn(|col| m[4*col + row])) //column major
+    }
+    let t = floats("translation").filter(|v| v.len() == 3).unwrap_or(vec![0.0; 3]);
+    let q = floats("rotation").filter(|v| v.len() == 4).unwrap_or(vec![0.0, 0.0, 0.0, 1.0]);
+    let s = floats("scale").filter(|v| v.len() == 3).unwrap_or(vec![1.0; 3]);
+    let (x, y, z, w) = (q[0], q[1], q[2], q[3]);
+    let r = [
+        [1.0 - 2.0*(y*y + z*z), 2.0*(x*y - z*w), 2.0*(x*z + y*w)],
+        [2.0*(x*y + z*w), 1.0 - 2.0*(x*x + z*z), 2.0*(y*z - x*w)],
+        [2.0*(x*z - y*w), 2.0*(y*z + x*w), 1.0 - 2.0*(x*x + y*y)],
+    ];
+    let mut m = MTX_IDENTITY;
+    for i in 0..3 {
+        for j in 0..3 { m[i][j] = r[i][j]*s[j] }
+        m[i][3] = t[i];
+    }
+    m
+}
+
+//triangle list indices for a primitive mode
+fn triangulate(mode: usize, indices: &[usize])->Option<Vec<[usize; 3]>>{
+    let n = indices.len();
+    Some(match mode {
+        4 => indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+        5 => (0..n.saturating_sub(2)).map(|i| match i % 2 {
+            0 => [indices[i], indices[i + 1], indices[i + 2]],
+            _ => [indices[i + 1], indices[i], indices[i + 2]],
+        }).collect(),
+        6 => (1..n.saturating_sub(1)).map(|i| [indices[0], indices[i], indices[i + 1]]).collect(),
+        _ => return None,
+    })
+}
+
+fn to_i16(value: f64)->Result<i16, VertexRangeError>{
+    let value = value.round();
+    match (i16::MIN as f64 ..= i16::MAX as f64).contains(&value) {
+        true => Ok(value as i16),
+        false => Err(VertexRangeError),
+    }
+}
+
+//texture list from the source images, texture index by image index
+fn import_textures(doc: &GltfDocument, images: &[usize], max_error: f32)->Result<(BKTextureList, HashMap<usize, usize>), Box<dyn Error>>{
+    let mut list = BKTextureList{texture_headers: Vec::new(), texture_data: Vec::new()};
+    let mut index_of = HashMap::new();
+    for &image in images {
+        let texture = BKTexture::<RGBA32>::from_png_bytes(&doc.image_bytes(Some(image))?)?;
+        let (width, height) = (texture.width(), texture.height());
+        if width == 0 || height == 0 || width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE { return Err(Box::new(TextureSizeError)) }
+        let (format, bytes) = texture.encode_best(max_error).ok_or(TextureSizeError)?;
+        let header = BKTextureHeader{offset: list.texture_data.len(), format, flags: 0, level_count: 0, width, height, padding: [0; 6]};
+        if header.validate_tmem().iter().any(|issue| issue.is_error()) { return Err(Box::new(TextureSizeError)) }
+
+        list.texture_data.extend(bytes);
+        list.texture_data.resize(list.texture_data.len().div_ceil(8)*8, 0); //loads read 8 byte aligned RDRAM
+        index_of.insert(image, list.texture_headers.len());
+        list.texture_headers.push(header);
+    }
+    Ok((list, index_of))
+}
+
+impl BKModel{
+    //.gltf text, external buffers and images are read relative to dir
+    pub fn from_gltf(json: &str, dir: &Path, max_texture_error: f32)->Result<BKModel, Box<dyn Error>>{
+        BKModel::from_gltf_document(&GltfDocument::new(json, None, Some(dir))?, max_texture_error)
+    }
+
+    //self contained .glb
+    pub fn from_glb(bytes: &[u8], max_texture_error: f32)->Result<BKModel, Box<dyn Error>>{
+        let u32_at = |o: usize| bytes.get(o .. o + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize);
+        if bytes.get(0..4) != Some(b"glTF") || u32_at(4) != Some(2) { return Err(format_error()) }
+
+        let mut json = None;
+        let mut bin = None;
+        let mut offset = 12;
+        while let Some(length) = u32_at(offset) {
+            let chunk = bytes.get(offset + 8 .. offset + 8 + length).ok_or_else(format_error)?;
+            match bytes.get(offset + 4 .. offset + 8) {
+                Some(b"JSON") => json = Some(std::str::from_utf8(chunk)?),
+                Some(b"BIN\0") => bin = Some(chunk),
+                _ => {},
+            }
+            offset += 8 + length;
+        }
+        let json = json.ok_or_else(format_error)?;
+        BKModel::from_gltf_document(&GltfDocument::new(json, bin, None)?, max_texture_error)
+    }
+
+    fn from_gltf_document(doc: &GltfDocument, max_texture_error: f32)->Result<BKModel, Box<dyn Error>>{
+        //primitives with their image and transform
+        let mut primitives = Vec::new();
+        for (mesh, transform) in doc.mesh_nodes() {
+            let mesh = doc.item("meshes", Some(mesh))?;
+            for primitive in as_list(mesh.get("primitives")) {
+                let image = doc.material_image(primitive.get("material").and_then(as_usize));
+                primitives.push((primitive, image, transform));
+            }
+        }
+
+        let mut images : Vec<usize> = primitives.iter().filter_map(|(_, image, _)| *image).collect();
+        images.sort();
+        images.dedup();
+        let (texture_list, texture_of) = import_textures(doc, &images, max_texture_error)?;
+
+        let mut vertices : Vec<Vtx> = Vec::new();
+        let mut vertex_of : HashMap<(Option<usize>, [u8; 16]), usize> = HashMap::new();
+        let mut groups : BTreeMap<Option<usize>, Vec<[usize; 3]>> = BTreeMap::new();
+        for (primitive, image, transform) in primitives {
+            let attribute = |key: &str| primitive.get("attributes").and_then(|a| a.get(key)).and_then(as_usize);
+            let positions = doc.accessor(Some(attribute("POSITION").ok_or_else(format_error)?))?;
+            let uvs = attribute("TEXCOORD_0").map(|a| doc.accessor(Some(a))).transpose()?;
+            let colors = attribute("COLOR_0").map(|a| doc.accessor(Some(a))).transpose()?;
+            let indices : Vec<usize> = match primitive.get("indices").and_then(as_usize) {
+                Some(accessor) => doc.accessor(Some(accessor))?.iter().map(|i| i[0] as usize).collect(),
+                None => (0..positions.len()).collect(),
+            };
+            let mode = primitive.get("mode").and_then(as_usize).unwrap_or(4);
+            let triangles = triangulate(mode, &indices).ok_or_else(format_error)?;
+
+            let texture_index = image.and_then(|i| texture_of.get(&i).cloned());
+            let size = texture_index.map_or([0.0; 2], |i| {
+                let header = &texture_list.texture_headers[i];
+                [header.width as f64, header.height as f64]
+            });
+            let mut vertex = |i: usize|->Result<usize, Box<dyn Error>>{
+                let position = positions.get(i).ok_or_else(format_error)?;
+                let ob = transform_point(&transform, [position[0] as f32, position[1] as f32, position[2] as f32]);
+                let uv = uvs.as_ref().and_then(|uvs| uvs.get(i)).map_or([0.0; 2], |uv| [uv[0], uv[1]]);
+                let color = colors.as_ref().and_then(|c| c.get(i)).map_or([0xFF; 4], |c| {
+                    std::array::from_fn(|k| c.get(k).map_or(0xFF, |v| (v.clamp(0.0, 1.0)*255.0).round() as u8))
+                });
+
+                let mut bytes = [0u8; 16];
+                for k in 0..3 { bytes[2*k .. 2*k + 2].copy_from_slice(&to_i16(ob[k] as f64)?.to_be_bytes()) }
+                for k in 0..2 { bytes[8 + 2*k .. 10 + 2*k].copy_from_slice(&to_i16(uv[k]*size[k]*32.0)?.to_be_bytes()) }
+                bytes[12..16].copy_from_slice(&color);
+                let next = vertices.len();
+                let index = *vertex_of.entry((texture_index, bytes)).or_insert(next);
+                if index == next { vertices.push(Vtx::from_be_bytes(&bytes)) }
+                Ok(index)
+            };
+            let mut group = Vec::with_capacity(triangles.len());
+            for tri in triangles {
+                group.push([vertex(tri[0])?, vertex(tri[1])?, vertex(tri[2])?]);
+            }
+            groups.entry(texture_index).or_default().extend(group);
+        }
+
+        let groups : Vec<BKMaterialGroup> = groups.into_iter()
+            .map(|(texture_index, triangles)| BKMaterialGroup{texture_index, material: None, triangles})
+            .collect();
+        let textures = (!texture_list.texture_headers.is_empty()).then_some(texture_list);
+        let (display_list, vertex_list) = BKGfxList::from_material_groups(&groups, &vertices, textures.as_ref());
+        if vertex_list.len() > MAX_VERTICES { return Err(Box::new(VertexCountError)) }
+
+        let mut model = BKModel{
+            texture_list: textures,
+            display_list: Some(display_list),
+            vertices: Some(vertex_list),
+            geo_list: Some(BKGeoList::new(vec![BKGeoCommand::new(BKGeoCmd::LoadDL{gfx_index: 0, unk_a: 0}, Vec::new())])),
+            ..Default::default()
+        };
+        model.header = model.create_header();
+        model.layout = Some(model.section_layout());
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::f3dex::tests::test_model;
+    use super::super::super::bktexture::BKTextureFormat;
+
+    #[test]
+    fn gltf_import_round_trip() {
+        let source = test_model();
+        let model = BKModel::from_glb(&source.to_glb().unwrap(), 0.0).unwrap();
+        let textures = model.texture_list.as_ref().unwrap();
+        assert_eq!(textures.texture_headers[0].format, BKTextureFormat::I8);
+        assert_eq!(textures.texture(0, 0).unwrap().tmem, source.texture_list.as_ref().unwrap().texture(0, 0).unwrap().tmem);
+
+        let vertices = model.vertices.as_ref().unwrap();
+        let triangles = model.triangles();
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles[0].0.material.texture_index, Some(0));
+        assert_eq!(triangles[1].0.vertices.map(|v| vertices[v].ob), [[100, 0, 0], [100, 100, 0], [0, 100, 0]]);
+        assert_eq!(vertices.tex_coords(triangles[1].0.vertices[1]), [64, 64]);
+        assert_eq!(vertices.color(0), [0xFF, 0x80, 0x40, 0xFF]);
+
+        assert_eq!(model.header, model.create_header());
+        let reread = BKModel::try_from_be_bytes(&model.to_be_bytes()).unwrap();
+        assert_eq!(reread.to_be_bytes(), model.to_be_bytes());
+        assert_eq!(reread.geo_list.unwrap().commands[0].cmd, BKGeoCmd::LoadDL{gfx_index: 0, unk_a: 0});
+
+        let gltf = BKModel::from_gltf(&source.to_gltf().unwrap(), Path::new("."), 0.0).unwrap();
+        assert_eq!(gltf.to_be_bytes(), model.to_be_bytes());
+    }
+
+    #[test]
+    fn gltf_import_limits() {
+        let positions : Vec<u8> = [0.0f32, 0.0, 0.0, 40000.0, 0.0, 0.0, 0.0, 1.0, 0.0].iter().flat_map(|f| f.to_le_bytes()).collect();
+        let json = format!(
+            r#"{{"asset":{{"version":"2.0"}},"nodes":[{{"mesh":0}}],"meshes":[{{"primitives":[{{"attributes":{{"POSITION":0}}}}]}}],"accessors":[{{"bufferView":0,"componentType":5126,"count":3,"type":"VEC3"}}],"bufferViews":[{{"buffer":0,"byteLength":36}}],"buffers":[{{"byteLength":36,"uri":"data:application/octet-stream;base64,{}"}}]}}"#,
+            BASE64.encode(&positions)
+        );
+        let err = BKModel::from_gltf(&json, Path::new("."), 0.0).unwrap_err();
+        assert!(err.downcast_ref::<VertexRangeError>().is_some());
+
+        //64x64 noise only fits as RGBA32, 16KB doesn't fit TMEM
+        let mut source = test_model();
+        let mut seed = 1u32;
+        let noise : Vec<u8> = (0..64*64*4).map(|_| { seed = seed.wrapping_mul(1103515245).wrapping_add(12345); (seed >> 16) as u8 }).collect();
+        source.texture_list = Some(BKTextureList{
+            texture_headers: vec![BKTextureHeader{offset: 0, format: BKTextureFormat::RGBA32, flags: 0, level_count: 0, width: 64, height: 64, padding: [0; 6]}],
+            texture_data: noise,
+        });
+        let err = BKModel::from_glb(&source.to_glb().unwrap(), 0.0).unwrap_err();
+        assert!(err.downcast_ref::<TextureSizeError>().is_some());
+    }
+}
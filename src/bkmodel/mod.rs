@@ -1,153 +1,5 @@
 use std::{fmt, thread::current, process::Output};
-
-trait BKGeo : std::fmt::Debug{
-}
-
-impl dyn BKGeo{
-    fn from_be_bytes(in_bytes: &[u8])->Box<dyn BKGeo>{
-        let cmd = u32::from_be_bytes(in_bytes[0..4].try_into().unwrap());
-        match cmd {
-            0 => Box::new(GeoCmd0::from_be_bytes(in_bytes)),
-            2 => Box::new(GeoCmd2::from_be_bytes(in_bytes)),
-            3 => Box::new(GeoCmd3::from_be_bytes(in_bytes)),
-            5 => Box::new(GeoCmd5::from_be_bytes(in_bytes)),
-            10 => Box::new(GeoCmdA::from_be_bytes(in_bytes)),
-            12 => Box::new(GeoCmdC{}),
-            15 => Box::new(GeoCmdF{}),
-            _ => panic!("Unknown gfx_command {}", cmd),
-        }
-    }
-}
-
-#[derive(Debug)]
-struct GeoCmdC{}
-impl BKGeo for GeoCmdC{}
-#[derive(Debug)]
-struct GeoCmdF{}
-impl BKGeo for GeoCmdF{}
-
-
-#[derive(Debug)]
-struct GeoCmd0 {
-    children: Option<BKGeoList>,
-    position: [f32; 3],
-}
-
-impl GeoCmd0 {
-    fn from_be_bytes(in_bytes: &[u8])->Self{
-        let cmd_0  = i32::from_be_bytes(in_bytes[0..4].try_into().unwrap());
-        assert_eq!(cmd_0, 0);
-        let child_offset = i16::from_be_bytes(in_bytes[8..10].try_into().unwrap()) as usize;
-        GeoCmd0{
-            children : if child_offset == 0 {None} else {Some(BKGeoList::from_be_bytes(&in_bytes[child_offset..]))},
-            position : [f32::from_be_bytes(in_bytes[0xC..0x10].try_into().unwrap()), f32::from_be_bytes(in_bytes[0x10..0x14].try_into().unwrap()), f32::from_be_bytes(in_bytes[0x14..0x18].try_into().unwrap())],
-        }
-    }
-}
-
-impl BKGeo for GeoCmd0{}
-
-#[derive(Debug)]
-struct GeoCmd2 {
-    children: Option<BKGeoList>,
-    mtx_index: usize,
-}
-
-impl GeoCmd2 {
-    fn from_be_bytes(in_bytes: &[u8])->Self{
-        let cmd_0  = i32::from_be_bytes(in_bytes[0..4].try_into().unwrap());
-        assert_eq!(cmd_0, 2);
-        let child_offset = in_bytes[8] as usize;
-        GeoCmd2{
-            children : if child_offset == 0 {None} else {Some(BKGeoList::from_be_bytes(&in_bytes[child_offset..]))},
-            mtx_index :in_bytes[9] as usize,
-        }
-    }
-}
-
-impl BKGeo for GeoCmd2{}
-
-#[derive(Debug)]
-struct GeoCmd3 {
-    gfx_offset: usize,
-}
-
-impl GeoCmd3 {
-    fn from_be_bytes(in_bytes: &[u8])->Self{
-        let cmd_0  = i32::from_be_bytes(in_bytes[0..4].try_into().unwrap());
-        assert_eq!(cmd_0, 3);
-        GeoCmd3{
-            gfx_offset : i16::from_be_bytes(in_bytes[8..10].try_into().unwrap()) as usize
-        }
-    }
-}
-
-impl BKGeo for GeoCmd3{}
-
-#[derive(Debug)]
-struct GeoCmd5{
-    gfx_index_list: Vec<usize>,
-}
-
-impl GeoCmd5 {
-    fn from_be_bytes(in_bytes: &[u8])->Self{
-        let cmd_0  = i32::from_be_bytes(in_bytes[0..4].try_into().unwrap());
-        assert_eq!(cmd_0, 5);
-        let mut offset_iter = in_bytes[8..].chunks_exact(2).map(|b| i16::from_be_bytes([b[0], b[1]]) as usize);
-        let gfx_index_list = offset_iter.clone().take(1)
-        .chain(offset_iter.skip(1).take_while(|&val| val != 0))
-        .collect();
-    let child_offset = i16::from_be_bytes(in_bytes[8..10].try_into().unwrap()) as usize;
-    GeoCmd5{
-        gfx_index_list,
-        }
-    }
-}
-
-impl BKGeo for GeoCmd5{}
-
-#[derive(Debug)]
-struct GeoCmdA{
-    unk_8: usize,
-    unk_a: usize,
-    unk_c: [f32; 3],
-}
-
-impl GeoCmdA {
-    fn from_be_bytes(in_bytes: &[u8])->Self{
-        let cmd_0  = i32::from_be_bytes(in_bytes[0..4].try_into().unwrap());
-        assert_eq!(cmd_0, 10);
-        let unk_8 = i16::from_be_bytes([in_bytes[8], in_bytes[9]]) as usize;
-        let unk_a = i16::from_be_bytes([in_bytes[0xa], in_bytes[0xb]]) as usize;
-        let unk_c = [f32::from_be_bytes(in_bytes[0xC..0x10].try_into().unwrap()), f32::from_be_bytes(in_bytes[0x10..0x14].try_into().unwrap()), f32::from_be_bytes(in_bytes[0x14..0x18].try_into().unwrap())];
-        GeoCmdA{ unk_8, unk_a, unk_c}
-    }
-}
-
-impl BKGeo for GeoCmdA{}
-
-#[derive(Debug)]
-pub struct BKGeoList{
-    geo: Vec<Box<dyn BKGeo>>
-}
-
-impl BKGeoList{
-    fn from_be_bytes(in_bytes: &[u8])->Self{
-        let mut root_offsets : Vec<usize> = vec![0];
-        loop {
-            let i = root_offsets.last().unwrap();
-            
-            let size = i32::from_be_bytes(in_bytes[i+4..i+8].try_into().unwrap()) as usize;
-            if size != 0 {
-                root_offsets.push(i + size);
-            }
-            else{
-                break;
-            }
-        }
-        BKGeoList{geo: root_offsets.iter().map(|&offset| <dyn BKGeo>::from_be_bytes(&in_bytes[offset..])).collect()}
-    }
-}
+use super::bktexture::TmemDiagnostic;
 
 mod texture;
 pub use texture::{*};
@@ -167,6 +19,9 @@ pub use animation::{*};
 mod collision;
 pub use collision::{*};
 
+mod collision_query;
+pub use collision_query::{*};
+
 mod unk_20;
 pub use unk_20::{*};
 
@@ -177,9 +32,42 @@ mod unk_28;
 pub use unk_28::{*};
 
 mod animated_texture;
-pub use animated_texture::BKAnimatedTextureList;
+pub use animated_texture::{BKAnimatedTexture, BKAnimatedTextureList, ANIMATED_TEXTURE_SEGMENT};
 
-#[derive(Debug, PartialEq)]
+mod geo;
+pub use geo::{*};
+
+mod geo_traverse;
+pub use geo_traverse::{*};
+
+mod f3dex;
+pub use f3dex::{*};
+
+mod gltf;
+
+mod obj;
+pub use obj::{*};
+
+mod gfx_builder;
+pub use gfx_builder::{*};
+
+mod gltf_import;
+
+mod gbi;
+
+mod render_mode;
+pub use render_mode::{*};
+
+mod gfx_optimize;
+pub use gfx_optimize::{*};
+
+mod segments;
+pub use segments::{*};
+
+mod relocation;
+pub use relocation::{*};
+
+#[derive(Debug, PartialEq, Default)]
 pub struct BKModelHeader{
     pub geo_list_offset : usize,
     pub texture_list_offset: usize,
@@ -245,7 +133,7 @@ impl BKModelHeader{
 
 
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct BKModel {
     pub header : BKModelHeader,
     pub texture_list  : Option<BKTextureList>,
@@ -258,8 +146,9 @@ pub struct BKModel {
     pub mesh_list     : Option<BKMeshList>,
     pub unk_28_list   : Option<BKModelUnk28List>,
     pub animated_texture_list   : Option<BKAnimatedTextureList>,
-    // pub geo_list      : Option<BKGeoList>,
-    pub data : Vec<u8>,
+    pub geo_list      : Option<BKGeoList>,
+    pub data : Vec<u8>, //bytes between the last section and the geo list
+    pub layout : Option<BKSectionLayout>, //sections the display list addresses point into, None to write them as they are
 }
 
 impl BKModel {
@@ -292,7 +181,10 @@ impl BKModel {
             animated_texture_list.as_ref().map(BKAnimatedTextureList::size),
         ].iter().flat_map(|size| size).sum();
 
-        Some(BKModel { 
+        let geo_list = if header.geo_list_offset < offset || header.geo_list_offset >= in_bytes.len() {None} else {BKGeoList::try_from_be_bytes(&in_bytes[header.geo_list_offset..]).ok()};
+        let data_end = if geo_list.is_some() {header.geo_list_offset} else {in_bytes.len()};
+
+        let mut model = BKModel { 
             header,
             texture_list,
             display_list: gfx,
@@ -304,8 +196,12 @@ impl BKModel {
             mesh_list,
             unk_28_list,
             animated_texture_list,
-            data: in_bytes[offset..].to_vec() 
-        })
+            geo_list,
+            data: in_bytes[offset..data_end].to_vec(),
+            layout: None,
+        };
+        model.layout = Some(model.section_layout());
+        Some(model)
     }
     // pub fn from_be_bytes(in_bytes: &[u8]) -> BKModel{
 
@@ -334,7 +230,7 @@ impl BKModel {
         [  
             Some(self.create_header().to_be_bytes().to_vec()), 
             self.texture_list.as_ref().map(BKTextureList::to_be_bytes),
-            self.display_list.as_ref().map(BKGfxList::to_be_bytes),
+            self.relocated_display_list().map(|(gfx, _)| gfx.to_be_bytes()),
             self.vertices.as_ref().map(BKVertexList::to_be_bytes),
             self.unk_14_list.as_ref().map(BKModelUnk14List::to_be_bytes),
             self.collision_list.as_ref().map(BKCollisionList::to_be_bytes),
@@ -343,10 +239,19 @@ impl BKModel {
             self.unk_28_list.as_ref().map(BKModelUnk28List::to_be_bytes),
             self.animation_list.as_ref().map(BKAnimationList::to_be_bytes),
             self.animated_texture_list.as_ref().map(|x| x.to_be_bytes().to_vec()),
-            Some(self.data.clone())
+            Some(self.data.clone()),
+            self.geo_list.as_ref().map(BKGeoList::to_be_bytes),
         ].into_iter().flatten().flatten().collect::<Vec<u8>>()
     }
 
+    //texture headers and display list loads, check before writing the model
+    pub fn validate_tmem(&self)->Vec<TmemDiagnostic>{
+        [
+            self.texture_list.as_ref().map(BKTextureList::validate_tmem),
+            self.display_list.as_ref().map(BKGfxList::validate_tmem),
+        ].into_iter().flatten().flatten().collect()
+    }
+
     pub fn create_header(&self)->BKModelHeader {
             let current_offset = 0x38;
 
@@ -401,7 +306,10 @@ impl BKModel {
                 None => (0, current_offset)
             };
 
-            let geo_list_offset = current_offset;
+            let geo_list_offset = match &self.geo_list {
+                Some(_) => current_offset + self.data.len(),
+                None => current_offset,
+            };
 
             BKModelHeader { 
                 geo_list_offset, 
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::BKModel;
+use super::super::error::VertexIndexError;
+
+/* Wavefront OBJ/MTL export
+    every vertex of the BKVertexList is written once, in list order, so OBJ
+    indices are the BKVertexList indices + 1. UVs depend on the bound texture's
+    size so each (vertex, texture) pair gets its own vt, with V flipped since OBJ
+    puts 0 at the bottom. faces are grouped by material, one per texture named
+    like the glTF export. vertex colours use the common `v x y z r g b` extension.
+*/
+
+pub struct BKObjExport{
+    pub obj: String,
+    pub mtl: String,
+    pub images: Vec<(String, Vec<u8>)>, //(file name, png bytes)
+}
+
+impl BKObjExport{
+    //writes {name}.obj, {name}.mtl and the textures into dir
+    pub fn write(&self, dir: &Path, name: &str)->io::Result<()>{
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(format!("{}.obj", name)), &self.obj)?;
+        fs::write(dir.join(format!("{}.mtl", name)), &self.mtl)?;
+        for (file_name, png) in self.images.iter() {
+            fs::write(dir.join(file_name), png)?;
+        }
+        Ok(())
+    }
+}
+
+impl BKModel{
+    //name is used for the mtllib and as a prefix of the texture files
+    pub fn to_obj(&self, name: &str, vertex_colors: bool)->Result<BKObjExport, Box<dyn Error>>{
+        let vertices = self.vertices.as_ref().ok_or(VertexIndexError)?;
+        let textures = self.texture_list.as_ref();
+
+        //materials, one per decodable texture
+        let mut mtl = String::new();
+        let mut images = Vec::new();
+        let mut material_of = BTreeMap::new();
+        if let Some(list) = textures {
+            for (i, header) in list.texture_headers.iter().enumerate() {
+                let png = match list.texture(i, 0) {
+                    Some(texture) => texture.to_png_bytes()?,
+                    None => continue,
+                };
+                let material = format!("texture_{}_{:?}", i, header.format);
+                let file_name = format!("{}_{}.png", name, material);
+                writeln!(mtl, "newmtl {}\nKa 1 1 1\nKd 1 1 1\nillum 1\nmap_Kd {}\n", material, file_name)?;
+                images.push((file_name, png));
+                material_of.insert(i, material);
+            }
+        }
+        writeln!(mtl, "newmtl untextured\nKa 1 1 1\nKd 1 1 1\nillum 1")?;
+
+        let mut obj = format!("mtllib {}.mtl\no {}\n", name, name);
+        for i in 0..vertices.len() {
+            let [x, y, z] = vertices[i].ob;
+            match vertex_colors {
+                true => {
+                    let [r, g, b, _] = vertices.color(i).map(|c| c as f32/255.0);
+                    writeln!(obj, "v {} {} {} {} {} {}", x, y, z, r, g, b)?;
+                },
+                false => writeln!(obj, "v {} {} {}", x, y, z)?,
+            }
+        }
+
+        //texture coordinates and faces, grouped by material
+        let mut uv_index : BTreeMap<(usize, Option<usize>), usize> = BTreeMap::new();
+        let mut faces : BTreeMap<&str, Vec<[(usize, usize); 3]>> = BTreeMap::new();
+        for (tri, _) in self.triangles() {
+            if tri.vertices.iter().any(|&v| v >= vertices.len()) { return Err(Box::new(VertexIndexError)) }
+            let material = tri.material.texture_index.and_then(|i| material_of.get(&i)).map_or("untextured", String::as_str);
+            let mut face = [(0, 0); 3];
+            for (corner, entry) in face.iter_mut().enumerate() {
+                let v = tri.vertices[corner];
+                let next = uv_index.len() + 1;
+                let vt = *uv_index.entry((v, tri.material.texture_index)).or_insert(next);
+                if vt == next {
+                    let [s, t] = tri.uv(corner, vertices, textures);
+                    writeln!(obj, "vt {} {}", s, 1.0 - t)?;
+                }
+                *entry = (v + 1, vt);
+            }
+            faces.entry(material).or_default().push(face);
+        }
+        for (material, faces) in faces.iter() {
+            writeln!(obj, "usemtl {}", material)?;
+            for [a, b, c] in faces.iter() {
+                writeln!(obj, "f {}/{} {}/{} {}/{}", a.0, a.1, b.0, b.1, c.0, c.1)?;
+            }
+        }
+
+        Ok(BKObjExport{obj, mtl, images})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::f3dex::tests::test_model;
+
+    #[test]
+    fn obj_export() {
+        let export = test_model().to_obj("quad", true).unwrap();
+        let lines : Vec<&str> = export.obj.lines().collect();
+        assert_eq!(lines[0], "mtllib quad.mtl");
+        assert_eq!(lines.iter().filter(|l| l.starts_with("v ")).count(), 4);
+        assert_eq!(lines[2], "v 0 0 0 1 0.5019608 0.2509804");
+        assert!(lines.contains(&"vt 1 1"));
+        assert!(lines.contains(&"usemtl texture_0_I8"));
+        assert!(lines.contains(&"f 1/1 2/2 3/3"));
+        assert!(lines.contains(&"f 2/2 4/4 3/3"));
+
+        assert!(export.mtl.contains("map_Kd quad_texture_0_I8.png"));
+        assert_eq!(export.images.len(), 1);
+        assert_eq!(&export.images[0].1[1..4], b"PNG");
+
+        let plain = test_model().to_obj("quad", false).unwrap();
+        assert!(plain.obj.contains("\nv 100 100 0\n"));
+
+        let mut broken = test_model();
+        broken.vertices = None;
+        assert!(broken.to_obj("quad", false).err().unwrap().is::<VertexIndexError>());
+    }
+}
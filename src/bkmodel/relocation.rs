@@ -0,0 +1,319 @@
+use std::error::Error;
+use std::ops::Range;
+
+use libultra::{F3dex, Vtx};
+
+use super::{BKGfxList, BKModel, BKTextureList, BKVertexList, BKSegmentDiagnostic, BKSegmentIssue};
+use super::f3dex::{split_cmd, segment_offset, VTX_SEGMENT, TEXTURE_SEGMENT};
+use super::super::bktexture::BKTextureHeader;
+use super::super::error::{TextureIndexError, VertexIndexError, VertexInUseError};
+
+/* Relocation
+    display list addresses are relative to the texture data and vertex list, so
+    resizing a texture or inserting/removing vertices leaves G_SETTIMG and G_VTX
+    pointing at the wrong bytes. the model keeps the layout its display list was
+    written against and to_be_bytes rewrites the addresses for the current one.
+    vertices and texture headers have to be inserted, removed and reordered with
+    the methods below, which record where everything went: a vertex address
+    follows its vertices, a texture address keeps its offset into the same
+    texture wherever its header and data moved. an index or range outside the
+    list is an error and changes nothing.
+    an address that can't follow (into removed vertices or textures, past the
+    end of a shrunk texture, or into a section whose count changed behind the
+    methods' back) is written as it is and reported as NotRelocated by
+    validate_segments, relocate refuses to run until it is fixed.
+*/
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BKSectionLayout{
+    pub textures: Vec<(usize, usize)>,     //(offset, span) of every texture the display list was written against
+    pub texture_index: Vec<Option<usize>>, //where each of them is in texture_headers now, None once removed
+    pub texture_count: usize,              //headers the recorded edits leave
+    pub vertex_index: Vec<Option<usize>>,  //where each vertex the display list was written against is now
+    pub vertex_count: usize,
+}
+
+//index of every element once `removed` are taken out and `inserted` put in at `at`
+fn shift(index: Option<usize>, removed: Range<usize>, at: usize, inserted: usize)->Option<usize>{
+    let index = index.filter(|i| !removed.contains(i))?;
+    let index = if index >= removed.end { index - removed.len() } else { index };
+    Some(if index >= at { index + inserted } else { index })
+}
+
+impl BKTextureList{
+    /* replaces a texture's bytes (palette and levels), padded to 8 bytes, and
+        moves the textures after it. update the header's size and format to match.
+    */
+    pub fn replace_texture_data(&mut self, index: usize, mut data: Vec<u8>)->Result<(), TextureIndexError>{
+        let start = self.texture_headers.get(index).ok_or(TextureIndexError)?.offset;
+        if start > self.texture_data.len() { return Err(TextureIndexError) }
+        let end = start + self.texture_span(index);
+        data.resize(data.len().div_ceil(8)*8, 0);
+        let new_end = start + data.len();
+        self.texture_data.splice(start .. end, data);
+        for header in self.texture_headers.iter_mut() {
+            if header.offset >= end && end > start { header.offset = header.offset + new_end - end }
+        }
+        Ok(())
+    }
+}
+
+impl BKSectionLayout{
+    fn edit_textures(&mut self, removed: Range<usize>, at: usize, inserted: usize){
+        for index in self.texture_index.iter_mut() { *index = shift(*index, removed.clone(), at, inserted) }
+        self.texture_count = self.texture_count - removed.len() + inserted;
+    }
+
+    fn edit_vertices(&mut self, removed: Range<usize>, at: usize, inserted: usize){
+        for index in self.vertex_index.iter_mut() { *index = shift(*index, removed.clone(), at, inserted) }
+        self.vertex_count = self.vertex_count - removed.len() + inserted;
+    }
+}
+
+impl BKModel{
+    pub fn section_layout(&self)->BKSectionLayout{
+        let textures : Vec<(usize, usize)> = self.texture_list.as_ref()
+            .map(|list| (0..list.texture_headers.len()).map(|i| (list.texture_headers[i].offset, list.texture_span(i))).collect())
+            .unwrap_or_default();
+        let vertex_count = self.vertices.as_ref().map_or(0, |list| list.len());
+        BKSectionLayout{
+            texture_index: (0..textures.len()).map(Some).collect(),
+            texture_count: textures.len(),
+            textures,
+            vertex_index: (0..vertex_count).map(Some).collect(),
+            vertex_count,
+        }
+    }
+
+    //collision, mesh and unk_28 vertex indices
+    fn vertex_refs(&mut self)->impl Iterator<Item = VertexRef<'_>>{
+        let collision = self.collision_list.iter_mut().flat_map(|list| list.tri.iter_mut()).flat_map(|tri| tri.vtx.iter_mut()).map(VertexRef::Collision);
+        let meshes = self.mesh_list.iter_mut().flat_map(|list| list.meshes.iter_mut()).flat_map(|mesh| mesh.vtx_indices.iter_mut());
+        let unk_28 = self.unk_28_list.iter_mut().flat_map(|list| list.list.iter_mut()).flat_map(|unk| unk.vtx_index_list.iter_mut());
+        collision.chain(meshes.chain(unk_28).map(VertexRef::Index))
+    }
+
+    pub fn insert_vertices(&mut self, index: usize, vertices: Vec<Vtx>)->Result<(), VertexIndexError>{
+        let count = vertices.len();
+        let list = self.vertices.get_or_insert_with(|| BKVertexList::new(Vec::new()));
+        if index > list.len() { return Err(VertexIndexError) }
+        list.splice(index .. index, vertices);
+        if let Some(layout) = self.layout.as_mut() { layout.edit_vertices(0..0, index, count) }
+        for vertex in self.vertex_refs() { vertex.update(|v| shift(Some(v), 0..0, index, count)) }
+        Ok(())
+    }
+
+    //fails with VertexInUseError while the collision, mesh or unk_28 list uses one of the vertices
+    pub fn remove_vertices(&mut self, range: Range<usize>)->Result<(), Box<dyn Error>>{
+        let len = self.vertices.as_ref().map_or(0, |list| list.len());
+        if range.start > range.end || range.end > len { return Err(Box::new(VertexIndexError)) }
+        if self.vertex_refs().any(|vertex| vertex.get().is_some_and(|v| range.contains(&v))) { return Err(Box::new(VertexInUseError)) }
+        if let Some(list) = self.vertices.as_mut() { list.drain(range.clone()); }
+        if let Some(layout) = self.layout.as_mut() { layout.edit_vertices(range.clone(), 0, 0) }
+        for vertex in self.vertex_refs() { vertex.update(|v| shift(Some(v), range.clone(), 0, 0)) }
+        Ok(())
+    }
+
+    //the data is appended to texture_data and header.offset pointed at it
+    pub fn insert_texture(&mut self, index: usize, mut header: BKTextureHeader, mut data: Vec<u8>)->Result<(), TextureIndexError>{
+        let list = self.texture_list.get_or_insert_with(|| BKTextureList{texture_headers: Vec::new(), texture_data: Vec::new()});
+        if index > list.texture_headers.len() { return Err(TextureIndexError) }
+        data.resize(data.len().div_ceil(8)*8, 0);
+        header.offset = list.texture_data.len();
+        list.texture_data.extend(data);
+        list.texture_headers.insert(index, header);
+        if let Some(layout) = self.layout.as_mut() { layout.edit_textures(0..0, index, 1) }
+        Ok(())
+    }
+
+    //the header only, its data stays in texture_data
+    pub fn remove_texture(&mut self, index: usize)->Result<BKTextureHeader, TextureIndexError>{
+        let list = self.texture_list.as_mut().filter(|list| index < list.texture_headers.len()).ok_or(TextureIndexError)?;
+        let header = list.texture_headers.remove(index);
+        if let Some(layout) = self.layout.as_mut() { layout.edit_textures(index .. index + 1, 0, 0) }
+        Ok(header)
+    }
+
+    pub fn move_texture(&mut self, from: usize, to: usize)->Result<(), TextureIndexError>{
+        let list = self.texture_list.as_mut().filter(|list| from.max(to) < list.texture_headers.len()).ok_or(TextureIndexError)?;
+        let header = list.texture_headers.remove(from);
+        list.texture_headers.insert(to, header);
+        if let Some(layout) = self.layout.as_mut() {
+            for index in layout.texture_index.iter_mut().filter(|i| i.is_some()) {
+                *index = if *index == Some(from) { Some(to) } else { shift(*index, from .. from + 1, to, 1) };
+            }
+        }
+        Ok(())
+    }
+
+    /* the display list with its addresses moved from `layout` to the current sections,
+        and a NotRelocated diagnostic for every address that couldn't be moved
+    */
+    pub(crate) fn relocated_display_list(&self)->Option<(BKGfxList, Vec<BKSegmentDiagnostic>)>{
+        let gfx = self.display_list.as_ref()?;
+        let mut relocated = BKGfxList{gfx: gfx.gfx.clone(), header_filler: gfx.header_filler};
+        let layout = match &self.layout {
+            Some(layout) => layout,
+            None => return Some((relocated, Vec::new())),
+        };
+        let vertex_count = self.vertices.as_ref().map_or(0, |list| list.len());
+        let textures = self.texture_list.as_ref();
+        let texture_count = textures.map_or(0, |list| list.texture_headers.len());
+
+        //sections whose count changed behind the edit methods can't be followed
+        let vertex = |i: usize| layout.vertex_index[i].filter(|_| vertex_count == layout.vertex_count);
+        let texture = |i: usize| layout.texture_index[i].filter(|_| texture_count == layout.texture_count);
+
+        let mut stuck = Vec::new();
+        for (gfx_index, cmd) in relocated.gfx.iter_mut().enumerate() {
+            let (w0, w1) = split_cmd(u64::from(cmd.clone()));
+            //None for addresses the old layout doesn't cover, validate_segments reports those as they are
+            let address = match w0 >> 24 {
+                0x04 => segment_offset(w1, VTX_SEGMENT).filter(|offset| offset % 0x10 == 0).and_then(|offset| { //G_VTX
+                    let (first, count) = (offset/0x10, ((w0 >> 10) & 0x3F) as usize);
+                    (first < layout.vertex_index.len() && first + count <= layout.vertex_index.len()).then(|| {
+                        let moved = vertex(first)?;
+                        (0..count).all(|i| vertex(first + i) == Some(moved + i)).then_some((VTX_SEGMENT, moved*0x10))
+                    })
+                }),
+                0xFD => segment_offset(w1, TEXTURE_SEGMENT).and_then(|offset| { //G_SETTIMG
+                    let (i, &(start, _)) = layout.textures.iter().enumerate()
+                        .find(|(_, &(start, span))| start <= offset && offset < start + span)?;
+                    Some(texture(i).zip(textures).and_then(|(j, list)| {
+                        let new_start = list.texture_headers[j].offset;
+                        (offset - start < list.texture_span(j)).then_some((TEXTURE_SEGMENT, new_start + offset - start))
+                    }))
+                }),
+                _ => None,
+            };
+            match address {
+                Some(Some((segment, offset))) => *cmd = F3dex::from(((w0 as u64) << 32) | ((segment << 24) | offset) as u64),
+                Some(None) => stuck.push(BKSegmentDiagnostic{gfx_index, issue: BKSegmentIssue::NotRelocated{
+                    segment: (w1 >> 24) as usize,
+                    offset: (w1 & 0xFFFFFF) as usize,
+                }}),
+                None => {},
+            }
+        }
+        Some((relocated, stuck))
+    }
+
+    /* rewrites the display list for the current sections, returns the commands changed.
+        nothing changes while an address can't be relocated, the diagnostics say which
+    */
+    pub fn relocate(&mut self)->Result<usize, Vec<BKSegmentDiagnostic>>{
+        let relocated = self.relocated_display_list();
+        if let Some((_, stuck)) = relocated.as_ref().filter(|(_, stuck)| !stuck.is_empty()) { return Err(stuck.clone()) }
+        self.layout = Some(self.section_layout());
+        match (self.display_list.as_mut(), relocated) {
+            (Some(gfx), Some((relocated, _))) => {
+                let changed = gfx.gfx.iter().zip(relocated.gfx.iter()).filter(|(a, b)| u64::from((*a).clone()) != u64::from((*b).clone())).count();
+                gfx.gfx = relocated.gfx;
+                Ok(changed)
+            },
+            _ => Ok(0),
+        }
+    }
+}
+
+enum VertexRef<'a>{
+    Collision(&'a mut i16),
+    Index(&'a mut usize),
+}
+
+impl VertexRef<'_>{
+    fn get(&self)->Option<usize>{
+        match self {
+            VertexRef::Collision(v) => usize::try_from(**v).ok(),
+            VertexRef::Index(v) => Some(**v),
+        }
+    }
+
+    fn update(self, f: impl Fn(usize)->Option<usize>){
+        match self {
+            VertexRef::Collision(v) => if let Some(new) = usize::try_from(*v).ok().and_then(f) { *v = new as i16 },
+            VertexRef::Index(v) => if let Some(new) = f(*v) { *v = new },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{BKMesh, BKMeshList};
+    use super::super::f3dex::tests::test_model;
+    use super::super::super::bktexture::BKTextureFormat;
+
+    #[test]
+    fn relocate_sections() {
+        let mut model = test_model();
+        let list = model.texture_list.as_mut().unwrap();
+        list.texture_data.resize(8, 0);
+        list.texture_data.extend([0x11; 8]);
+        let header = || BKTextureHeader{offset: 8, format: BKTextureFormat::I8, flags: 0, level_count: 0, width: 2, height: 2, padding: [0; 6]};
+        list.texture_headers.push(header());
+        model.display_list.as_mut().unwrap().extend([0xFD500000_02000008u64, 0x0400040F_01000030, 0xB8000000_00000000].map(F3dex::from));
+        model.mesh_list = Some(BKMeshList{meshes: vec![BKMesh{uid: 0, vtx_indices: vec![3]}]});
+        model.layout = Some(model.section_layout());
+        let triangles = model.triangles();
+
+        //first texture grows to 4x4, a vertex is inserted in front of the others
+        let list = model.texture_list.as_mut().unwrap();
+        list.replace_texture_data(0, vec![0x22; 16]).unwrap();
+        list.texture_headers[0].width = 4;
+        list.texture_headers[0].height = 4;
+        assert_eq!(list.texture_headers[1].offset, 16);
+        let extra = || Vtx::from_be_bytes(&[0; 16]);
+        model.insert_vertices(0, vec![extra()]).unwrap();
+        assert_eq!(model.mesh_list.as_ref().unwrap().meshes[0].vtx_indices, [4]);
+        assert!(model.remove_vertices(4..5).unwrap_err().is::<VertexInUseError>());
+
+        let bytes = model.to_be_bytes();
+        let gfx = model.display_list.as_ref().unwrap();
+        let offset = u32::from_be_bytes(bytes[0xC..0x10].try_into().unwrap()) as usize + 8; //gfx list commands
+        let written = |i: usize| u64::from_be_bytes(bytes[offset + 8*i .. offset + 8*i + 8].try_into().unwrap());
+        assert_eq!(written(0), 0xFD500000_02000000);
+        assert_eq!(written(2), 0x0400103F_01000010);
+        assert_eq!(written(5), 0xFD500000_02000010);
+        assert_eq!(written(6), 0x0400040F_01000040);
+        assert_eq!(u64::from(gfx[6].clone()), 0x0400040F_01000030); //the model keeps the old addresses
+        assert!(model.validate_segments().is_empty());
+
+        assert_eq!(model.relocate(), Ok(3));
+        assert_eq!(u64::from(model.display_list.as_ref().unwrap()[5].clone()), 0xFD500000_02000010);
+        assert_eq!(model.relocate(), Ok(0));
+        assert_eq!(model.triangles().len(), triangles.len());
+
+        //a vertex inside a load splits it, relocate refuses until it's gone
+        model.insert_vertices(3, vec![extra()]).unwrap();
+        let stuck = BKSegmentDiagnostic{gfx_index: 2, issue: BKSegmentIssue::NotRelocated{segment: VTX_SEGMENT, offset: 0x10}};
+        assert_eq!(model.validate_segments(), [stuck]);
+        assert_eq!(model.relocate(), Err(vec![stuck]));
+        model.remove_vertices(3..4).unwrap();
+        assert_eq!(model.relocate(), Ok(0));
+
+        //vertices added behind the methods' back can't be followed at all
+        model.vertices.as_mut().unwrap().insert(0, extra());
+        assert_eq!(model.validate_segments().iter().map(|d| d.gfx_index).collect::<Vec<_>>(), [2, 6]);
+        model.vertices.as_mut().unwrap().remove(0);
+
+        //reordering headers leaves the data, and so the addresses, where they are
+        model.move_texture(1, 0).unwrap();
+        assert_eq!(model.relocate(), Ok(0));
+        assert_eq!(model.remove_texture(0).unwrap().offset, 16);
+        let stuck = BKSegmentDiagnostic{gfx_index: 5, issue: BKSegmentIssue::NotRelocated{segment: TEXTURE_SEGMENT, offset: 16}};
+        assert_eq!(model.validate_segments(), [stuck]);
+        assert_eq!(model.relocate(), Err(vec![stuck]));
+
+        //indices and ranges outside the lists are errors that leave the model alone
+        let (vertex_count, texture_count) = (model.vertices.as_ref().unwrap().len(), model.texture_list.as_ref().unwrap().texture_headers.len());
+        assert!(model.insert_vertices(vertex_count + 1, vec![extra()]).is_err());
+        assert!(model.remove_vertices(vertex_count - 1 .. vertex_count + 1).unwrap_err().is::<VertexIndexError>());
+        assert!(model.move_texture(0, texture_count).is_err());
+        assert!(model.remove_texture(texture_count).is_err());
+        assert!(model.insert_texture(texture_count + 1, header(), Vec::new()).is_err());
+        assert!(model.texture_list.as_mut().unwrap().replace_texture_data(texture_count, Vec::new()).is_err());
+        assert_eq!(model.vertices.as_ref().unwrap().len(), vertex_count);
+        assert_eq!(model.texture_list.as_ref().unwrap().texture_headers.len(), texture_count);
+    }
+}
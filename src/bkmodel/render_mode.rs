@@ -0,0 +1,486 @@
+use std::fmt;
+
+use libultra::F3dex;
+
+use super::{BKGfxList, BKMaterialState};
+use super::f3dex::split_cmd;
+use super::gbi::{combine_fields, combine_input, combine_words, CC_A, CC_B, CC_C, CC_D, AC_ABD, AC_C};
+
+/* Combiner and render modes
+    G_SETCOMBINE holds (a - b) * c + d for colour and alpha of both cycles, each
+    input is a small index into a per slot table (see gbi.rs). not every input is
+    valid in every slot, so encoding a BKCombiner can fail.
+    the render mode is bits 3..31 of G_SETOTHERMODE_L: coverage/z flags shared by
+    both cycles and one blender (p * a + m * b) / (a + b) per cycle. the
+    G_RM_ presets are flags | blender of one cycle, gsDPSetRenderMode or's a
+    cycle 1 and a cycle 2 preset.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BKCombineInput{
+    Combined, Texel0, Texel1, Primitive, Shade, Environment, One, Zero, Noise, Center, K4,
+    Scale, CombinedAlpha, Texel0Alpha, Texel1Alpha, PrimitiveAlpha, ShadeAlpha, EnvAlpha,
+    LodFraction, PrimLodFrac, K5,
+}
+
+const COMBINE_INPUTS : [(BKCombineInput, &str); 21] = [
+    (BKCombineInput::Combined, "COMBINED"), (BKCombineInput::Texel0, "TEXEL0"), (BKCombineInput::Texel1, "TEXEL1"),
+    (BKCombineInput::Primitive, "PRIMITIVE"), (BKCombineInput::Shade, "SHADE"), (BKCombineInput::Environment, "ENVIRONMENT"),
+    (BKCombineInput::One, "1"), (BKCombineInput::Zero, "0"), (BKCombineInput::Noise, "NOISE"),
+    (BKCombineInput::Center, "CENTER"), (BKCombineInput::K4, "K4"), (BKCombineInput::Scale, "SCALE"),
+    (BKCombineInput::CombinedAlpha, "COMBINED_ALPHA"), (BKCombineInput::Texel0Alpha, "TEXEL0_ALPHA"),
+    (BKCombineInput::Texel1Alpha, "TEXEL1_ALPHA"), (BKCombineInput::PrimitiveAlpha, "PRIMITIVE_ALPHA"),
+    (BKCombineInput::ShadeAlpha, "SHADE_ALPHA"), (BKCombineInput::EnvAlpha, "ENV_ALPHA"),
+    (BKCombineInput::LodFraction, "LOD_FRACTION"), (BKCombineInput::PrimLodFrac, "PRIM_LOD_FRAC"), (BKCombineInput::K5, "K5"),
+];
+
+impl BKCombineInput{
+    //G_CCMUX_/G_ACMUX_ name without the prefix
+    pub fn name(&self)->&'static str{
+        COMBINE_INPUTS.iter().find(|(input, _)| input == self).map(|(_, name)| *name).unwrap()
+    }
+
+    pub fn from_name(name: &str)->Option<BKCombineInput>{
+        COMBINE_INPUTS.iter().find(|(_, n)| *n == name).map(|(input, _)| *input)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BKCombineFormula{
+    pub a: BKCombineInput,
+    pub b: BKCombineInput,
+    pub c: BKCombineInput,
+    pub d: BKCombineInput,
+}
+
+impl fmt::Display for BKCombineFormula{
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        write!(f, "({} - {}) * {} + {}", self.a.name(), self.b.name(), self.c.name(), self.d.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BKCombiner{
+    pub color: [BKCombineFormula; 2], //per cycle
+    pub alpha: [BKCombineFormula; 2],
+}
+
+//slot tables in gsDPSetCombineLERP order
+const COMBINE_SLOTS : [&[&str]; 16] = [
+    &CC_A, &CC_B, &CC_C, &CC_D, &AC_ABD, &AC_ABD, &AC_C, &AC_ABD,
+    &CC_A, &CC_B, &CC_C, &CC_D, &AC_ABD, &AC_ABD, &AC_C, &AC_ABD,
+];
+
+//G_CC_ presets, [a, b, c, d, Aa, Ab, Ac, Ad]. the first of identical presets is the decoded name
+const COMBINE_PRESETS : [(&str, [&str; 8]); 30] = [
+    ("G_CC_PRIMITIVE", ["0", "0", "0", "PRIMITIVE", "0", "0", "0", "PRIMITIVE"]),
+    ("G_CC_SHADE", ["0", "0", "0", "SHADE", "0", "0", "0", "SHADE"]),
+    ("G_CC_MODULATERGB", ["TEXEL0", "0", "SHADE", "0", "0", "0", "0", "SHADE"]),
+    ("G_CC_MODULATEI", ["TEXEL0", "0", "SHADE", "0", "0", "0", "0", "SHADE"]),
+    ("G_CC_MODULATERGBA", ["TEXEL0", "0", "SHADE", "0", "TEXEL0", "0", "SHADE", "0"]),
+    ("G_CC_MODULATEIA", ["TEXEL0", "0", "SHADE", "0", "TEXEL0", "0", "SHADE", "0"]),
+    ("G_CC_MODULATEIDECALA", ["TEXEL0", "0", "SHADE", "0", "0", "0", "0", "TEXEL0"]),
+    ("G_CC_MODULATERGB_PRIM", ["TEXEL0", "0", "PRIMITIVE", "0", "0", "0", "0", "PRIMITIVE"]),
+    ("G_CC_MODULATEI_PRIM", ["TEXEL0", "0", "PRIMITIVE", "0", "0", "0", "0", "PRIMITIVE"]),
+    ("G_CC_MODULATERGBA_PRIM", ["TEXEL0", "0", "PRIMITIVE", "0", "TEXEL0", "0", "PRIMITIVE", "0"]),
+    ("G_CC_MODULATEIA_PRIM", ["TEXEL0", "0", "PRIMITIVE", "0", "TEXEL0", "0", "PRIMITIVE", "0"]),
+    ("G_CC_DECALRGB", ["0", "0", "0", "TEXEL0", "0", "0", "0", "SHADE"]),
+    ("G_CC_DECALRGBA", ["0", "0", "0", "TEXEL0", "0", "0", "0", "TEXEL0"]),
+    ("G_CC_BLENDI", ["ENVIRONMENT", "SHADE", "TEXEL0", "SHADE", "0", "0", "0", "SHADE"]),
+    ("G_CC_BLENDIA", ["ENVIRONMENT", "SHADE", "TEXEL0", "SHADE", "TEXEL0", "0", "SHADE", "0"]),
+    ("G_CC_BLENDRGBA", ["TEXEL0", "SHADE", "TEXEL0_ALPHA", "SHADE", "0", "0", "0", "SHADE"]),
+    ("G_CC_BLENDRGBDECALA", ["TEXEL0", "SHADE", "TEXEL0_ALPHA", "SHADE", "0", "0", "0", "TEXEL0"]),
+    ("G_CC_REFLECTRGB", ["ENVIRONMENT", "0", "TEXEL0", "SHADE", "0", "0", "0", "SHADE"]),
+    ("G_CC_HILITERGB", ["PRIMITIVE", "SHADE", "TEXEL0", "SHADE", "0", "0", "0", "SHADE"]),
+    ("G_CC_HILITERGBA", ["PRIMITIVE", "SHADE", "TEXEL0", "SHADE", "PRIMITIVE", "SHADE", "TEXEL0", "SHADE"]),
+    ("G_CC_SHADEDECALA", ["0", "0", "0", "SHADE", "0", "0", "0", "TEXEL0"]),
+    ("G_CC_FADE", ["SHADE", "0", "ENVIRONMENT", "0", "SHADE", "0", "ENVIRONMENT", "0"]),
+    ("G_CC_FADEA", ["TEXEL0", "0", "ENVIRONMENT", "0", "TEXEL0", "0", "ENVIRONMENT", "0"]),
+    ("G_CC_TRILERP", ["TEXEL1", "TEXEL0", "LOD_FRACTION", "TEXEL0", "TEXEL1", "TEXEL0", "LOD_FRACTION", "TEXEL0"]),
+    ("G_CC_INTERFERENCE", ["TEXEL0", "0", "TEXEL1", "0", "TEXEL0", "0", "TEXEL1", "0"]),
+    ("G_CC_PASS2", ["0", "0", "0", "COMBINED", "0", "0", "0", "COMBINED"]),
+    ("G_CC_MODULATERGB2", ["COMBINED", "0", "SHADE", "0", "0", "0", "0", "SHADE"]),
+    ("G_CC_MODULATERGBA2", ["COMBINED", "0", "SHADE", "0", "COMBINED", "0", "SHADE", "0"]),
+    ("G_CC_MODULATEIA2", ["COMBINED", "0", "SHADE", "0", "COMBINED", "0", "SHADE", "0"]),
+    ("G_CC_DECALRGB2", ["0", "0", "0", "COMBINED", "0", "0", "0", "SHADE"]),
+];
+
+impl BKCombiner{
+    pub fn from_cmd(cmd: u64)->Option<BKCombiner>{
+        let (w0, w1) = split_cmd(cmd);
+        if w0 >> 24 != 0xFC { return None }
+        let fields = combine_fields(w0, w1);
+        let input = |i: usize| BKCombineInput::from_name(COMBINE_SLOTS[i][fields[i]]).unwrap();
+        let formula = |i: usize| BKCombineFormula{a: input(i), b: input(i + 1), c: input(i + 2), d: input(i + 3)};
+        Some(BKCombiner{color: [formula(0), formula(8)], alpha: [formula(4), formula(12)]})
+    }
+
+    //None if an input isn't available in its slot
+    pub fn to_cmd(&self)->Option<u64>{
+        let formulas = [self.color[0], self.alpha[0], self.color[1], self.alpha[1]];
+        let mut fields = [0; 16];
+        for (i, field) in fields.iter_mut().enumerate() {
+            let f = &formulas[i/4];
+            let input = [f.a, f.b, f.c, f.d][i % 4];
+            *field = combine_input(COMBINE_SLOTS[i], input.name())?;
+        }
+        let (w0, w1) = combine_words(&fields);
+        Some(((w0 as u64) << 32) | w1 as u64)
+    }
+
+    fn cycle(&self, cycle: usize)->[&'static str; 8]{
+        let (c, a) = (self.color[cycle], self.alpha[cycle]);
+        [c.a, c.b, c.c, c.d, a.a, a.b, a.c, a.d].map(|input| input.name())
+    }
+
+    //gsDPSetCombineMode arguments, if both cycles are G_CC_ presets
+    pub fn presets(&self)->Option<(&'static str, &'static str)>{
+        let find = |cycle| COMBINE_PRESETS.iter().find(|(_, inputs)| *inputs == self.cycle(cycle)).map(|(name, _)| *name);
+        Some((find(0)?, find(1)?))
+    }
+
+    pub fn from_presets(cycle_1: &str, cycle_2: &str)->Option<BKCombiner>{
+        let find = |name: &str| COMBINE_PRESETS.iter().find(|(n, _)| *n == name).map(|(_, inputs)| *inputs);
+        let (p0, p1) = (find(cycle_1)?, find(cycle_2)?);
+        let formula = |p: &[&str]| Some(BKCombineFormula{
+            a: BKCombineInput::from_name(p[0])?, b: BKCombineInput::from_name(p[1])?,
+            c: BKCombineInput::from_name(p[2])?, d: BKCombineInput::from_name(p[3])?,
+        });
+        Some(BKCombiner{color: [formula(&p0[..4])?, formula(&p1[..4])?], alpha: [formula(&p0[4..])?, formula(&p1[4..])?]})
+    }
+}
+
+impl fmt::Display for BKCombiner{
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        for cycle in 0..2 {
+            if cycle != 0 { write!(f, "; ")? }
+            write!(f, "cycle {}: rgb = {}, a = {}", cycle + 1, self.color[cycle], self.alpha[cycle])?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BKBlendColor{ Input, Memory, Blend, Fog }   //G_BL_CLR_IN, _MEM, _BL, _FOG
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BKBlendAlpha{ Input, Fog, Shade, Zero }     //G_BL_A_IN, _A_FOG, _A_SHADE, G_BL_0
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BKBlendFactor{ OneMinusA, Memory, One, Zero } //G_BL_1MA, _A_MEM, G_BL_1, G_BL_0
+
+const BLEND_COLORS : [BKBlendColor; 4] = [BKBlendColor::Input, BKBlendColor::Memory, BKBlendColor::Blend, BKBlendColor::Fog];
+const BLEND_ALPHAS : [BKBlendAlpha; 4] = [BKBlendAlpha::Input, BKBlendAlpha::Fog, BKBlendAlpha::Shade, BKBlendAlpha::Zero];
+const BLEND_FACTORS : [BKBlendFactor; 4] = [BKBlendFactor::OneMinusA, BKBlendFactor::Memory, BKBlendFactor::One, BKBlendFactor::Zero];
+
+//(p * a + m * b) / (a + b)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BKBlender{
+    pub p: BKBlendColor,
+    pub a: BKBlendAlpha,
+    pub m: BKBlendColor,
+    pub b: BKBlendFactor,
+}
+
+impl BKBlender{
+    fn from_bits(bits: [u32; 4])->BKBlender{
+        BKBlender{
+            p: BLEND_COLORS[bits[0] as usize & 3], a: BLEND_ALPHAS[bits[1] as usize & 3],
+            m: BLEND_COLORS[bits[2] as usize & 3], b: BLEND_FACTORS[bits[3] as usize & 3],
+        }
+    }
+
+    fn bits(&self)->[u32; 4]{
+        [self.p as u32, self.a as u32, self.m as u32, self.b as u32]
+    }
+
+    //the GBL_c1/GBL_c2 bits of othermode L
+    fn to_othermode_l(self, cycle: usize)->u32{
+        let [p, a, m, b] = self.bits();
+        let shift = 16 + 2*(1 - cycle as u32);
+        (p << (shift + 12)) | (a << (shift + 8)) | (m << (shift + 4)) | (b << shift)
+    }
+
+    fn from_othermode_l(mode: u32, cycle: usize)->BKBlender{
+        let shift = 16 + 2*(1 - cycle as u32);
+        BKBlender::from_bits([12, 8, 4, 0].map(|s| (mode >> (shift + s)) & 3))
+    }
+}
+
+const RENDER_FLAGS : [(&str, u32); 7] = [
+    ("AA_EN", 0x8), ("Z_CMP", 0x10), ("Z_UPD", 0x20), ("IM_RD", 0x40), ("CLR_ON_CVG", 0x80),
+    ("CVG_X_ALPHA", 0x1000), ("ALPHA_CVG_SEL", 0x2000),
+];
+const CVG_DST : [&str; 4] = ["CVG_DST_CLAMP", "CVG_DST_WRAP", "CVG_DST_FULL", "CVG_DST_SAVE"];
+const ZMODE : [&str; 4] = ["ZMODE_OPA", "ZMODE_INTER", "ZMODE_XLU", "ZMODE_DEC"];
+const FORCE_BL : u32 = 0x4000;
+
+/* G_RM_ presets: (name, flags, cycle blender [p, a, m, b], has a cycle 2 version).
+    G_BL_CLR_IN = 0, CLR_MEM = 1, A_IN = 0, A_SHADE = 2, 1MA = 0, A_MEM = 1, 1 = 2, 0 = 3
+*/
+const RENDER_PRESETS : [(&str, u32, [u32; 4], bool); 23] = [
+    ("G_RM_AA_ZB_OPA_SURF", 0x2078, [0, 0, 1, 1], true),
+    ("G_RM_AA_ZB_XLU_SURF", 0x49D8, [0, 0, 1, 0], true),
+    ("G_RM_AA_ZB_OPA_DECAL", 0x2D58, [0, 0, 1, 1], true),
+    ("G_RM_AA_ZB_XLU_DECAL", 0x4DD8, [0, 0, 1, 0], true),
+    ("G_RM_AA_ZB_OPA_INTER", 0x24F8, [0, 0, 1, 1], true),
+    ("G_RM_AA_ZB_XLU_INTER", 0x45D8, [0, 0, 1, 0], true),
+    ("G_RM_AA_ZB_TEX_EDGE", 0x3078, [0, 0, 1, 1], true),
+    ("G_RM_AA_OPA_SURF", 0x2048, [0, 0, 1, 1], true),
+    ("G_RM_AA_XLU_SURF", 0x41C8, [0, 0, 1, 0], true),
+    ("G_RM_AA_TEX_EDGE", 0x3048, [0, 0, 1, 1], true),
+    ("G_RM_ZB_OPA_SURF", 0x2230, [0, 0, 1, 1], true),
+    ("G_RM_ZB_XLU_SURF", 0x4A50, [0, 0, 1, 0], true),
+    ("G_RM_ZB_OPA_DECAL", 0x2E10, [0, 0, 1, 1], true),
+    ("G_RM_ZB_XLU_DECAL", 0x4E50, [0, 0, 1, 0], true),
+    ("G_RM_ZB_CLD_SURF", 0x4B50, [0, 0, 1, 0], true),
+    ("G_RM_OPA_SURF", 0x4000, [0, 3, 0, 2], true),
+    ("G_RM_XLU_SURF", 0x4240, [0, 0, 1, 0], true),
+    ("G_RM_TEX_EDGE", 0x7000, [0, 3, 0, 2], true),
+    ("G_RM_CLD_SURF", 0x4340, [0, 0, 1, 0], true),
+    ("G_RM_NOOP", 0, [0, 0, 0, 0], true),
+    ("G_RM_PASS", 0, [0, 3, 0, 2], false),
+    ("G_RM_FOG_SHADE_A", 0, [3, 2, 0, 0], false),
+    ("G_RM_FOG_PRIM_A", 0, [3, 1, 0, 0], false),
+];
+
+//value of a G_RM_ preset name, cycle 2 names end in 2
+pub(crate) fn render_preset(name: &str)->Option<u32>{
+    RENDER_PRESETS.iter().find_map(|&(n, flags, blender, has_cycle_2)| {
+        let cycle = if n == name { 0 } else if has_cycle_2 && name.strip_suffix('2') == Some(n) { 1 } else { return None };
+        Some(flags | BKBlender::from_bits(blender).to_othermode_l(cycle))
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BKRenderMode{
+    pub flags: u32, //bits 3..15 of othermode L, shared by both cycles
+    pub blender: [BKBlender; 2],
+}
+
+impl BKRenderMode{
+    //alpha compare and depth source bits are ignored
+    pub fn from_othermode_l(mode: u32)->BKRenderMode{
+        BKRenderMode{
+            flags: mode & 0xFFF8,
+            blender: [BKBlender::from_othermode_l(mode, 0), BKBlender::from_othermode_l(mode, 1)],
+        }
+    }
+
+    pub fn to_othermode_l(&self)->u32{
+        (self.flags & 0xFFF8) | self.blender[0].to_othermode_l(0) | self.blender[1].to_othermode_l(1)
+    }
+
+    //gsDPSetRenderMode
+    pub fn to_cmd(&self)->u64{
+        0xB900031D_00000000 | self.to_othermode_l() as u64
+    }
+
+    pub fn flag_names(&self)->Vec<&'static str>{
+        let mut names : Vec<&str> = RENDER_FLAGS.iter().filter(|(_, bit)| self.flags & bit != 0).map(|(name, _)| *name).collect();
+        names.push(CVG_DST[(self.flags >> 8) as usize & 3]);
+        names.push(ZMODE[(self.flags >> 10) as usize & 3]);
+        if self.flags & FORCE_BL != 0 { names.push("FORCE_BL") }
+        names
+    }
+
+    //gsDPSetRenderMode arguments, if the mode is a cycle 1 preset or'd with a cycle 2 one
+    pub fn presets(&self)->Option<(&'static str, String)>{
+        let mode = self.to_othermode_l();
+        let cycle_1 = RENDER_PRESETS.iter().map(|p| p.0);
+        let cycle_2 = || RENDER_PRESETS.iter().filter(|p| p.3).map(|p| p.0);
+        //same preset in both cycles first
+        cycle_1.clone().filter(|&n| render_preset(&format!("{}2", n)).is_some()).map(|n| (n, n))
+            .chain(cycle_1.flat_map(|a| cycle_2().map(move |b| (a, b))))
+            .find(|(a, b)| render_preset(a).zip(render_preset(&format!("{}2", b))).map(|(a, b)| a | b) == Some(mode))
+            .map(|(a, b)| (a, format!("{}2", b)))
+    }
+
+    pub fn from_presets(cycle_1: &str, cycle_2: &str)->Option<BKRenderMode>{
+        Some(BKRenderMode::from_othermode_l(render_preset(cycle_1)? | render_preset(cycle_2)?))
+    }
+}
+
+impl fmt::Display for BKRenderMode{
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        if let Some((a, b)) = self.presets() { return write!(f, "{}, {}", a, b) }
+        write!(f, "{}", self.flag_names().join(" | "))?;
+        for (cycle, blender) in self.blender.iter().enumerate() {
+            write!(f, ", cycle {}: ({:?} * {:?} + {:?} * {:?})", cycle + 1, blender.p, blender.a, blender.m, blender.b)?;
+        }
+        Ok(())
+    }
+}
+
+//G_SETOTHERMODE_H fields: (name, shift, len, named values)
+const OTHERMODE_H_FIELDS : [(&str, u32, u32, &[&str]); 11] = [
+    ("alpha_dither", 4, 2, &["G_AD_PATTERN", "G_AD_NOTPATTERN", "G_AD_NOISE", "G_AD_DISABLE"]),
+    ("rgb_dither", 6, 2, &["G_CD_MAGICSQ", "G_CD_BAYER", "G_CD_NOISE", "G_CD_DISABLE"]),
+    ("combine_key", 8, 1, &["G_CK_NONE", "G_CK_KEY"]),
+    ("texture_convert", 9, 3, &["G_TC_CONV", "1", "2", "3", "4", "G_TC_FILTCONV", "G_TC_FILT", "7"]),
+    ("texture_filter", 12, 2, &["G_TF_POINT", "1", "G_TF_BILERP", "G_TF_AVERAGE"]),
+    ("texture_lut", 14, 2, &["G_TT_NONE", "1", "G_TT_RGBA16", "G_TT_IA16"]),
+    ("texture_lod", 16, 1, &["G_TL_TILE", "G_TL_LOD"]),
+    ("texture_detail", 17, 2, &["G_TD_CLAMP", "G_TD_SHARPEN", "G_TD_DETAIL", "3"]),
+    ("texture_persp", 19, 1, &["G_TP_NONE", "G_TP_PERSP"]),
+    ("cycle_type", 20, 2, &["G_CYC_1CYCLE", "G_CYC_2CYCLE", "G_CYC_COPY", "G_CYC_FILL"]),
+    ("pipeline", 23, 1, &["G_PM_NPRIMITIVE", "G_PM_1PRIMITIVE"]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BKOtherModeH{
+    pub alpha_dither: u8,
+    pub rgb_dither: u8,
+    pub combine_key: u8,
+    pub texture_convert: u8,
+    pub texture_filter: u8,
+    pub texture_lut: u8,
+    pub texture_lod: u8,
+    pub texture_detail: u8,
+    pub texture_persp: u8,
+    pub cycle_type: u8,
+    pub pipeline: u8,
+}
+
+impl BKOtherModeH{
+    fn fields(&mut self)->[&mut u8; 11]{
+        [
+            &mut self.alpha_dither, &mut self.rgb_dither, &mut self.combine_key, &mut self.texture_convert,
+            &mut self.texture_filter, &mut self.texture_lut, &mut self.texture_lod, &mut self.texture_detail,
+            &mut self.texture_persp, &mut self.cycle_type, &mut self.pipeline,
+        ]
+    }
+
+    pub fn from_othermode_h(mode: u32)->BKOtherModeH{
+        let mut this = BKOtherModeH::default();
+        for (field, (_, shift, len, _)) in this.fields().into_iter().zip(OTHERMODE_H_FIELDS.iter()) {
+            *field = ((mode >> shift) & ((1 << len) - 1)) as u8;
+        }
+        this
+    }
+
+    pub fn to_othermode_h(&self)->u32{
+        let mut this = *self;
+        this.fields().into_iter().zip(OTHERMODE_H_FIELDS.iter())
+            .map(|(field, (_, shift, len, _))| (*field as u32 & ((1 << len) - 1)) << shift)
+            .fold(0, |acc, bits| acc | bits)
+    }
+
+    //G_SETOTHERMODE_H covering every field
+    pub fn to_cmd(&self)->u64{
+        0xBA000414_00000000 | self.to_othermode_h() as u64
+    }
+
+    //one gbi.h name per field
+    pub fn names(&self)->Vec<&'static str>{
+        let mut this = *self;
+        this.fields().into_iter().zip(OTHERMODE_H_FIELDS.iter()).map(|(field, (_, _, _, names))| names[*field as usize]).collect()
+    }
+}
+
+impl fmt::Display for BKOtherModeH{
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        write!(f, "{}", self.names().join(" | "))
+    }
+}
+
+impl BKMaterialState{
+    pub fn combiner(&self)->BKCombiner{
+        BKCombiner::from_cmd(0xFC000000_00000000 | self.combine).unwrap()
+    }
+
+    pub fn render_mode(&self)->BKRenderMode{
+        BKRenderMode::from_othermode_l(self.othermode_l)
+    }
+
+    pub fn other_modes(&self)->BKOtherModeH{
+        BKOtherModeH::from_othermode_h(self.othermode_h)
+    }
+}
+
+impl BKGfxList{
+    //G_SETCOMBINE at index
+    pub fn combiner(&self, index: usize)->Option<BKCombiner>{
+        BKCombiner::from_cmd(u64::from(self.gfx.get(index)?.clone()))
+    }
+
+    //G_SETOTHERMODE_L at index that sets the whole render mode
+    pub fn render_mode(&self, index: usize)->Option<BKRenderMode>{
+        let (w0, w1) = split_cmd(u64::from(self.gfx.get(index)?.clone()));
+        let (shift, len) = ((w0 >> 8) & 0xFF, w0 & 0xFF);
+        (w0 >> 24 == 0xB9 && shift <= 3 && shift + len == 32).then(|| BKRenderMode::from_othermode_l(w1))
+    }
+
+    //G_SETOTHERMODE_H at index, fields it doesn't set are 0
+    pub fn other_modes(&self, index: usize)->Option<BKOtherModeH>{
+        let (w0, w1) = split_cmd(u64::from(self.gfx.get(index)?.clone()));
+        (w0 >> 24 == 0xBA).then(|| BKOtherModeH::from_othermode_h(w1))
+    }
+
+    //overwrites gfx[index] only when it has the same opcode as cmd
+    fn replace_cmd(&mut self, index: usize, cmd: u64)->Option<()>{
+        let old = self.gfx.get_mut(index).filter(|old| u64::from((*old).clone()) >> 56 == cmd >> 56)?;
+        *old = F3dex::from(cmd);
+        Some(())
+    }
+
+    //None when gfx[index] isn't a G_SETCOMBINE
+    pub fn set_combiner(&mut self, index: usize, combiner: &BKCombiner)->Option<()>{
+        self.replace_cmd(index, combiner.to_cmd()?)
+    }
+
+    //None when gfx[index] isn't a G_SETOTHERMODE_L
+    pub fn set_render_mode(&mut self, index: usize, mode: &BKRenderMode)->Option<()>{
+        self.replace_cmd(index, mode.to_cmd())
+    }
+
+    //None when gfx[index] isn't a G_SETOTHERMODE_H
+    pub fn set_other_modes(&mut self, index: usize, modes: &BKOtherModeH)->Option<()>{
+        self.replace_cmd(index, modes.to_cmd())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combiner_and_render_mode() {
+        let combiner = BKCombiner::from_cmd(0xFC121824_FF33FFFF).unwrap();
+        assert_eq!(combiner.color[0].to_string(), "(TEXEL0 - 0) * SHADE + 0");
+        assert_eq!(combiner.presets(), Some(("G_CC_MODULATERGBA", "G_CC_MODULATERGBA")));
+        assert_eq!(combiner.to_cmd(), Some(0xFC121824_FF33FFFF));
+        assert_eq!(BKCombiner::from_presets("G_CC_SHADE", "G_CC_SHADE").unwrap().to_cmd(), Some(0xFCFFFFFF_FFFE793C));
+        let mut invalid = combiner;
+        invalid.color[0].d = BKCombineInput::Noise;
+        assert_eq!(invalid.to_cmd(), None);
+
+        let mode = BKRenderMode::from_othermode_l(0x00552078);
+        assert_eq!(mode.presets(), Some(("G_RM_AA_ZB_OPA_SURF", "G_RM_AA_ZB_OPA_SURF2".to_string())));
+        assert_eq!(mode.blender[0], BKBlender{p: BKBlendColor::Input, a: BKBlendAlpha::Input, m: BKBlendColor::Memory, b: BKBlendFactor::Memory});
+        assert_eq!(mode.flag_names(), ["AA_EN", "Z_CMP", "Z_UPD", "IM_RD", "ALPHA_CVG_SEL", "CVG_DST_CLAMP", "ZMODE_OPA"]);
+        assert_eq!(mode.to_cmd(), 0xB900031D_00552078);
+        let fog = BKRenderMode::from_presets("G_RM_FOG_SHADE_A", "G_RM_AA_ZB_XLU_SURF2").unwrap();
+        assert_eq!(fog.to_othermode_l(), 0xC81049D8);
+        assert_eq!(fog.presets(), Some(("G_RM_FOG_SHADE_A", "G_RM_AA_ZB_XLU_SURF2".to_string())));
+
+        let modes = BKOtherModeH::from_othermode_h(0x00182C00 | 0x8000);
+        assert_eq!(modes.cycle_type, 1);
+        assert_eq!(modes.to_othermode_h(), 0x0018AC00);
+        assert!(modes.to_string().contains("G_TT_RGBA16"));
+
+        let mut gfx = BKGfxList::new(vec![F3dex::from(0xFC121824_FF33FFFF), F3dex::from(0xB900031D_00552078)]);
+        assert_eq!(gfx.combiner(0), Some(combiner));
+        assert_eq!(gfx.render_mode(1), Some(mode));
+        gfx.set_render_mode(1, &fog).unwrap();
+        assert_eq!(u64::from(gfx.gfx[1].clone()), 0xB900031D_C81049D8);
+
+        //a command of another kind at the index is left alone
+        assert_eq!(gfx.set_render_mode(0, &fog), None);
+        assert_eq!(gfx.set_combiner(1, &combiner), None);
+        assert_eq!(gfx.set_other_modes(1, &modes), None);
+        assert_eq!(gfx.set_combiner(2, &combiner), None);
+        assert_eq!(u64::from(gfx.gfx[0].clone()), 0xFC121824_FF33FFFF);
+        assert_eq!(u64::from(gfx.gfx[1].clone()), 0xB900031D_C81049D8);
+    }
+}
@@ -0,0 +1,166 @@
+use super::{BKGfxList, BKModel, BKTextureList, BKVertexList};
+use super::f3dex::{split_cmd, VTX_SEGMENT, TEXTURE_SEGMENT, GFX_SEGMENT};
+
+/* Segmented addresses
+    G_VTX, G_SETTIMG and G_DL don't hold file offsets but segment << 24 | offset,
+    the game points segment 1 at the vertex list, 2 at the texture data and 3 at
+    the display list itself when it loads the model. an address stays the same
+    wherever create_header puts a section, but not when the section it points
+    into is resized or reordered, so every address is checked against the
+    current vertex count, texture data and display list length.
+    an address in another of the model's segments (a G_VTX into the texture
+    data, say) is an error, addresses in segments other than 1-3 are set up by
+    the game and can't be checked.
+    BKModel::validate_segments checks the list to_be_bytes would write, so
+    addresses relocation couldn't follow show up as NotRelocated.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BKSegmentTarget{
+    Vertices{first: usize, count: usize},         //into the BKVertexList
+    Texture{texture_index: usize, offset: usize}, //offset from the texture's header offset
+    Gfx{gfx_index: usize},
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BKSegmentIssue{
+    UnknownSegment{segment: usize},
+    WrongSegment{segment: usize, expected: usize}, //one of the model's segments, but not the command's
+    Misaligned{offset: usize, alignment: usize},
+    VerticesOutOfRange{first: usize, count: usize, len: usize},
+    TextureOutOfRange{offset: usize, len: usize}, //past texture_data, or in no texture
+    GfxOutOfRange{gfx_index: usize, len: usize},
+    NotRelocated{segment: usize, offset: usize}, //into vertices or a texture edited out from under it
+}
+
+impl BKSegmentIssue{
+    pub fn is_error(&self)->bool{
+        !matches!(self, BKSegmentIssue::UnknownSegment{..})
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BKSegmentDiagnostic{
+    pub gfx_index: usize,
+    pub issue: BKSegmentIssue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BKSegmentRef{
+    pub gfx_index: usize,
+    pub address: u32,
+    pub target: Result<BKSegmentTarget, BKSegmentIssue>,
+}
+
+impl BKGfxList{
+    //every G_VTX, G_SETTIMG and G_DL address and what it points at
+    pub fn segment_refs(&self, vertices: Option<&BKVertexList>, textures: Option<&BKTextureList>)->Vec<BKSegmentRef>{
+        let mut refs = Vec::new();
+        for (gfx_index, cmd) in self.gfx.iter().enumerate() {
+            let (w0, address) = split_cmd(u64::from(cmd.clone()));
+            let (segment, offset) = ((address >> 24) as usize, (address & 0xFFFFFF) as usize);
+            let expected = match w0 >> 24 {
+                0x04 => VTX_SEGMENT,     //G_VTX
+                0xFD => TEXTURE_SEGMENT, //G_SETTIMG
+                0x06 => GFX_SEGMENT,     //G_DL
+                _ => continue,
+            };
+            let target = match segment {
+                VTX_SEGMENT | TEXTURE_SEGMENT | GFX_SEGMENT if segment != expected => Err(BKSegmentIssue::WrongSegment{segment, expected}),
+                _ if segment != expected => Err(BKSegmentIssue::UnknownSegment{segment}),
+                VTX_SEGMENT => {
+                    let (first, count) = (offset/0x10, ((w0 >> 10) & 0x3F) as usize);
+                    let len = vertices.map_or(0, |v| v.len());
+                    if offset % 0x10 != 0 {
+                        Err(BKSegmentIssue::Misaligned{offset, alignment: 0x10})
+                    } else if first + count > len {
+                        Err(BKSegmentIssue::VerticesOutOfRange{first, count, len})
+                    } else {
+                        Ok(BKSegmentTarget::Vertices{first, count})
+                    }
+                },
+                TEXTURE_SEGMENT => {
+                    let len = textures.map_or(0, |t| t.texture_data.len());
+                    match textures.and_then(|t| t.texture_at(offset).map(|i| (i, t.texture_headers[i].offset))) {
+                        _ if offset % 8 != 0 => Err(BKSegmentIssue::Misaligned{offset, alignment: 8}),
+                        Some((texture_index, start)) if offset < len => Ok(BKSegmentTarget::Texture{texture_index, offset: offset - start}),
+                        _ => Err(BKSegmentIssue::TextureOutOfRange{offset, len}),
+                    }
+                },
+                _ => {
+                    let gfx_index = offset/8;
+                    if offset % 8 != 0 {
+                        Err(BKSegmentIssue::Misaligned{offset, alignment: 8})
+                    } else if gfx_index >= self.len() {
+                        Err(BKSegmentIssue::GfxOutOfRange{gfx_index, len: self.len()})
+                    } else {
+                        Ok(BKSegmentTarget::Gfx{gfx_index})
+                    }
+                },
+            };
+            refs.push(BKSegmentRef{gfx_index, address, target});
+        }
+        refs
+    }
+
+    pub fn validate_segments(&self, vertices: Option<&BKVertexList>, textures: Option<&BKTextureList>)->Vec<BKSegmentDiagnostic>{
+        self.segment_refs(vertices, textures).into_iter()
+            .filter_map(|r| r.target.err().map(|issue| BKSegmentDiagnostic{gfx_index: r.gfx_index, issue}))
+            .collect()
+    }
+}
+
+impl BKModel{
+    //display list addresses against the model's own sections, check before writing the model
+    pub fn validate_segments(&self)->Vec<BKSegmentDiagnostic>{
+        let (gfx, stuck) = match self.relocated_display_list() {
+            Some(relocated) => relocated,
+            None => return Vec::new(),
+        };
+        let mut diagnostics : Vec<_> = gfx.validate_segments(self.vertices.as_ref(), self.texture_list.as_ref()).into_iter()
+            .filter(|d| !stuck.iter().any(|s| s.gfx_index == d.gfx_index))
+            .collect();
+        diagnostics.extend(stuck);
+        diagnostics.sort_by_key(|d| d.gfx_index);
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libultra::F3dex;
+    use super::super::f3dex::tests::test_model;
+
+    #[test]
+    fn gfx_segments() {
+        let mut model = test_model();
+        assert!(model.validate_segments().is_empty());
+        let refs = model.display_list.as_ref().unwrap().segment_refs(model.vertices.as_ref(), model.texture_list.as_ref());
+        assert_eq!(refs[0].target, Ok(BKSegmentTarget::Texture{texture_index: 0, offset: 0}));
+        assert_eq!(refs[1].target, Ok(BKSegmentTarget::Vertices{first: 0, count: 4}));
+
+        let gfx = model.display_list.as_mut().unwrap();
+        gfx.extend([
+            0x0400103F_01000010u64, //G_VTX 4 from vertex 1
+            0xFD500000_02000008,    //G_SETTIMG past the texture data
+            0x06000000_03000100,    //G_DL past the list
+            0x06000000_0E000000,    //G_DL into a game segment
+            0x04000000_01000004,    //G_VTX misaligned
+            0x0400103F_02000000,    //G_VTX into the texture data
+            0x06000000_01000000,    //G_DL into the vertices
+        ].map(F3dex::from));
+        let issues : Vec<_> = model.validate_segments().into_iter().map(|d| (d.gfx_index, d.issue)).collect();
+        assert_eq!(issues, [
+            (5, BKSegmentIssue::VerticesOutOfRange{first: 1, count: 4, len: 4}),
+            (6, BKSegmentIssue::TextureOutOfRange{offset: 8, len: 4}),
+            (7, BKSegmentIssue::GfxOutOfRange{gfx_index: 0x20, len: 12}),
+            (8, BKSegmentIssue::UnknownSegment{segment: 0x0E}),
+            (9, BKSegmentIssue::Misaligned{offset: 4, alignment: 0x10}),
+            (10, BKSegmentIssue::WrongSegment{segment: TEXTURE_SEGMENT, expected: VTX_SEGMENT}),
+            (11, BKSegmentIssue::WrongSegment{segment: VTX_SEGMENT, expected: GFX_SEGMENT}),
+        ]);
+        assert!(!issues[3].1.is_error());
+        assert!(issues[4..].iter().all(|(_, issue)| issue.is_error()));
+    }
+}
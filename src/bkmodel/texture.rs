@@ -1,4 +1,6 @@
 use super::super::bktexture::*;
+use super::super::pixels::*;
+use super::super::error::TextureSizeError;
 
 #[derive(Debug)]
 pub struct BKTextureList{
@@ -36,4 +38,136 @@ impl BKTextureList{
     pub fn size(&self)->usize{
         8 + 0x10*self.texture_headers.len() + self.texture_data.len()
     }
-}
\ No newline at end of file
+
+    //bytes from a texture's offset to the start of the next one
+    pub fn texture_span(&self, index: usize)->usize{
+        let offset = self.texture_headers[index].offset;
+        let end = self.texture_headers.iter()
+            .map(|hdr| hdr.offset)
+            .filter(|&o| o > offset)
+            .min()
+            .unwrap_or(self.texture_data.len());
+        end.saturating_sub(offset)
+    }
+
+    //texture whose bytes (palette and levels) contain `offset` into texture_data
+    pub fn texture_at(&self, offset: usize)->Option<usize>{
+        (0..self.texture_headers.len()).find(|&i| {
+            let start = self.texture_headers[i].offset;
+            start <= offset && offset < start + self.texture_span(i)
+        })
+    }
+
+    //the header's level chain, less any level past the end of the texture data
+    pub fn levels(&self, index: usize)->Vec<BKTextureLevel>{
+        let mut levels = self.texture_headers[index].levels();
+        levels.retain(|lvl| lvl.offset + lvl.size <= self.texture_data.len());
+        levels
+    }
+
+    pub fn palette(&self, index: usize)->Option<Vec<RGBA16>>{
+        let header = &self.texture_headers[index];
+        let size = 2*header.palette_size();
+        if size == 0 { return None }
+        let bytes = self.texture_data.get(header.offset .. header.offset + size)?;
+        Some(bytes.iter().cloned().rgba16_iter().collect())
+    }
+
+    pub fn texture(&self, index: usize, level: usize)->Option<BKTexture<RGBA32>>{
+        let header = &self.texture_headers[index];
+        let lvl = *self.levels(index).get(level)?;
+        let bytes = [
+            self.texture_data.get(header.offset .. header.offset + 2*header.palette_size())?,
+            &self.texture_data[lvl.offset .. lvl.offset + lvl.size],
+        ].concat();
+        header.format.decode(lvl.width, lvl.height, &bytes)
+    }
+
+    //rebuilds every lower level from the top one, returns the number of levels rewritten
+    pub fn regenerate_levels(&mut self, index: usize)->Option<usize>{
+        let format = self.texture_headers[index].format;
+        let palette_size = 2*format.palette_size();
+        let palette = self.palette(index);
+        let levels = self.levels(index);
+        let mut image = self.texture(index, 0)?;
+
+        for lvl in levels.iter().skip(1) {
+            image = image.box_filter();
+            let bytes = format.encode(&image, palette.clone())?;
+            self.texture_data[lvl.offset .. lvl.offset + lvl.size].copy_from_slice(&bytes[palette_size .. palette_size + lvl.size]);
+        }
+        Some(levels.len().saturating_sub(1))
+    }
+
+    pub fn validate_tmem(&self)->Vec<TmemDiagnostic>{
+        (0..self.texture_headers.len())
+            .flat_map(|i| self.texture_headers[i].validate_tmem().into_iter()
+                .map(move |issue| TmemDiagnostic{texture_index: Some(i), gfx_index: None, issue})
+            )
+            .collect()
+    }
+
+    pub fn set_palette(&mut self, index: usize, palette: &[RGBA16])->Result<(), TextureSizeError>{
+        let header = &self.texture_headers[index];
+        let size = header.palette_size();
+        if size == 0 || palette.len() > size { return Err(TextureSizeError) }
+        let bytes : Vec<u8> = palette.iter()
+            .chain(std::iter::repeat(&RGBA16{r: 0, g: 0, b: 0, a: 0}))
+            .take(size)
+            .flat_map(RGBA16::to_be_bytes)
+            .collect();
+        let offset = header.offset;
+        self.texture_data.get_mut(offset .. offset + bytes.len()).ok_or(TextureSizeError)?.copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    //CI textures with byte identical palettes, only groups of two or more
+    pub fn palette_groups(&self)->Vec<Vec<usize>>{
+        let mut groups : Vec<(Vec<RGBA16>, Vec<usize>)> = Vec::new();
+        for i in 0..self.texture_headers.len() {
+            let palette = match self.palette(i) {
+                Some(palette) => palette,
+                None => continue,
+            };
+            match groups.iter_mut().find(|(p, _)| *p == palette) {
+                Some((_, members)) => members.push(i),
+                None => groups.push((palette, vec![i])),
+            }
+        }
+        groups.into_iter().map(|(_, members)| members).filter(|members| members.len() > 1).collect()
+    }
+
+    /* palettes are stored in front of every CI texture, so a shared palette is
+        written to each texture of the group that shares it
+    */
+    pub fn set_shared_palette(&mut self, index: usize, palette: &[RGBA16])->Result<usize, TextureSizeError>{
+        let group = self.palette_groups().into_iter()
+            .find(|members| members.contains(&index))
+            .unwrap_or(vec![index]);
+        for &i in group.iter() {
+            self.set_palette(i, palette)?;
+        }
+        Ok(group.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_list_shared_palette() {
+        let header = |offset| BKTextureHeader{offset, format: BKTextureFormat::CI4, flags: 0, level_count: 0, width: 2, height: 1, padding: [0; 6]};
+        let mut list = BKTextureList{
+            texture_headers: vec![header(0), header(0x21), header(0x42)],
+            texture_data: [vec![0x11; 0x21], vec![0x11; 0x21], vec![0x22; 0x21]].concat(),
+        };
+        assert_eq!(list.palette_groups(), vec![vec![0, 1]]);
+
+        let red = RGBA16{r: 0x1F, g: 0, b: 0, a: 1};
+        assert_eq!(list.set_shared_palette(1, &[red]).unwrap(), 2);
+        assert_eq!(list.palette(0).unwrap()[0], red);
+        assert_eq!(list.palette(1), list.palette(0));
+        assert_eq!(list.palette(2).unwrap()[0], RGBA16::from_be_bytes([0x22, 0x22]));
+    }
+}
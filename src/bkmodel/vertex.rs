@@ -55,6 +55,10 @@ pub struct BKVertexList{
 }
 
 impl BKVertexList{
+    pub fn new(vertex: Vec<Vtx>)->BKVertexList{
+        BKVertexList{vertex, preserved_global_norm: None}
+    }
+
     pub fn from_be_bytes(bytes: &[u8]) -> BKVertexList{
         let header = BKVertexListHeader::from_be_bytes(bytes[0..0x18].try_into().unwrap());
         let vertex = bytes[0x18..]
@@ -143,6 +147,17 @@ impl BKVertexList{
     pub fn size(&self) -> usize{
         0x18 + 0x10*self.len()
     }
+
+    //S10.5 texture coordinates, from the Vtx bytes {ob[3], flag, tc[2], cn[4]}
+    pub fn tex_coords(&self, index: usize) -> [i16; 2]{
+        let bytes = self.vertex[index].to_bytes();
+        [i16::from_be_bytes([bytes[8], bytes[9]]), i16::from_be_bytes([bytes[10], bytes[11]])]
+    }
+
+    //vertex colour, or the normal when lighting is on
+    pub fn color(&self, index: usize) -> [u8; 4]{
+        self.vertex[index].to_bytes()[12..16].try_into().unwrap()
+    }
 }
 
 impl Deref for BKVertexList{
@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+
+use super::{BKTexture, BKTextureFormat};
+use super::super::pixels::*;
+
+/* Format selection
+    every candidate is encoded and decoded again, the smallest one (palette included)
+    whose RMS error stays under the threshold wins. the analysis only rules out
+    formats that can't possibly fit before doing the round trip.
+    colour of fully transparent texels is ignored when measuring error.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureAnalysis{
+    pub greyscale: bool,
+    pub alpha_levels: usize, //distinct alpha values
+    pub color_count: usize,  //distinct colours once reduced to RGBA16
+}
+
+const CANDIDATES : [BKTextureFormat; 9] = [
+    BKTextureFormat::I4,
+    BKTextureFormat::IA4,
+    BKTextureFormat::CI4,
+    BKTextureFormat::I8,
+    BKTextureFormat::IA8,
+    BKTextureFormat::CI8,
+    BKTextureFormat::IA16,
+    BKTextureFormat::RGBA16,
+    BKTextureFormat::RGBA32,
+];
+
+impl BKTexture<RGBA32> {
+    pub fn analyze(&self)->TextureAnalysis{
+        let pixels = || self.tmem.iter().flatten();
+        TextureAnalysis{
+            greyscale: pixels().all(|px| px.a == 0 || (px.r == px.g && px.g == px.b)),
+            alpha_levels: pixels().map(|px| px.a).collect::<HashSet<_>>().len(),
+            color_count: pixels().map(RGBA16::from).map(|c| (c.r, c.g, c.b, c.a)).collect::<HashSet<_>>().len(),
+        }
+    }
+
+    //root mean square difference over the compared channels, 0-255
+    pub fn rms_error(&self, other: &BKTexture<RGBA32>)->f32{
+        let mut sum = 0.0;
+        let mut count = 0;
+        for (a, b) in self.tmem.iter().flatten().zip(other.tmem.iter().flatten()) {
+            let channels = if a.a == 0 && b.a == 0 { 3..4 } else { 0..4 };
+            count += channels.len();
+            for ch in channels {
+                let d = [a.r, a.g, a.b, a.a][ch] as f32 - [b.r, b.g, b.b, b.a][ch] as f32;
+                sum += d*d;
+            }
+        }
+        if count == 0 { return 0.0 }
+        (sum/count as f32).sqrt()
+    }
+
+    fn may_fit(&self, analysis: &TextureAnalysis, format: &BKTextureFormat)->bool{
+        match format {
+            BKTextureFormat::I4 | BKTextureFormat::I8 => analysis.greyscale && analysis.alpha_levels == 1,
+            BKTextureFormat::IA4 | BKTextureFormat::IA8 | BKTextureFormat::IA16 => analysis.greyscale,
+            BKTextureFormat::CI4 | BKTextureFormat::CI8 => analysis.alpha_levels <= 2,
+            _ => true,
+        }
+    }
+
+    pub fn recommend_format(&self, max_error: f32)->BKTextureFormat{
+        self.encode_best(max_error).map_or(BKTextureFormat::RGBA32, |(format, _)| format)
+    }
+
+    //smallest format within max_error and its encoded bytes
+    pub fn encode_best(&self, max_error: f32)->Option<(BKTextureFormat, Vec<u8>)>{
+        let (width, height) = (self.width(), self.height());
+        let analysis = self.analyze();
+        let mut candidates : Vec<BKTextureFormat> = CANDIDATES.iter().cloned()
+            .filter(|format| self.may_fit(&analysis, format))
+            .collect();
+        candidates.sort_by_key(|format| format.byte_size(width, height)); //stable, keeps preference on ties
+
+        candidates.into_iter()
+            .filter_map(|format| {
+                let bytes = format.encode(self, None)?;
+                let decoded = format.decode(width, height, &bytes)?;
+                (self.rms_error(&decoded) <= max_error).then_some((format, bytes))
+            })
+            .next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(pixels: impl Fn(usize, usize)->RGBA32)->BKTexture<RGBA32>{
+        BKTexture::<RGBA32>{palette: None, tmem: (0..8).map(|y| (0..8).map(|x| pixels(x, y)).collect()).collect()}
+    }
+
+    #[test]
+    fn analyze_recommends_smallest_format() {
+        let grey = texture(|x, y| { let v = (0x11*((x + y) & 0xF)) as u8; RGBA32{r: v, g: v, b: v, a: 0xFF} });
+        assert_eq!(grey.analyze(), TextureAnalysis{greyscale: true, alpha_levels: 1, color_count: 15});
+        assert_eq!(grey.recommend_format(0.0), BKTextureFormat::I4);
+
+        let palette = [RGBA32{r: 0xFF, g: 0, b: 0, a: 0xFF}, RGBA32{r: 0, g: 0xFF, b: 0, a: 0xFF}, RGBA32{r: 0, g: 0, b: 0, a: 0}];
+        let few_colors = texture(|x, y| palette[(x + y) % 3].clone());
+        assert_eq!(few_colors.recommend_format(0.0), BKTextureFormat::CI4);
+
+        let gradient = texture(|x, y| RGBA32{r: (x*32) as u8, g: (y*32) as u8, b: ((x*y) as u8) << 2, a: 0xFF});
+        assert_eq!(gradient.recommend_format(0.0), BKTextureFormat::RGBA32);
+        let (format, bytes) = gradient.encode_best(8.0).unwrap();
+        assert_eq!(format, BKTextureFormat::RGBA16);
+        assert_eq!(bytes.len(), 8*8*2);
+
+        //texels transparent in both only count their alpha
+        let grey = |r: u8, a: u8| RGBA32{r, g: 0x80, b: 0x80, a};
+        let clear = texture(|x, _| if x < 4 { RGBA32{r: 0, g: 0, b: 0, a: 0} } else { grey(0x80, 0xFF) });
+        let shifted = texture(|x, _| if x < 4 { RGBA32{r: 0xFF, g: 0xFF, b: 0xFF, a: 0} } else { grey(0x88, 0xFF) });
+        assert!((clear.rms_error(&shifted) - (32.0*64.0/(32.0*4.0 + 32.0) as f32).sqrt()).abs() < 1e-4);
+    }
+}
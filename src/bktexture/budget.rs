@@ -0,0 +1,119 @@
+use super::{BKTextureFormat, BKTextureHeader};
+
+/* TMEM budget
+    TMEM is 4KB of 64 bit words, every texture line is padded to a whole word.
+    CI textures keep their palette (TLUT) in the upper 2KB, each entry stored
+    four times, leaving the lower 2KB for texels.
+    all levels of a mipmapped texture are loaded together.
+*/
+
+pub const TMEM_SIZE : usize = 0x1000;
+pub const TMEM_TLUT_OFFSET : usize = 0x800;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TmemUsage{
+    pub texels: usize,  //bytes, including line padding
+    pub palette: usize, //bytes of TLUT, quadrupled entries
+    pub limit: usize,   //bytes available to texels
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TmemIssue{
+    UnknownFormat,
+    DimensionsTooLarge{width: usize, height: usize}, //header stores u8 sizes
+    OverBudget{used: usize, limit: usize},
+    OverlapsPalette{end: usize},                     //texel load runs into the TLUT
+    UnpaddedLine{line_size: usize},                  //can't be loaded with LoadBlock
+    NotPowerOfTwo{width: usize, height: usize},      //wrapping and mirroring need powers of two
+}
+
+impl TmemIssue{
+    pub fn is_error(&self)->bool{
+        !matches!(self, TmemIssue::UnpaddedLine{..} | TmemIssue::NotPowerOfTwo{..})
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TmemDiagnostic{
+    pub texture_index: Option<usize>,
+    pub gfx_index: Option<usize>,
+    pub issue: TmemIssue,
+}
+
+pub fn tmem_line_size(width: usize, bits_per_pixel: usize)->usize{
+    (width*bits_per_pixel).div_ceil(64)*8
+}
+
+impl BKTextureFormat{
+    pub fn tmem_limit(&self)->usize{
+        match self.palette_size() {
+            0 => TMEM_SIZE,
+            _ => TMEM_TLUT_OFFSET,
+        }
+    }
+}
+
+impl BKTextureHeader{
+    pub fn tmem_usage(&self)->Option<TmemUsage>{
+        let bpp = self.bits_per_pixel()?;
+        let levels = self.levels();
+        let texels = match levels.is_empty() {
+            true => tmem_line_size(self.width, bpp)*self.height,
+            false => levels.iter().map(|lvl| tmem_line_size(lvl.width, bpp)*lvl.height).sum(),
+        };
+        Some(TmemUsage{texels, palette: 8*self.palette_size(), limit: self.format.tmem_limit()})
+    }
+
+    pub fn validate_tmem(&self)->Vec<TmemIssue>{
+        let mut issues = Vec::new();
+        if self.width > 0xFF || self.height > 0xFF {
+            issues.push(TmemIssue::DimensionsTooLarge{width: self.width, height: self.height});
+        }
+        let (bpp, usage) = match (self.bits_per_pixel(), self.tmem_usage()) {
+            (Some(bpp), Some(usage)) => (bpp, usage),
+            _ => { issues.push(TmemIssue::UnknownFormat); return issues },
+        };
+        if usage.texels > usage.limit {
+            issues.push(TmemIssue::OverBudget{used: usage.texels, limit: usage.limit});
+        }
+        let line_size = self.width*bpp/8;
+        if (self.width*bpp) & 63 != 0 {
+            issues.push(TmemIssue::UnpaddedLine{line_size});
+        }
+        if !self.width.is_power_of_two() || !self.height.is_power_of_two() {
+            issues.push(TmemIssue::NotPowerOfTwo{width: self.width, height: self.height});
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(format: BKTextureFormat, width: usize, height: usize)->BKTextureHeader{
+        BKTextureHeader{offset: 0, format, flags: 0, level_count: 0, width, height, padding: [0; 6]}
+    }
+
+    #[test]
+    fn tmem_budget() {
+        //64x32 RGBA16 fills TMEM exactly
+        let rgba16 = header(BKTextureFormat::RGBA16, 64, 32);
+        assert_eq!(rgba16.tmem_usage().unwrap().texels, TMEM_SIZE);
+        assert!(rgba16.validate_tmem().is_empty());
+
+        //64x64 CI8 only has the lower half
+        let ci8 = header(BKTextureFormat::CI8, 64, 64);
+        assert_eq!(ci8.validate_tmem(), vec![TmemIssue::OverBudget{used: 0x1000, limit: 0x800}]);
+
+        //odd widths are padded to whole words
+        let i4 = header(BKTextureFormat::I4, 12, 4);
+        assert_eq!(i4.tmem_usage().unwrap().texels, 4*8);
+        let issues = i4.validate_tmem();
+        assert!(issues.iter().all(|issue| !issue.is_error()));
+        assert!(issues.contains(&TmemIssue::UnpaddedLine{line_size: 6}));
+
+        let wide = header(BKTextureFormat::I8, 0x100, 1);
+        assert!(wide.validate_tmem().contains(&TmemIssue::DimensionsTooLarge{width: 0x100, height: 1}));
+    }
+}
@@ -0,0 +1,156 @@
+use super::BKTexture;
+use super::super::pixels::*;
+
+/* Lossy whole-image conversion from RGBA32
+    - channels are rounded to the nearest level instead of truncated
+    - optional ordered (4x4 Bayer) or error-diffusion (Floyd-Steinberg) dithering
+    - 1 bit channels (alpha of RGBA16/IA4) are thresholded, never dithered
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dither{
+    None,
+    Bayer,
+    FloydSteinberg,
+}
+
+const BAYER_4X4 : [[u8; 4]; 4] = [
+    [ 0,  8,  2, 10],
+    [12,  4, 14,  6],
+    [ 3, 11,  1,  9],
+    [15,  7, 13,  5],
+];
+
+pub trait DitherTarget: Sized {
+    //bits per channel, 0 for unused channels
+    const DEPTHS: [u8; 4];
+    fn channels(px: &RGBA32)->[f32; 4];
+    fn from_levels(levels: [u8; 4])->Self;
+}
+
+fn intensity(px: &RGBA32)->f32{
+    (px.r as f32 + px.g as f32 + px.b as f32)/3.0
+}
+
+impl DitherTarget for RGBA16 {
+    const DEPTHS: [u8; 4] = [5, 5, 5, 1];
+    fn channels(px: &RGBA32)->[f32; 4]{ [px.r as f32, px.g as f32, px.b as f32, px.a as f32] }
+    fn from_levels(l: [u8; 4])->Self{ RGBA16{r: l[0], g: l[1], b: l[2], a: l[3]} }
+}
+
+impl DitherTarget for IA4 {
+    const DEPTHS: [u8; 4] = [3, 0, 0, 1];
+    fn channels(px: &RGBA32)->[f32; 4]{ [intensity(px), 0.0, 0.0, px.a as f32] }
+    fn from_levels(l: [u8; 4])->Self{ IA4{i: l[0], a: l[3]} }
+}
+
+impl DitherTarget for IA8 {
+    const DEPTHS: [u8; 4] = [4, 0, 0, 4];
+    fn channels(px: &RGBA32)->[f32; 4]{ [intensity(px), 0.0, 0.0, px.a as f32] }
+    fn from_levels(l: [u8; 4])->Self{ IA8{i: l[0], a: l[3]} }
+}
+
+impl DitherTarget for I4 {
+    const DEPTHS: [u8; 4] = [4, 0, 0, 0];
+    fn channels(px: &RGBA32)->[f32; 4]{ [intensity(px), 0.0, 0.0, 0.0] }
+    fn from_levels(l: [u8; 4])->Self{ I4{i: l[0]} }
+}
+
+impl DitherTarget for I8 {
+    const DEPTHS: [u8; 4] = [8, 0, 0, 0];
+    fn channels(px: &RGBA32)->[f32; 4]{ [intensity(px), 0.0, 0.0, 0.0] }
+    fn from_levels(l: [u8; 4])->Self{ I8{i: l[0]} }
+}
+
+fn max_level(bits: u8)->f32{
+    ((1u32 << bits) - 1) as f32
+}
+
+fn to_level(value: f32, bits: u8)->u8{
+    let max = max_level(bits);
+    (value * max / 255.0).round().clamp(0.0, max) as u8
+}
+
+fn from_level(level: u8, bits: u8)->f32{
+    level as f32 * 255.0 / max_level(bits)
+}
+
+impl BKTexture<RGBA32> {
+    pub fn dither<T: DitherTarget + Clone>(&self, mode: Dither)->BKTexture<T>{
+        let width = self.width();
+        let height = self.height();
+        let mut values : Vec<[f32; 4]> = self.tmem.iter().flatten().map(T::channels).collect();
+        let mut out : Vec<T> = Vec::with_capacity(values.len());
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut levels = [0u8; 4];
+                for (ch, &bits) in T::DEPTHS.iter().enumerate() {
+                    if bits == 0 { continue }
+                    let value = values[y*width + x][ch];
+                    if bits == 1 {
+                        levels[ch] = if value >= 128.0 {1} else {0};
+                        continue;
+                    }
+
+                    levels[ch] = match mode {
+                        Dither::None | Dither::FloydSteinberg => to_level(value, bits),
+                        Dither::Bayer => {
+                            let threshold = (BAYER_4X4[y % 4][x % 4] as f32 + 0.5)/16.0 - 0.5;
+                            to_level(value + threshold * 255.0 / max_level(bits), bits)
+                        },
+                    };
+
+                    if mode == Dither::FloydSteinberg {
+                        let error = value - from_level(levels[ch], bits);
+                        let mut spread = |dx: isize, dy: usize, weight: f32| {
+                            let nx = x as isize + dx;
+                            if nx < 0 || nx >= width as isize || y + dy >= height { return }
+                            values[(y + dy)*width + nx as usize][ch] += error * weight / 16.0;
+                        };
+                        spread( 1, 0, 7.0);
+                        spread(-1, 1, 3.0);
+                        spread( 0, 1, 5.0);
+                        spread( 1, 1, 1.0);
+                    }
+                }
+                out.push(T::from_levels(levels));
+            }
+        }
+
+        let tmem = if width == 0 { Vec::new() } else {
+            out.chunks_exact(width).map(|row| row.to_vec()).collect()
+        };
+        BKTexture::<T>{palette: None, tmem}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat(width: usize, height: usize, v: u8)->BKTexture<RGBA32>{
+        BKTexture::<RGBA32>{palette: None, tmem: vec![vec![RGBA32{r: v, g: v, b: v, a: 0xFF}; width]; height]}
+    }
+
+    #[test]
+    fn dither_none_rounds() {
+        let rgba16 = flat(1, 1, 0x06).dither::<RGBA16>(Dither::None);
+        assert_eq!(rgba16.tmem[0][0], RGBA16{r: 1, g: 1, b: 1, a: 1});
+
+        let ia4 = flat(1, 1, 0xFF).dither::<IA4>(Dither::None);
+        assert_eq!(ia4.tmem[0][0], IA4{i: 7, a: 1});
+    }
+
+    #[test]
+    fn dither_preserves_mean() {
+        //0x80 sits between I4 levels 7 (0x77) and 8 (0x88)
+        for mode in [Dither::Bayer, Dither::FloydSteinberg] {
+            let i4 = flat(16, 16, 0x80).dither::<I4>(mode);
+            let levels : Vec<u8> = i4.tmem.iter().flatten().map(|px| px.i).collect();
+            assert!(levels.iter().all(|&i| i == 7 || i == 8));
+            let mean = levels.iter().map(|&i| from_level(i, 4)).sum::<f32>() / levels.len() as f32;
+            assert!((mean - 128.0).abs() < 2.0, "{:?}: {}", mode, mean);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use super::BKTexture;
+use super::super::pixels::*;
+
+/* Three point filter preview
+    the RDP blends three texels instead of four: the triangle of the texel quad
+    that contains the sample point. samples are taken at pixel centres,
+    addressing is chosen per axis like G_TX_CLAMP / G_TX_WRAP / G_TX_MIRROR.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureAddress{
+    Clamp,
+    Wrap,
+    Mirror,
+}
+
+impl TextureAddress{
+    pub fn apply(&self, i: isize, size: usize)->usize{
+        let n = size as isize;
+        match self {
+            TextureAddress::Clamp => i.clamp(0, n - 1) as usize,
+            TextureAddress::Wrap => i.rem_euclid(n) as usize,
+            TextureAddress::Mirror => {
+                let i = i.rem_euclid(2*n);
+                (if i < n { i } else { 2*n - 1 - i }) as usize
+            },
+        }
+    }
+}
+
+impl BKTexture<RGBA32> {
+    pub fn three_point(&self, width: usize, height: usize, address_s: TextureAddress, address_t: TextureAddress)->BKTexture<RGBA32>{
+        let (src_width, src_height) = (self.width(), self.height());
+        if src_width == 0 || src_height == 0 { return BKTexture::<RGBA32>{palette: None, tmem: Vec::new()} }
+
+        let texel = |s: isize, t: isize| {
+            let px = &self.tmem[address_t.apply(t, src_height)][address_s.apply(s, src_width)];
+            [px.r, px.g, px.b, px.a].map(|c| c as f32)
+        };
+
+        let tmem = (0..height).map(|y| (0..width).map(|x| {
+                let u = (x as f32 + 0.5)*src_width as f32/width as f32 - 0.5;
+                let v = (y as f32 + 0.5)*src_height as f32/height as f32 - 0.5;
+                let (s, t) = (u.floor() as isize, v.floor() as isize);
+                let (fu, fv) = (u - u.floor(), v - v.floor());
+
+                let (t00, t10, t01, t11) = (texel(s, t), texel(s + 1, t), texel(s, t + 1), texel(s + 1, t + 1));
+                let c : [f32; 4] = std::array::from_fn(|ch| match fu + fv < 1.0 {
+                    true => t00[ch] + fu*(t10[ch] - t00[ch]) + fv*(t01[ch] - t00[ch]),
+                    false => t11[ch] + (1.0 - fu)*(t01[ch] - t11[ch]) + (1.0 - fv)*(t10[ch] - t11[ch]),
+                });
+                let [r, g, b, a] = c.map(|v| v.round().clamp(0.0, 255.0) as u8);
+                RGBA32{r, g, b, a}
+            }).collect())
+            .collect();
+        BKTexture::<RGBA32>{palette: None, tmem}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_three_point() {
+        let black = RGBA32{r: 0, g: 0, b: 0, a: 0xFF};
+        let white = RGBA32{r: 0xFF, g: 0xFF, b: 0xFF, a: 0xFF};
+        let texture = BKTexture::<RGBA32>{palette: None, tmem: vec![vec![black.clone(), white.clone()]]};
+
+        let same = texture.three_point(2, 1, TextureAddress::Clamp, TextureAddress::Clamp);
+        assert_eq!(same.tmem, texture.tmem);
+
+        let clamped = texture.three_point(4, 1, TextureAddress::Clamp, TextureAddress::Clamp);
+        assert_eq!(clamped.tmem[0].iter().map(|px| px.r).collect::<Vec<_>>(), vec![0x00, 0x40, 0xBF, 0xFF]);
+
+        //wrapping blends the right edge back into the first texel
+        let wrapped = texture.three_point(4, 1, TextureAddress::Wrap, TextureAddress::Clamp);
+        assert_eq!(wrapped.tmem[0][0].r, 0x40);
+        assert_eq!(wrapped.tmem[0][3].r, 0xBF);
+
+        assert_eq!(TextureAddress::Mirror.apply(-1, 4), 0);
+        assert_eq!(TextureAddress::Mirror.apply(5, 4), 2);
+        assert_eq!(TextureAddress::Wrap.apply(5, 4), 1);
+    }
+}
@@ -0,0 +1,101 @@
+use std::cmp;
+
+use super::{BKTexture, BKTextureHeader};
+use super::super::pixels::*;
+
+/* Texture level chains
+    the header's level_count says how many halved images follow the top level,
+    each one right after the previous. CI levels share the palette stored in
+    front of the top level.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BKTextureLevel{
+    pub offset: usize, //texels, relative to the start of the texture data
+    pub width: usize,
+    pub height: usize,
+    pub size: usize,
+}
+
+impl BKTextureHeader{
+    pub fn bits_per_pixel(&self)->Option<usize>{
+        self.format.bits_per_pixel()
+    }
+
+    pub fn palette_size(&self)->usize{
+        self.format.palette_size()
+    }
+
+    //the top level and the level_count levels after it
+    pub fn levels(&self)->Vec<BKTextureLevel>{
+        let bpp = match self.bits_per_pixel() {
+            Some(bpp) => bpp,
+            None => return Vec::new(),
+        };
+        let mut offset = self.offset + 2*self.palette_size();
+        let (mut width, mut height) = (self.width, self.height);
+        let mut levels = Vec::new();
+        for _ in 0 ..= self.level_count {
+            let size = (width*height*bpp).div_ceil(8);
+            if size == 0 { break }
+            levels.push(BKTextureLevel{offset, width, height, size});
+            offset += size;
+            width = cmp::max(width/2, 1);
+            height = cmp::max(height/2, 1);
+        }
+        levels
+    }
+}
+
+impl BKTexture<RGBA32> {
+    //2x2 box filter down to the next level, odd edges reuse the last row/column
+    pub fn box_filter(&self)->BKTexture<RGBA32>{
+        let (width, height) = (self.width(), self.height());
+        if width == 0 || height == 0 { return self.clone() }
+        let out_width = cmp::max(width/2, 1);
+        let out_height = cmp::max(height/2, 1);
+
+        let tmem = (0..out_height).map(|y| (0..out_width).map(|x| {
+                let samples = [(2*x, 2*y), (2*x + 1, 2*y), (2*x, 2*y + 1), (2*x + 1, 2*y + 1)]
+                    .map(|(sx, sy)| &self.tmem[cmp::min(sy, height - 1)][cmp::min(sx, width - 1)]);
+                let mean = |f: fn(&RGBA32)->u8| ((samples.iter().map(|px| f(px) as u32).sum::<u32>() + 2)/4) as u8;
+                RGBA32{r: mean(|px| px.r), g: mean(|px| px.g), b: mean(|px| px.b), a: mean(|px| px.a)}
+            }).collect())
+            .collect();
+        BKTexture::<RGBA32>{palette: None, tmem}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::BKTextureFormat;
+
+    #[test]
+    fn mipmap_level_chain() {
+        let header = BKTextureHeader{
+            offset: 0x100, format: BKTextureFormat::CI4, flags: 0, level_count: 3, width: 8, height: 4, padding: [0; 6]
+        };
+        //palette + 8x4 + 4x2 + 2x1 + 1x1
+        let levels = header.levels();
+        assert_eq!(levels.iter().map(|l| (l.width, l.height)).collect::<Vec<_>>(), vec![(8, 4), (4, 2), (2, 1), (1, 1)]);
+        assert_eq!(levels[0].offset, 0x120);
+        assert_eq!(levels[1].offset, 0x130);
+
+        let bytes = header.to_be_bytes();
+        assert_eq!(bytes[6..8], [0, 3]);
+        assert_eq!(BKTextureHeader::from_be_bytes(&bytes).level_count, 3);
+        assert_eq!(BKTextureHeader{level_count: 0, ..header}.levels().len(), 1);
+    }
+
+    #[test]
+    fn mipmap_box_filter() {
+        let px = |v: u8| RGBA32{r: v, g: v, b: v, a: 0xFF};
+        let texture = BKTexture::<RGBA32>{palette: None, tmem: vec![
+            vec![px(0), px(4), px(8)],
+            vec![px(4), px(8), px(8)],
+        ]};
+        let half = texture.box_filter();
+        assert_eq!(half.tmem, vec![vec![px(4)]]);
+    }
+}
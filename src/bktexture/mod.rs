@@ -0,0 +1,347 @@
+use super::pixels::*;
+use super::error::TextureDataError;
+
+mod quantize;
+pub use quantize::{*};
+
+mod dither;
+pub use dither::{*};
+
+mod tmem;
+pub use tmem::{*};
+
+mod mipmap;
+pub use mipmap::{*};
+
+mod png_file;
+pub use png_file::{*};
+
+mod budget;
+pub use budget::{*};
+
+mod analyze;
+pub use analyze::{*};
+
+mod palette;
+pub use palette::{*};
+
+mod filter;
+pub use filter::{*};
+
+/* BKTexture Trait can 
+    - be converted between texture types 
+    - from_be_bytes
+    - to_be_bytes
+*/
+
+#[derive(Debug, Clone)]
+pub struct BKTexture<T>
+{
+    pub palette: Option<Vec<RGBA16>>,
+    pub tmem: Vec<Vec<T>>
+}
+
+impl<T> BKTexture<T> {
+    pub fn width(&self)->usize{
+        self.tmem.first().map(Vec::len).unwrap_or(0)
+    }
+
+    pub fn height(&self)->usize{
+        self.tmem.len()
+    }
+}
+
+impl<T: Pixel> BKTexture<T> {
+    pub fn from_be_bytes(width: usize, height: usize, bytes: &[u8])->Result<Self, TextureDataError>{
+        let palette_size = 2*T::PALETTE_SIZE;
+        if bytes.len() < palette_size + (width*height*T::BITS_PER_PIXEL).div_ceil(8) {
+            return Err(TextureDataError)
+        }
+        let palette = match T::PALETTE_SIZE {
+            0 => None,
+            _ => Some(bytes[..palette_size].iter().cloned().rgba16_iter().collect()),
+        };
+        let tmem = match width {
+            0 => Vec::new(),
+            _ => T::decode_iter(bytes[palette_size..].iter().cloned())
+                .take(width*height)
+                .collect::<Vec<_>>()
+                .chunks_exact(width)
+                .map(|row| row.to_vec())
+                .collect(),
+        };
+        Ok(BKTexture::<T>{palette, tmem})
+    }
+
+    pub fn to_be_bytes(&self)->Vec<u8>{
+        let mut palette = self.palette.clone().unwrap_or_default();
+        palette.resize(T::PALETTE_SIZE, RGBA16{r: 0, g: 0, b: 0, a: 0});
+        palette.iter().flat_map(RGBA16::to_be_bytes)
+            .chain(T::encode_iter(self.tmem.iter().flatten().cloned()))
+            .collect()
+    }
+
+    pub fn to_rgba32(&self)->BKTexture<RGBA32>{
+        self.convert::<RGBA32>()
+    }
+
+    pub fn convert<U: Pixel>(&self)->BKTexture<U>{
+        //CI -> CI keeps the palette when it fits, otherwise generate one
+        let palette = match (U::PALETTE_SIZE, &self.palette) {
+            (0, _) => None,
+            (size, Some(palette)) if palette.len() <= size => Some(palette.clone()),
+            (size, _) => {
+                let palette = self.palette.as_deref().unwrap_or(&[]);
+                let rgba32 : Vec<RGBA32> = self.tmem.iter().flatten().map(|px| px.to_rgba32(palette)).collect();
+                Some(quantize(&rgba32, size, &QuantizeOptions::default()).0)
+            },
+        };
+        self.convert_with_palette(palette)
+    }
+
+    pub fn convert_with_palette<U: Pixel>(&self, palette: Option<Vec<RGBA16>>)->BKTexture<U>{
+        let src_palette = self.palette.as_deref().unwrap_or(&[]);
+        let dst_palette = palette.as_deref().unwrap_or(&[]);
+        let tmem = self.tmem.iter()
+            .map(|row| row.iter().map(|px| U::from_rgba32(&px.to_rgba32(src_palette), dst_palette)).collect())
+            .collect();
+        BKTexture::<U>{palette, tmem}
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BKTextureFormat{
+    CI4,
+    CI8,
+    I4,
+    I8,
+    IA4,
+    IA8,
+    IA16,
+    RGBA16,
+    RGBA32,
+    Unknown(u16),
+}
+
+impl BKTextureFormat{
+    pub fn bits_per_pixel(&self)->Option<usize>{
+        match self {
+            BKTextureFormat::CI4 => Some(CI4::BITS_PER_PIXEL),
+            BKTextureFormat::CI8 => Some(CI8::BITS_PER_PIXEL),
+            BKTextureFormat::I4 => Some(I4::BITS_PER_PIXEL),
+            BKTextureFormat::I8 => Some(I8::BITS_PER_PIXEL),
+            BKTextureFormat::IA4 => Some(IA4::BITS_PER_PIXEL),
+            BKTextureFormat::IA8 => Some(IA8::BITS_PER_PIXEL),
+            BKTextureFormat::IA16 => Some(IA16::BITS_PER_PIXEL),
+            BKTextureFormat::RGBA16 => Some(RGBA16::BITS_PER_PIXEL),
+            BKTextureFormat::RGBA32 => Some(RGBA32::BITS_PER_PIXEL),
+            BKTextureFormat::Unknown(_) => None,
+        }
+    }
+
+    pub fn palette_size(&self)->usize{
+        match self {
+            BKTextureFormat::CI4 => CI4::PALETTE_SIZE,
+            BKTextureFormat::CI8 => CI8::PALETTE_SIZE,
+            _ => 0,
+        }
+    }
+
+    //bytes used by the palette and a width x height image
+    pub fn byte_size(&self, width: usize, height: usize)->Option<usize>{
+        Some(2*self.palette_size() + (width*height*self.bits_per_pixel()?).div_ceil(8))
+    }
+
+    pub fn decode(&self, width: usize, height: usize, bytes: &[u8])->Option<BKTexture<RGBA32>>{
+        Some(match self {
+            BKTextureFormat::CI4 => BKTexture::<CI4>::from_be_bytes(width, height, bytes).ok()?.to_rgba32(),
+            BKTextureFormat::CI8 => BKTexture::<CI8>::from_be_bytes(width, height, bytes).ok()?.to_rgba32(),
+            BKTextureFormat::I4 => BKTexture::<I4>::from_be_bytes(width, height, bytes).ok()?.to_rgba32(),
+            BKTextureFormat::I8 => BKTexture::<I8>::from_be_bytes(width, height, bytes).ok()?.to_rgba32(),
+            BKTextureFormat::IA4 => BKTexture::<IA4>::from_be_bytes(width, height, bytes).ok()?.to_rgba32(),
+            BKTextureFormat::IA8 => BKTexture::<IA8>::from_be_bytes(width, height, bytes).ok()?.to_rgba32(),
+            BKTextureFormat::IA16 => BKTexture::<IA16>::from_be_bytes(width, height, bytes).ok()?.to_rgba32(),
+            BKTextureFormat::RGBA16 => BKTexture::<RGBA16>::from_be_bytes(width, height, bytes).ok()?.to_rgba32(),
+            BKTextureFormat::RGBA32 => BKTexture::<RGBA32>::from_be_bytes(width, height, bytes).ok()?,
+            BKTextureFormat::Unknown(_) => return None,
+        })
+    }
+
+    //palette only applies to CI formats, None generates one
+    pub fn encode(&self, texture: &BKTexture<RGBA32>, palette: Option<Vec<RGBA16>>)->Option<Vec<u8>>{
+        Some(match (self, palette) {
+            (BKTextureFormat::CI4, None) => texture.convert::<CI4>().to_be_bytes(),
+            (BKTextureFormat::CI4, palette) => texture.convert_with_palette::<CI4>(palette).to_be_bytes(),
+            (BKTextureFormat::CI8, None) => texture.convert::<CI8>().to_be_bytes(),
+            (BKTextureFormat::CI8, palette) => texture.convert_with_palette::<CI8>(palette).to_be_bytes(),
+            (BKTextureFormat::I4, _) => texture.convert::<I4>().to_be_bytes(),
+            (BKTextureFormat::I8, _) => texture.convert::<I8>().to_be_bytes(),
+            (BKTextureFormat::IA4, _) => texture.convert::<IA4>().to_be_bytes(),
+            (BKTextureFormat::IA8, _) => texture.convert::<IA8>().to_be_bytes(),
+            (BKTextureFormat::IA16, _) => texture.convert::<IA16>().to_be_bytes(),
+            (BKTextureFormat::RGBA16, _) => texture.convert::<RGBA16>().to_be_bytes(),
+            (BKTextureFormat::RGBA32, _) => texture.to_be_bytes(),
+            (BKTextureFormat::Unknown(_), _) => return None,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct BKTextureHeader {
+    pub offset: usize,
+    pub format: BKTextureFormat,
+    pub flags: u8,        //meaning unknown, kept as read
+    pub level_count: u8,  //smaller levels stored after the top one
+    pub width: usize,
+    pub height: usize,
+    pub padding: [u8;6],  //zero in game files
+}
+
+impl BKTextureHeader{
+    pub fn from_be_bytes(in_bytes: &[u8])->BKTextureHeader{
+        let offset = u32::from_be_bytes(in_bytes[..4].try_into().unwrap()) as usize;
+        let fmt_u16 = u16::from_be_bytes([in_bytes[4], in_bytes[5]]);
+        let format = match fmt_u16{
+            0x001 => BKTextureFormat::CI4,
+            0x004 => BKTextureFormat::CI8,
+            0x020 => BKTextureFormat::I4,
+            0x040 => BKTextureFormat::I8,
+            0x080 => BKTextureFormat::IA4,
+            0x100 => BKTextureFormat::IA8,
+            0x200 => BKTextureFormat::IA16,
+            0x400 => BKTextureFormat::RGBA16,
+            0x800 => BKTextureFormat::RGBA32,
+            _=> BKTextureFormat::Unknown(fmt_u16),
+        };
+        let width = in_bytes[8] as usize;
+        let height = in_bytes[9] as usize;
+        let padding = in_bytes[0xA.. 0x10].try_into().unwrap();
+        BKTextureHeader{
+            offset,
+            format,
+            flags: in_bytes[6],
+            level_count: in_bytes[7],
+            width,
+            height,
+            padding,
+        }
+    }
+
+    pub fn to_be_bytes(&self)->Vec<u8>{
+        let fmt : u16 = match self.format {
+            BKTextureFormat::CI4 => 0x001 ,
+            BKTextureFormat::CI8 => 0x004 ,
+            BKTextureFormat::I4 => 0x020 ,
+            BKTextureFormat::I8 => 0x040 ,
+            BKTextureFormat::IA4 => 0x080 ,
+            BKTextureFormat::IA8 => 0x100 ,
+            BKTextureFormat::IA16 => 0x200 ,
+            BKTextureFormat::RGBA16 => 0x400 ,
+            BKTextureFormat::RGBA32 => 0x800 ,
+            BKTextureFormat::Unknown(x)=> x,
+        };
+        
+        vec![
+            (self.offset as u32).to_be_bytes().to_vec(),
+            fmt.to_be_bytes().to_vec(),
+            vec![self.flags, self.level_count, self.width as u8, self.height as u8],
+            self.padding.to_vec(),
+        ].concat()
+    }
+}
+
+pub trait Texture {
+    //convertable to other texture types
+}
+// struct BKTextureCI4{
+//     CI4{palette: Box<[RGBA16; 0x10]>, tmem: Vec<Vec<usize>>},
+//     CI8{palette: Box<[RGBA16; 0x100]>, tmem: Vec<Vec<usize>>},
+// }
+
+// pub struct BKTexture {
+//     pub format : BKTextureFormat,
+//     pub palette: Option<Box<[Pixel]>>,
+//     pub tmem: Vec<Vec<Box<dyn Pxl>>>
+// } 
+
+// use super::pixels;
+// impl BKTexture {
+//     pub fn from_be_bytes(format: &BKTextureFormat, width: usize, height: usize, bytes: &[u8])->Self{
+//         let palette : Option<Box<[Pixel]>> = match format {
+//             BKTextureFormat::CI4 => Some(bytes[0..0x20].iter().cloned().rgba16_iter().collect()),
+//             BKTextureFormat::CI8 => Some(bytes[0..0x200].iter().cloned().rgba16_iter().collect()),
+//             _ => None,
+//         };
+
+//         let tmem : Vec<Pixel> = match format {
+//             BKTextureFormat::CI4 => bytes[0x20..].iter().cloned()
+//                 .ci4_iter()
+//                 .take(width*height).collect(),
+
+//             BKTextureFormat::CI8 => bytes[0x20..].iter().cloned()
+//                 .ci8_iter()
+//                 .take(width*height).collect(),
+
+//             BKTextureFormat::RGBA32 => bytes.iter().cloned()
+//                 .rgba32_iter()
+//                 .take(width*height).collect(),
+//             _ => Vec::new(),
+//         };
+
+//         let tmem : Vec<Vec<Pixel>> = tmem.chunks_exact(width).map(|row| row.to_vec()).collect();
+
+//         BKTexture{
+//             format: *format,
+//             palette,
+//             tmem,
+//         }
+//     }
+
+// }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_ci4_round_trip() {
+        let palette : Vec<u8> = (0..0x20).collect();
+        let bytes = [palette.as_slice(), &[0x01, 0x23, 0x45, 0x67, 0x89, 0xAB]].concat();
+        let ci4 = BKTexture::<CI4>::from_be_bytes(4, 3, &bytes).unwrap();
+
+        assert_eq!(ci4.width(), 4);
+        assert_eq!(ci4.height(), 3);
+        assert_eq!(ci4.tmem[2][3], CI4(0xB));
+        assert_eq!(ci4.to_be_bytes(), bytes);
+
+        //short palette or texels
+        assert!(BKTexture::<CI4>::from_be_bytes(4, 3, &bytes[..0x10]).is_err());
+        assert!(BKTexture::<CI4>::from_be_bytes(4, 3, &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn texture_convert() {
+        let bytes : Vec<u8> = (0..0x18).map(|i| i*11).collect();
+        let rgba16 = BKTexture::<RGBA16>::from_be_bytes(4, 3, &bytes).unwrap();
+        let back = rgba16.to_rgba32().convert::<RGBA16>();
+        assert_eq!(rgba16.tmem, back.tmem);
+
+        let ci4 = BKTexture::<CI4>::from_be_bytes(2, 1, &[vec![0xFF; 0x20], vec![0x01]].concat()).unwrap();
+        let ci8 = ci4.convert::<CI8>();
+        assert_eq!(ci8.palette, ci4.palette);
+        assert_eq!(ci8.tmem, vec![vec![CI8(0), CI8(0)]]);
+    }
+
+    #[test]
+    fn texture_header_round_trip() {
+        use BKTextureFormat::*;
+        for format in [CI4, CI8, I4, I8, IA4, IA8, IA16, RGBA16, RGBA32, Unknown(0x1000)] {
+            let header = BKTextureHeader{offset: 0x120, format, flags: 0x04, level_count: 2, width: 16, height: 8, padding: [0; 6]};
+            let bytes = header.to_be_bytes();
+            let read = BKTextureHeader::from_be_bytes(&bytes);
+            assert_eq!(read.format, format);
+            assert_eq!(read.to_be_bytes(), bytes);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use super::BKTexture;
+use super::super::pixels::*;
+use super::super::error::TextureSizeError;
+
+/* Palette editing
+    CI texels are left untouched, only the palette changes.
+    hue/saturation shifts keep each entry's alpha bit.
+*/
+
+impl<T: Pixel> BKTexture<T> {
+    //shorter palettes are padded with transparent black when written
+    pub fn replace_palette(&mut self, palette: Vec<RGBA16>)->Result<(), TextureSizeError>{
+        if T::PALETTE_SIZE == 0 || palette.len() > T::PALETTE_SIZE { return Err(TextureSizeError) }
+        self.palette = Some(palette);
+        Ok(())
+    }
+
+    //returns the number of palette entries changed
+    pub fn remap_colors(&mut self, map: &[(RGBA16, RGBA16)])->usize{
+        let palette = match self.palette.as_mut() {
+            Some(palette) => palette,
+            None => return 0,
+        };
+        let mut changed = 0;
+        for entry in palette.iter_mut() {
+            if let Some((_, to)) = map.iter().find(|(from, _)| from == entry) {
+                *entry = *to;
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    //hue in degrees, saturation as a factor
+    pub fn shift_palette_hsv(&mut self, hue: f32, saturation: f32){
+        if let Some(palette) = self.palette.as_mut() {
+            for entry in palette.iter_mut() {
+                *entry = shift_hsv(entry, hue, saturation);
+            }
+        }
+    }
+}
+
+pub fn shift_hsv(color: &RGBA16, hue: f32, saturation: f32)->RGBA16{
+    let [r, g, b] = [color.r, color.g, color.b].map(|c| c as f32 / 31.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let h = if delta == 0.0 { 0.0 }
+        else if max == r { 60.0*((g - b)/delta).rem_euclid(6.0) }
+        else if max == g { 60.0*((b - r)/delta + 2.0) }
+        else { 60.0*((r - g)/delta + 4.0) };
+    let s = if max == 0.0 { 0.0 } else { delta/max };
+
+    let h = (h + hue).rem_euclid(360.0);
+    let s = (s*saturation).clamp(0.0, 1.0);
+    let c = max*s;
+    let x = c*(1.0 - ((h/60.0).rem_euclid(2.0) - 1.0).abs());
+    let m = max - c;
+    let (r, g, b) = match (h/60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let to_5bit = |v: f32| ((v + m)*31.0).round().clamp(0.0, 31.0) as u8;
+    RGBA16{r: to_5bit(r), g: to_5bit(g), b: to_5bit(b), a: color.a}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_edits() {
+        let red = RGBA16{r: 0x1F, g: 0, b: 0, a: 1};
+        let blue = RGBA16{r: 0, g: 0, b: 0x1F, a: 1};
+        let mut ci4 = BKTexture::<CI4>{palette: Some(vec![red, red, blue]), tmem: vec![vec![CI4(0), CI4(2)]]};
+
+        assert_eq!(ci4.remap_colors(&[(red, blue)]), 2);
+        assert_eq!(ci4.palette, Some(vec![blue; 3]));
+
+        ci4.shift_palette_hsv(120.0, 1.0);
+        assert_eq!(ci4.palette.as_ref().unwrap()[0], red);
+        ci4.shift_palette_hsv(0.0, 0.0);
+        assert_eq!(ci4.palette.as_ref().unwrap()[0], RGBA16{r: 0x1F, g: 0x1F, b: 0x1F, a: 1});
+
+        assert!(ci4.replace_palette(vec![blue; 0x11]).is_err());
+        assert!(ci4.replace_palette(vec![blue; 0x10]).is_ok());
+        assert_eq!(ci4.tmem, vec![vec![CI4(0), CI4(2)]]);
+        assert!(BKTexture::<RGBA16>{palette: None, tmem: Vec::new()}.replace_palette(vec![blue]).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use std::error::Error;
+
+use super::BKTexture;
+use super::super::pixels::*;
+use super::super::error::FrameCountError;
+
+/* PNG / APNG import and export of RGBA32 textures
+    animated exports use one delay for every frame, derived from the framerate.
+    imported animation frames are composited with BlendOp::Source,
+    partial frames are drawn over the previous one.
+*/
+
+impl BKTexture<RGBA32> {
+    pub fn to_png_bytes(&self)->Result<Vec<u8>, Box<dyn Error>>{
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width() as u32, self.height() as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.to_be_bytes())?;
+        writer.finish()?;
+        Ok(out)
+    }
+
+    pub fn from_png_bytes(bytes: &[u8])->Result<Self, Box<dyn Error>>{
+        let mut apng = frames_from_apng(bytes)?;
+        Ok(apng.frames.remove(0))
+    }
+}
+
+pub fn frames_to_apng(frames: &[BKTexture<RGBA32>], framerate_fps: f32)->Result<Vec<u8>, Box<dyn Error>>{
+    let first = frames.first().ok_or(FrameCountError)?;
+    let delay_ms = if framerate_fps > 0.0 { (1000.0/framerate_fps).round() as u16 } else { 100 };
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, first.width() as u32, first.height() as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0)?;
+    encoder.set_frame_delay(delay_ms, 1000)?;
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        writer.write_image_data(&frame.to_be_bytes())?;
+    }
+    writer.finish()?;
+    Ok(out)
+}
+
+#[derive(Debug, Clone)]
+pub struct ApngFrames{
+    pub frames: Vec<BKTexture<RGBA32>>,
+    pub framerate_fps: f32, //taken from the first frame's delay
+}
+
+pub fn frames_from_apng(bytes: &[u8])->Result<ApngFrames, Box<dyn Error>>{
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+
+    let (width, height) = reader.info().size();
+    let (width, height) = (width as usize, height as usize);
+    //the count comes from the file, frames are only allocated as they are decoded
+    let frame_count = reader.info().animation_control().map_or(1, |ac| ac.num_frames as usize);
+    if frame_count == 0 { return Err(Box::new(FrameCountError)) }
+    let mut buffer = vec![0; reader.output_buffer_size()];
+
+    //IDAT without a fcTL is a fallback image that isn't part of the animation
+    if reader.info().animation_control().is_some() && reader.info().frame_control().is_none() {
+        reader.next_frame(&mut buffer)?;
+    }
+
+    let mut framerate_fps = 0.0;
+    let mut canvas = vec![vec![RGBA32{r: 0, g: 0, b: 0, a: 0}; width]; height];
+    let mut frames = Vec::new();
+    for i in 0..frame_count {
+        let output = reader.next_frame(&mut buffer)?;
+        let (x_offset, y_offset) = reader.info().frame_control()
+            .map_or((0, 0), |fc| (fc.x_offset as usize, fc.y_offset as usize));
+        if i == 0 {
+            framerate_fps = reader.info().frame_control()
+                .filter(|fc| fc.delay_num != 0)
+                .map_or(0.0, |fc| (if fc.delay_den == 0 {100} else {fc.delay_den}) as f32 / fc.delay_num as f32);
+        }
+
+        for (y, line) in buffer.chunks(output.line_size).take(output.height as usize).enumerate() {
+            for x in 0..output.width as usize {
+                let px = match output.color_type {
+                    png::ColorType::Grayscale => RGBA32{r: line[x], g: line[x], b: line[x], a: 0xFF},
+                    png::ColorType::GrayscaleAlpha => RGBA32{r: line[2*x], g: line[2*x], b: line[2*x], a: line[2*x + 1]},
+                    png::ColorType::Rgb => RGBA32{r: line[3*x], g: line[3*x + 1], b: line[3*x + 2], a: 0xFF},
+                    _ => RGBA32::from_be_bytes(line[4*x .. 4*x + 4].try_into()?),
+                };
+                if let Some(dst) = canvas.get_mut(y_offset + y).and_then(|row| row.get_mut(x_offset + x)) {
+                    *dst = px;
+                }
+            }
+        }
+        frames.push(BKTexture::<RGBA32>{palette: None, tmem: canvas.clone()});
+    }
+    Ok(ApngFrames{frames, framerate_fps})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_apng_round_trip() {
+        let frames : Vec<BKTexture<RGBA32>> = (0..3u8).map(|f| BKTexture::<RGBA32>{palette: None, tmem:
+            (0..2u8).map(|y| (0..3u8).map(|x| RGBA32{r: f, g: x, b: y, a: 0x80}).collect()).collect()
+        }).collect();
+
+        let apng = frames_to_apng(&frames, 20.0).unwrap();
+        let decoded = frames_from_apng(&apng).unwrap();
+        assert_eq!(decoded.framerate_fps, 20.0);
+        assert_eq!(decoded.frames.len(), 3);
+        for (a, b) in frames.iter().zip(decoded.frames.iter()) {
+            assert_eq!(a.tmem, b.tmem);
+        }
+
+        let png = frames[1].to_png_bytes().unwrap();
+        assert_eq!(BKTexture::<RGBA32>::from_png_bytes(&png).unwrap().tmem, frames[1].tmem);
+
+        //acTL num_frames rewritten, CRC included
+        let with_frame_count = |count: u32| {
+            let mut apng = apng.clone();
+            let chunk = apng.windows(4).position(|w| w == b"acTL").unwrap();
+            apng[chunk + 4 .. chunk + 8].copy_from_slice(&count.to_be_bytes());
+            let crc = !apng[chunk .. chunk + 12].iter().fold(!0u32, |crc, &b| {
+                (0..8).fold(crc ^ b as u32, |crc, _| if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 })
+            });
+            apng[chunk + 12 .. chunk + 16].copy_from_slice(&crc.to_be_bytes());
+            apng
+        };
+        assert!(frames_from_apng(&with_frame_count(0)).unwrap_err().is::<FrameCountError>());
+        assert!(BKTexture::<RGBA32>::from_png_bytes(&with_frame_count(0)).is_err());
+        assert!(frames_from_apng(&with_frame_count(u32::MAX)).is_err());
+    }
+}
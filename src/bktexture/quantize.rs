@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use super::BKTexture;
+use super::super::pixels::*;
+
+/* Palette quantization
+    - median-cut in RGBA16 space (5 bits per colour channel, 1 bit alpha)
+    - preserved palette entries keep their index, free slots are generated
+    - optional reserved index that every transparent pixel maps to
+*/
+
+#[derive(Debug, Clone, Default)]
+pub struct QuantizeOptions{
+    pub preserve_palette: Option<Vec<RGBA16>>,
+    pub transparent_index: Option<usize>,
+}
+
+impl BKTexture<RGBA32> {
+    pub fn quantize_ci4(&self, options: &QuantizeOptions)->BKTexture<CI4>{
+        let (palette, indices) = quantize(&self.tmem.concat(), 0x10, options);
+        BKTexture::<CI4>{
+            palette: Some(palette),
+            tmem: to_rows(indices.into_iter().map(CI4), self.width()),
+        }
+    }
+
+    pub fn quantize_ci8(&self, options: &QuantizeOptions)->BKTexture<CI8>{
+        let (palette, indices) = quantize(&self.tmem.concat(), 0x100, options);
+        BKTexture::<CI8>{
+            palette: Some(palette),
+            tmem: to_rows(indices.into_iter().map(CI8), self.width()),
+        }
+    }
+}
+
+fn to_rows<T: Clone>(pixels: impl Iterator<Item = T>, width: usize)->Vec<Vec<T>>{
+    if width == 0 { return Vec::new() }
+    pixels.collect::<Vec<_>>()
+        .chunks_exact(width)
+        .map(|row| row.to_vec())
+        .collect()
+}
+
+pub(crate) fn quantize(pixels: &[RGBA32], palette_size: usize, options: &QuantizeOptions)->(Vec<RGBA16>, Vec<usize>){
+    let colors : Vec<RGBA16> = pixels.iter().map(RGBA16::from).collect();
+    let transparent = options.transparent_index.filter(|&i| i < palette_size);
+
+    let mut slots : Vec<Option<RGBA16>> = vec![None; palette_size];
+    if let Some(preserved) = &options.preserve_palette {
+        for (slot, color) in slots.iter_mut().zip(preserved.iter()) {
+            *slot = Some(*color);
+        }
+    }
+    if let Some(i) = transparent {
+        slots[i] = Some(RGBA16{r: 0, g: 0, b: 0, a: 0});
+    }
+
+    //only colours not already covered by a fixed slot compete for the free ones
+    let mut histogram : HashMap<(u8, u8, u8, u8), usize> = HashMap::new();
+    for color in colors.iter() {
+        if transparent.is_some() && color.a == 0 { continue }
+        if slots.iter().flatten().any(|fixed| fixed == color) { continue }
+        *histogram.entry((color.r, color.g, color.b, color.a)).or_insert(0) += 1;
+    }
+    let mut histogram : Vec<(RGBA16, usize)> = histogram.into_iter()
+        .map(|((r, g, b, a), n)| (RGBA16{r, g, b, a}, n))
+        .collect();
+    histogram.sort_by_key(|(c, _)| (c.r, c.g, c.b, c.a)); //deterministic output
+
+    let free = slots.iter().filter(|slot| slot.is_none()).count();
+    let mut generated = median_cut(histogram, free).into_iter();
+    let palette : Vec<RGBA16> = slots.into_iter()
+        .map(|slot| slot.or_else(|| generated.next()).unwrap_or(RGBA16{r: 0, g: 0, b: 0, a: 0}))
+        .collect();
+
+    let indices = colors.iter()
+        .map(|color| match transparent {
+            Some(i) if color.a == 0 => i,
+            _ => nearest_palette_index(&palette, color, transparent),
+        })
+        .collect();
+    (palette, indices)
+}
+
+fn channels(color: &RGBA16)->[u32; 4]{
+    [color.r as u32, color.g as u32, color.b as u32, (color.a as u32) * 0x1F]
+}
+
+fn median_cut(colors: Vec<(RGBA16, usize)>, count: usize)->Vec<RGBA16>{
+    if colors.is_empty() || count == 0 { return Vec::new() }
+
+    let mut boxes = vec![colors];
+    while boxes.len() < count {
+        let widest = boxes.iter().enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| (i, widest_channel(b)))
+            .max_by_key(|&(_, (_, range))| range);
+        let (i, (channel, _)) = match widest {
+            Some(w) => w,
+            None => break,
+        };
+
+        let mut lower = boxes.swap_remove(i);
+        lower.sort_by_key(|(c, _)| channels(c)[channel]);
+        let total : usize = lower.iter().map(|(_, n)| n).sum();
+        let mut acc = 0;
+        let split = lower.iter()
+            .position(|(_, n)| { acc += n; 2*acc >= total })
+            .map_or(1, |i| i + 1)
+            .clamp(1, lower.len() - 1);
+        let upper = lower.split_off(split);
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|b| average(b)).collect()
+}
+
+fn widest_channel(colors: &[(RGBA16, usize)])->(usize, u32){
+    (0..4).map(|ch| {
+            let values = colors.iter().map(|(c, _)| channels(c)[ch]);
+            (ch, values.clone().max().unwrap_or(0) - values.min().unwrap_or(0))
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or((0, 0))
+}
+
+fn average(colors: &[(RGBA16, usize)])->RGBA16{
+    let total = colors.iter().map(|(_, n)| *n as u32).sum::<u32>().max(1);
+    let mean = |ch: usize| (colors.iter().map(|(c, n)| channels(c)[ch] * (*n as u32)).sum::<u32>() + total/2) / total;
+    RGBA16{
+        r: mean(0) as u8,
+        g: mean(1) as u8,
+        b: mean(2) as u8,
+        a: if mean(3) >= 0x10 {1} else {0},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba32(r: u8, g: u8, b: u8, a: u8)->RGBA32{ RGBA32{r, g, b, a} }
+
+    #[test]
+    fn quantize_exact_when_palette_fits() {
+        let texture = BKTexture::<RGBA32>{ palette: None, tmem: vec![
+            vec![rgba32(0xFF, 0, 0, 0xFF), rgba32(0, 0xFF, 0, 0xFF)],
+            vec![rgba32(0, 0, 0xFF, 0xFF), rgba32(0xFF, 0, 0, 0xFF)],
+        ]};
+        let ci4 = texture.quantize_ci4(&QuantizeOptions::default());
+        let palette = ci4.palette.as_ref().unwrap();
+
+        assert_eq!(palette.len(), 0x10);
+        for (row_in, row_out) in texture.tmem.iter().zip(ci4.tmem.iter()) {
+            for (px_in, CI4(i)) in row_in.iter().zip(row_out.iter()) {
+                assert_eq!(RGBA16::from(px_in), palette[*i]);
+            }
+        }
+        assert_eq!(ci4.tmem[0][0], ci4.tmem[1][1]);
+    }
+
+    #[test]
+    fn quantize_reserves_transparent_and_preserved() {
+        let texture = BKTexture::<RGBA32>{ palette: None, tmem: vec![
+            vec![rgba32(0x80, 0x80, 0x80, 0), rgba32(0xFF, 0xFF, 0xFF, 0xFF), rgba32(0x10, 0x20, 0x30, 0xFF)],
+        ]};
+        let white = RGBA16{r: 0x1F, g: 0x1F, b: 0x1F, a: 1};
+        let options = QuantizeOptions{
+            preserve_palette: Some(vec![white]),
+            transparent_index: Some(0xFF),
+        };
+        let ci8 = texture.quantize_ci8(&options);
+        let palette = ci8.palette.as_ref().unwrap();
+
+        assert_eq!(palette[0], white);
+        assert_eq!(palette[0xFF], RGBA16{r: 0, g: 0, b: 0, a: 0});
+        assert_eq!(ci8.tmem[0][0], CI8(0xFF));
+        assert_eq!(ci8.tmem[0][1], CI8(0));
+        assert_eq!(palette[ci8.tmem[0][2].0], RGBA16::from(&texture.tmem[0][2]));
+    }
+}
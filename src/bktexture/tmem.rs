@@ -0,0 +1,80 @@
+use super::BKTexture;
+use super::super::pixels::*;
+use super::super::error::TextureDataError;
+
+/* TMEM odd row interleaving
+    textures loaded with LoadBlock are stored with the words of every odd row
+    swapped so the RDP's bank interleaving reads them back in order.
+    32 bit textures swap 8 byte pairs, all other sizes swap 4 byte pairs.
+    the swizzle is its own inverse.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TmemLayout{
+    Linear,
+    OddRowSwapped,
+}
+
+pub fn swap_odd_rows(bytes: &mut [u8], width: usize, bits_per_pixel: usize){
+    let line_size = width*bits_per_pixel/8;
+    //rows that don't fill whole TMEM words can't be LoadBlock'ed
+    if line_size == 0 || line_size & 7 != 0 { return }
+    let word_size = if bits_per_pixel == 32 {8} else {4};
+
+    for row in bytes.chunks_mut(line_size).skip(1).step_by(2) {
+        for pair in row.chunks_exact_mut(2*word_size) {
+            let (lower, upper) = pair.split_at_mut(word_size);
+            lower.swap_with_slice(upper);
+        }
+    }
+}
+
+impl<T: Pixel> BKTexture<T> {
+    pub fn from_be_bytes_with_layout(width: usize, height: usize, bytes: &[u8], layout: TmemLayout)->Result<Self, TextureDataError>{
+        match layout {
+            TmemLayout::Linear => Self::from_be_bytes(width, height, bytes),
+            TmemLayout::OddRowSwapped => {
+                let mut bytes = bytes.to_vec();
+                let texel_start = (2*T::PALETTE_SIZE).min(bytes.len());
+                swap_odd_rows(&mut bytes[texel_start..], width, T::BITS_PER_PIXEL);
+                Self::from_be_bytes(width, height, &bytes)
+            },
+        }
+    }
+
+    pub fn to_be_bytes_with_layout(&self, layout: TmemLayout)->Vec<u8>{
+        let mut bytes = self.to_be_bytes();
+        if layout == TmemLayout::OddRowSwapped {
+            swap_odd_rows(&mut bytes[2*T::PALETTE_SIZE..], self.width(), T::BITS_PER_PIXEL);
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tmem_swaps_odd_rows() {
+        //4x3 RGBA16, 8 bytes per row
+        let bytes : Vec<u8> = (0..24).collect();
+        let mut swapped = bytes.clone();
+        swap_odd_rows(&mut swapped, 4, 16);
+        assert_eq!(swapped[..8], bytes[..8]);
+        assert_eq!(swapped[8..16], [12, 13, 14, 15, 8, 9, 10, 11]);
+        assert_eq!(swapped[16..], bytes[16..]);
+
+        let texture = BKTexture::<RGBA16>::from_be_bytes_with_layout(4, 3, &swapped, TmemLayout::OddRowSwapped).unwrap();
+        assert_eq!(texture.tmem, BKTexture::<RGBA16>::from_be_bytes(4, 3, &bytes).unwrap().tmem);
+        assert_eq!(texture.to_be_bytes_with_layout(TmemLayout::OddRowSwapped), swapped);
+    }
+
+    #[test]
+    fn tmem_swaps_32bit_by_dword() {
+        //4x2 RGBA32, 16 bytes per row
+        let mut bytes : Vec<u8> = (0..32).collect();
+        swap_odd_rows(&mut bytes, 4, 32);
+        assert_eq!(bytes[16..], (24..32).chain(16..24).collect::<Vec<u8>>());
+    }
+}
@@ -10,4 +10,182 @@ impl fmt::Display for TryFromBEBytesError {
     }
 }
 
-impl Error for TryFromBEBytesError {}
\ No newline at end of file
+impl Error for TryFromBEBytesError {}
+
+#[derive(Debug)]
+pub struct TextureSizeError;
+
+impl fmt::Display for TextureSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Texture does not fit in the space available")
+    }
+}
+
+impl Error for TextureSizeError {}
+
+#[derive(Debug)]
+pub struct TextureDataError;
+
+impl fmt::Display for TextureDataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Texture data is shorter than its size and format need")
+    }
+}
+
+impl Error for TextureDataError {}
+
+#[derive(Debug)]
+pub struct FrameCountError;
+
+impl fmt::Display for FrameCountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Frame count does not match the animated texture")
+    }
+}
+
+impl Error for FrameCountError {}
+
+#[derive(Debug)]
+pub struct FrameSizeError;
+
+impl fmt::Display for FrameSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Animated texture frame size is not positive or too small for a frame")
+    }
+}
+
+impl Error for FrameSizeError {}
+
+#[derive(Debug)]
+pub struct TextureIndexError;
+
+impl fmt::Display for TextureIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Texture index is not in the texture list")
+    }
+}
+
+impl Error for TextureIndexError {}
+
+#[derive(Debug)]
+pub struct TextureFormatError;
+
+impl fmt::Display for TextureFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Texture format can not be encoded or decoded")
+    }
+}
+
+impl Error for TextureFormatError {}
+
+#[derive(Debug)]
+pub struct TextureDimensionsError;
+
+impl fmt::Display for TextureDimensionsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Image dimensions do not match the texture header")
+    }
+}
+
+impl Error for TextureDimensionsError {}
+
+#[derive(Debug)]
+pub struct AnimatedTextureSlotError;
+
+impl fmt::Display for AnimatedTextureSlotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Animated texture slot is empty or no texture is drawn through it")
+    }
+}
+
+impl Error for AnimatedTextureSlotError {}
+
+#[derive(Debug)]
+pub struct ModelNameError;
+
+impl fmt::Display for ModelNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No model with that name in the asset folder")
+    }
+}
+
+impl Error for ModelNameError {}
+
+#[derive(Debug)]
+pub struct VertexCountError;
+
+impl fmt::Display for VertexCountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Model has more vertices than a vertex list can hold")
+    }
+}
+
+impl Error for VertexCountError {}
+
+#[derive(Debug)]
+pub struct VertexRangeError;
+
+impl fmt::Display for VertexRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Vertex position or texture coordinate does not fit in 16 bits")
+    }
+}
+
+impl Error for VertexRangeError {}
+
+#[derive(Debug)]
+pub struct VertexIndexError;
+
+impl fmt::Display for VertexIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Vertex index is outside the model's vertex list")
+    }
+}
+
+impl Error for VertexIndexError {}
+
+#[derive(Debug)]
+pub struct VertexInUseError;
+
+impl fmt::Display for VertexInUseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Vertex is still used by the collision, mesh or unk_28 list")
+    }
+}
+
+impl Error for VertexInUseError {}
+
+#[derive(Debug)]
+pub struct GltfFormatError;
+
+impl fmt::Display for GltfFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "glTF file is malformed or uses an unsupported feature")
+    }
+}
+
+impl Error for GltfFormatError {}
+
+#[derive(Debug)]
+pub struct GltfFloatError;
+
+impl fmt::Display for GltfFloatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Model has a NaN or infinite value, glTF only stores finite numbers")
+    }
+}
+
+impl Error for GltfFloatError {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GbiSyntaxError{
+    pub line: usize, //1 based
+}
+
+impl fmt::Display for GbiSyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not assemble display list text at line {}", self.line)
+    }
+}
+
+impl Error for GbiSyntaxError {}
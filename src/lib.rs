@@ -4,6 +4,12 @@ pub mod bkmodel;
 pub mod bktexture;
 pub mod pixels;
 pub mod error;
+pub mod texture_report;
+
+use std::fs;
+use std::path::Path;
+
+use bkmodel::BKModel;
 
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum ImgFmt{
@@ -31,6 +37,37 @@ pub enum AssetType{
     Sprite(ImgFmt),
 }
 
+#[derive(Default)]
 pub struct AssetFolder{
+    pub models: Vec<(String, BKModel)>,
+}
+
+impl AssetFolder{
+    pub fn new()->AssetFolder{
+        AssetFolder::default()
+    }
+
+    pub fn add_model(&mut self, name: &str, model: BKModel){
+        self.models.push((name.to_string(), model));
+    }
+
+    //loads every file in `dir` that parses as a model, sorted by file name
+    pub fn read_models(dir: &Path)->std::io::Result<AssetFolder>{
+        let mut paths : Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
 
+        let mut folder = AssetFolder::new();
+        for path in paths {
+            let bytes = fs::read(&path)?;
+            if bytes.len() < 0x38 { continue }
+            if let Some(model) = BKModel::try_from_be_bytes(&bytes) {
+                let name = path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+                folder.add_model(&name, model);
+            }
+        }
+        Ok(folder)
+    }
 }
\ No newline at end of file
@@ -1,7 +1,14 @@
 /* base type */
 #[derive(PartialEq, Debug, Clone, Copy)]
-pub struct CI4(usize);
+pub struct CI4(pub usize);
 
+impl CI4{
+    pub fn to_bits(&self)->u8{
+        (self.0 & 0x0F) as u8
+    }
+}
+
+/* ADAPTOR ITERATOR */
 pub struct CI4Adaptor<I>{
     value: Option<u8>,
     iter: I,
@@ -38,6 +45,38 @@ pub trait CI4Iterator: Iterator<Item = u8> + Sized{
 
 impl<I: Iterator<Item = u8>> CI4Iterator for I {}
 
+/* PACKING ITERATOR */
+pub struct CI4Packer<I>{
+    iter: I,
+}
+
+impl<I> Iterator for CI4Packer<I>
+where
+    I: Iterator<Item = CI4>
+{
+    type Item = u8;
+    fn next(&mut self) -> Option<Self::Item> {
+        let upper = self.iter.next()?.to_bits();
+        let lower = self.iter.next().map_or(0, |px| px.to_bits());
+        Some((upper << 4) | lower)
+    }
+}
+
+impl<I> CI4Packer<I>{
+    pub fn new(iter: I)->Self{
+        Self{iter}
+    }
+}
+
+pub trait CI4PackIterator: Iterator<Item = CI4> + Sized{
+    fn pack_ci4(self) -> CI4Packer<Self> {
+        CI4Packer::new(self)
+    }
+}
+
+impl<I: Iterator<Item = CI4>> CI4PackIterator for I {}
+
+/* TESTS */
 #[cfg(test)]
 mod tests {
     use super::*;
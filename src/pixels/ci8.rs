@@ -1,6 +1,14 @@
+/* base type */
 #[derive(PartialEq, Debug, Clone, Copy)]
-pub struct CI8(usize);
+pub struct CI8(pub usize);
 
+impl CI8{
+    pub fn to_be_bytes(&self)->[u8; 1]{
+        [self.0 as u8]
+    }
+}
+
+/* ADAPTOR ITERATOR */
 pub struct CI8Adaptor<I>{
     iter: I,
 }
@@ -29,6 +37,36 @@ pub trait CI8Iterator: Iterator<Item = u8> + Sized{
 
 impl<I: Iterator<Item = u8>> CI8Iterator for I {}
 
+/* PACKING ITERATOR */
+pub struct CI8Packer<I>{
+    iter: I,
+}
+
+impl<I> Iterator for CI8Packer<I>
+where
+    I: Iterator<Item = CI8>
+{
+    type Item = u8;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.iter.next()?.to_be_bytes()[0])
+    }
+}
+
+impl<I> CI8Packer<I>{
+    pub fn new(iter: I)->Self{
+        Self{iter}
+    }
+}
+
+pub trait CI8PackIterator: Iterator<Item = CI8> + Sized{
+    fn pack_ci8(self) -> CI8Packer<Self> {
+        CI8Packer::new(self)
+    }
+}
+
+impl<I: Iterator<Item = CI8>> CI8PackIterator for I {}
+
+/* TESTS */
 #[cfg(test)]
 mod tests {
     use super::*;
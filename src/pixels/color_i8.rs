@@ -4,6 +4,12 @@ pub struct I8{
     pub i: u8,
 }
 
+impl I8{
+    pub fn to_be_bytes(&self)->[u8; 1]{
+        [self.i]
+    }
+}
+
 /* ADAPTOR ITERATOR */
 pub struct I8Adaptor<I>{
     iter: I,
@@ -35,6 +41,35 @@ pub trait I8Iterator: Iterator<Item = u8> + Sized{
 
 impl<I: Iterator<Item = u8>> I8Iterator for I {}
 
+/* PACKING ITERATOR */
+pub struct I8Packer<I>{
+    iter: I,
+}
+
+impl<I> Iterator for I8Packer<I>
+where
+    I: Iterator<Item = I8>
+{
+    type Item = u8;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.iter.next()?.to_be_bytes()[0])
+    }
+}
+
+impl<I> I8Packer<I>{
+    pub fn new(iter: I)->Self{
+        Self{iter}
+    }
+}
+
+pub trait I8PackIterator: Iterator<Item = I8> + Sized{
+    fn pack_i8(self) -> I8Packer<Self> {
+        I8Packer::new(self)
+    }
+}
+
+impl<I: Iterator<Item = I8>> I8PackIterator for I {}
+
 /* TESTS */
 #[cfg(test)]
 mod tests {
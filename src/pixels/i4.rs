@@ -4,6 +4,12 @@ pub struct I4{
     pub i: u8,
 }
 
+impl I4{
+    pub fn to_bits(&self)->u8{
+        self.i & 0x0F
+    }
+}
+
 /* ADAPTOR ITERATOR */
 pub struct I4Adaptor<I>{
     buffer: Option<u8>,
@@ -41,6 +47,37 @@ pub trait I4Iterator: Iterator<Item = u8> + Sized{
 
 impl<I: Iterator<Item = u8>> I4Iterator for I {}
 
+/* PACKING ITERATOR */
+pub struct I4Packer<I>{
+    iter: I,
+}
+
+impl<I> Iterator for I4Packer<I>
+where
+    I: Iterator<Item = I4>
+{
+    type Item = u8;
+    fn next(&mut self) -> Option<Self::Item> {
+        let upper = self.iter.next()?.to_bits();
+        let lower = self.iter.next().map_or(0, |px| px.to_bits());
+        Some((upper << 4) | lower)
+    }
+}
+
+impl<I> I4Packer<I>{
+    pub fn new(iter: I)->Self{
+        Self{iter}
+    }
+}
+
+pub trait I4PackIterator: Iterator<Item = I4> + Sized{
+    fn pack_i4(self) -> I4Packer<Self> {
+        I4Packer::new(self)
+    }
+}
+
+impl<I: Iterator<Item = I4>> I4PackIterator for I {}
+
 /* TESTS */
 #[cfg(test)]
 mod tests {
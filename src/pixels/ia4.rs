@@ -5,6 +5,12 @@ pub struct IA4{
     pub a: u8
 }
 
+impl IA4{
+    pub fn to_bits(&self)->u8{
+        ((self.i & 0x7) << 1) | (self.a & 1)
+    }
+}
+
 /* ADAPTOR ITERATOR */
 pub struct IA4Adaptor<I>{
     buffer: Option<u8>,
@@ -43,6 +49,37 @@ pub trait IA4Iterator: Iterator<Item = u8> + Sized{
 
 impl<I: Iterator<Item = u8>> IA4Iterator for I {}
 
+/* PACKING ITERATOR */
+pub struct IA4Packer<I>{
+    iter: I,
+}
+
+impl<I> Iterator for IA4Packer<I>
+where
+    I: Iterator<Item = IA4>
+{
+    type Item = u8;
+    fn next(&mut self) -> Option<Self::Item> {
+        let upper = self.iter.next()?.to_bits();
+        let lower = self.iter.next().map_or(0, |px| px.to_bits());
+        Some((upper << 4) | lower)
+    }
+}
+
+impl<I> IA4Packer<I>{
+    pub fn new(iter: I)->Self{
+        Self{iter}
+    }
+}
+
+pub trait IA4PackIterator: Iterator<Item = IA4> + Sized{
+    fn pack_ia4(self) -> IA4Packer<Self> {
+        IA4Packer::new(self)
+    }
+}
+
+impl<I: Iterator<Item = IA4>> IA4PackIterator for I {}
+
 /* TESTS */
 #[cfg(test)]
 mod tests {
@@ -5,6 +5,12 @@ pub struct IA8{
     pub a: u8
 }
 
+impl IA8{
+    pub fn to_be_bytes(&self)->[u8; 1]{
+        [((self.i & 0x0F) << 4) | (self.a & 0x0F)]
+    }
+}
+
 /* ADAPTOR ITERATOR */
 pub struct IA8Adaptor<I>{
     iter: I,
@@ -38,6 +44,35 @@ pub trait IA8Iterator: Iterator<Item = u8> + Sized{
 
 impl<I: Iterator<Item = u8>> IA8Iterator for I {}
 
+/* PACKING ITERATOR */
+pub struct IA8Packer<I>{
+    iter: I,
+}
+
+impl<I> Iterator for IA8Packer<I>
+where
+    I: Iterator<Item = IA8>
+{
+    type Item = u8;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.iter.next()?.to_be_bytes()[0])
+    }
+}
+
+impl<I> IA8Packer<I>{
+    pub fn new(iter: I)->Self{
+        Self{iter}
+    }
+}
+
+pub trait IA8PackIterator: Iterator<Item = IA8> + Sized{
+    fn pack_ia8(self) -> IA8Packer<Self> {
+        IA8Packer::new(self)
+    }
+}
+
+impl<I: Iterator<Item = IA8>> IA8PackIterator for I {}
+
 /* TESTS */
 #[cfg(test)]
 mod tests {
@@ -7,6 +7,7 @@ pub mod color_i8;
 pub mod ia4;
 pub mod ia8;
 pub mod ia16;
+pub mod pixel;
 
 pub use rgba32::*;
 pub use rgba16::*;
@@ -17,3 +18,38 @@ pub use color_i8::*;
 pub use ia4::*;
 pub use ia8::*;
 pub use ia16::*;
+pub use pixel::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //packs and unpacks the pixels, returns the packed size
+    fn round_trip<P, E, D>(pixels: Vec<P>, pack: E, unpack: D)->usize
+    where
+        P: PartialEq + std::fmt::Debug + Clone,
+        E: Fn(Vec<P>)->Vec<u8>,
+        D: Fn(Vec<u8>)->Vec<P>,
+    {
+        let bytes = pack(pixels.clone());
+        let decoded : Vec<_> = unpack(bytes.clone()).into_iter().take(pixels.len()).collect();
+        assert_eq!(pixels, decoded);
+        bytes.len()
+    }
+
+    #[test]
+    fn packing_round_trip_odd_width() {
+        //3x3 textures for 4 bit formats (rows are not byte aligned), 5x3 for 8 bit
+        let cases = [
+            ("I4", 5, round_trip((0..9u8).map(|i| I4{i: i + 6}).collect(), |p| p.into_iter().pack_i4().collect(), |b| b.into_iter().i4_iter().collect())),
+            ("IA4", 5, round_trip((0..9u8).map(|i| IA4{i: i & 0x7, a: i & 1}).collect(), |p| p.into_iter().pack_ia4().collect(), |b| b.into_iter().ia4_iter().collect())),
+            ("CI4", 5, round_trip((0..9usize).map(|i| CI4(15 - i)).collect(), |p| p.into_iter().pack_ci4().collect(), |b| b.into_iter().ci4_iter().collect())),
+            ("I8", 15, round_trip((0..15u8).map(|i| I8{i: i * 17}).collect(), |p| p.into_iter().pack_i8().collect(), |b| b.into_iter().i8_iter().collect())),
+            ("IA8", 15, round_trip((0..15u8).map(|i| IA8{i, a: 15 - i}).collect(), |p| p.into_iter().pack_ia8().collect(), |b| b.into_iter().ia8_iter().collect())),
+            ("CI8", 15, round_trip((0..15usize).map(|i| CI8(0xF0 + i)).collect(), |p| p.into_iter().pack_ci8().collect(), |b| b.into_iter().ci8_iter().collect())),
+        ];
+        for (format, expected, size) in cases {
+            assert_eq!(size, expected, "{} packed size", format);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use super::*;
+
+/* common interface of every pixel format
+    - packed size and palette size (CI formats only)
+    - decode from / encode to big-endian bytes
+    - conversion through RGBA32, CI formats resolve through their palette
+*/
+pub trait Pixel: Sized + Clone {
+    const BITS_PER_PIXEL: usize;
+    const PALETTE_SIZE: usize = 0;
+
+    fn decode_iter<I: Iterator<Item = u8>>(iter: I) -> impl Iterator<Item = Self>;
+    fn encode_iter<I: Iterator<Item = Self>>(iter: I) -> impl Iterator<Item = u8>;
+
+    fn to_rgba32(&self, palette: &[RGBA16]) -> RGBA32;
+    fn from_rgba32(rgba32: &RGBA32, palette: &[RGBA16]) -> Self;
+}
+
+fn intensity(rgba32: &RGBA32)->u32{
+    (rgba32.r as u32 + rgba32.g as u32 + rgba32.b as u32 + 1)/3
+}
+
+//rounds an 8 bit value down to `bits`
+fn scale(value: u32, bits: u32)->u8{
+    let max = (1 << bits) - 1;
+    ((value * max + 0x7F) / 0xFF) as u8
+}
+
+pub(crate) fn nearest_palette_index(palette: &[RGBA16], color: &RGBA16, skip: Option<usize>)->usize{
+    let channels = |c: &RGBA16| [c.r as u32, c.g as u32, c.b as u32, (c.a as u32) * 0x1F];
+    palette.iter().enumerate()
+        .filter(|(i, _)| Some(*i) != skip)
+        .min_by_key(|(_, entry)| channels(entry).iter().zip(channels(color).iter())
+            .map(|(&x, &y)| x.abs_diff(y).pow(2))
+            .sum::<u32>()
+        )
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+fn palette_color(palette: &[RGBA16], index: usize)->RGBA32{
+    palette.get(index)
+        .map(RGBA32::from)
+        .unwrap_or(RGBA32{r: 0, g: 0, b: 0, a: 0})
+}
+
+impl Pixel for CI4 {
+    const BITS_PER_PIXEL: usize = 4;
+    const PALETTE_SIZE: usize = 0x10;
+    fn decode_iter<I: Iterator<Item = u8>>(iter: I) -> impl Iterator<Item = Self> { iter.ci4_iter() }
+    fn encode_iter<I: Iterator<Item = Self>>(iter: I) -> impl Iterator<Item = u8> { iter.pack_ci4() }
+    fn to_rgba32(&self, palette: &[RGBA16]) -> RGBA32 { palette_color(palette, self.0) }
+    fn from_rgba32(rgba32: &RGBA32, palette: &[RGBA16]) -> Self {
+        CI4(nearest_palette_index(&palette[..palette.len().min(0x10)], &RGBA16::from(rgba32), None))
+    }
+}
+
+impl Pixel for CI8 {
+    const BITS_PER_PIXEL: usize = 8;
+    const PALETTE_SIZE: usize = 0x100;
+    fn decode_iter<I: Iterator<Item = u8>>(iter: I) -> impl Iterator<Item = Self> { iter.ci8_iter() }
+    fn encode_iter<I: Iterator<Item = Self>>(iter: I) -> impl Iterator<Item = u8> { iter.pack_ci8() }
+    fn to_rgba32(&self, palette: &[RGBA16]) -> RGBA32 { palette_color(palette, self.0) }
+    fn from_rgba32(rgba32: &RGBA32, palette: &[RGBA16]) -> Self {
+        CI8(nearest_palette_index(&palette[..palette.len().min(0x100)], &RGBA16::from(rgba32), None))
+    }
+}
+
+impl Pixel for I4 {
+    const BITS_PER_PIXEL: usize = 4;
+    fn decode_iter<I: Iterator<Item = u8>>(iter: I) -> impl Iterator<Item = Self> { iter.i4_iter() }
+    fn encode_iter<I: Iterator<Item = Self>>(iter: I) -> impl Iterator<Item = u8> { iter.pack_i4() }
+    fn to_rgba32(&self, _palette: &[RGBA16]) -> RGBA32 { RGBA32::from(self) }
+    fn from_rgba32(rgba32: &RGBA32, _palette: &[RGBA16]) -> Self {
+        I4{i: scale(intensity(rgba32), 4)}
+    }
+}
+
+impl Pixel for I8 {
+    const BITS_PER_PIXEL: usize = 8;
+    fn decode_iter<I: Iterator<Item = u8>>(iter: I) -> impl Iterator<Item = Self> { iter.i8_iter() }
+    fn encode_iter<I: Iterator<Item = Self>>(iter: I) -> impl Iterator<Item = u8> { iter.pack_i8() }
+    fn to_rgba32(&self, _palette: &[RGBA16]) -> RGBA32 { RGBA32::from(self) }
+    fn from_rgba32(rgba32: &RGBA32, _palette: &[RGBA16]) -> Self {
+        I8{i: intensity(rgba32) as u8}
+    }
+}
+
+impl Pixel for IA4 {
+    const BITS_PER_PIXEL: usize = 4;
+    fn decode_iter<I: Iterator<Item = u8>>(iter: I) -> impl Iterator<Item = Self> { iter.ia4_iter() }
+    fn encode_iter<I: Iterator<Item = Self>>(iter: I) -> impl Iterator<Item = u8> { iter.pack_ia4() }
+    fn to_rgba32(&self, _palette: &[RGBA16]) -> RGBA32 { RGBA32::from(self) }
+    fn from_rgba32(rgba32: &RGBA32, _palette: &[RGBA16]) -> Self {
+        IA4{i: scale(intensity(rgba32), 3), a: scale(rgba32.a as u32, 1)}
+    }
+}
+
+impl Pixel for IA8 {
+    const BITS_PER_PIXEL: usize = 8;
+    fn decode_iter<I: Iterator<Item = u8>>(iter: I) -> impl Iterator<Item = Self> { iter.ia8_iter() }
+    fn encode_iter<I: Iterator<Item = Self>>(iter: I) -> impl Iterator<Item = u8> { iter.pack_ia8() }
+    fn to_rgba32(&self, _palette: &[RGBA16]) -> RGBA32 { RGBA32::from(self) }
+    fn from_rgba32(rgba32: &RGBA32, _palette: &[RGBA16]) -> Self {
+        IA8{i: scale(intensity(rgba32), 4), a: scale(rgba32.a as u32, 4)}
+    }
+}
+
+impl Pixel for IA16 {
+    const BITS_PER_PIXEL: usize = 16;
+    fn decode_iter<I: Iterator<Item = u8>>(iter: I) -> impl Iterator<Item = Self> { iter.ia16_iter() }
+    fn encode_iter<I: Iterator<Item = Self>>(iter: I) -> impl Iterator<Item = u8> { iter.flat_map(|px| px.to_be_bytes()) }
+    fn to_rgba32(&self, _palette: &[RGBA16]) -> RGBA32 { RGBA32::from(self) }
+    fn from_rgba32(rgba32: &RGBA32, _palette: &[RGBA16]) -> Self { IA16::from(rgba32) }
+}
+
+impl Pixel for RGBA16 {
+    const BITS_PER_PIXEL: usize = 16;
+    fn decode_iter<I: Iterator<Item = u8>>(iter: I) -> impl Iterator<Item = Self> { iter.rgba16_iter() }
+    fn encode_iter<I: Iterator<Item = Self>>(iter: I) -> impl Iterator<Item = u8> { iter.flat_map(|px| px.to_be_bytes()) }
+    fn to_rgba32(&self, _palette: &[RGBA16]) -> RGBA32 { RGBA32::from(self) }
+    fn from_rgba32(rgba32: &RGBA32, _palette: &[RGBA16]) -> Self { RGBA16::from(rgba32) }
+}
+
+impl Pixel for RGBA32 {
+    const BITS_PER_PIXEL: usize = 32;
+    fn decode_iter<I: Iterator<Item = u8>>(iter: I) -> impl Iterator<Item = Self> { iter.rgba32_iter() }
+    fn encode_iter<I: Iterator<Item = Self>>(iter: I) -> impl Iterator<Item = u8> { iter.flat_map(|px| px.to_be_bytes()) }
+    fn to_rgba32(&self, _palette: &[RGBA16]) -> RGBA32 { self.clone() }
+    fn from_rgba32(rgba32: &RGBA32, _palette: &[RGBA16]) -> Self { rgba32.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: Pixel + PartialEq + std::fmt::Debug>(bytes: &[u8]) {
+        let pixels : Vec<T> = T::decode_iter(bytes.iter().cloned()).collect();
+        assert_eq!(pixels.len(), 8*bytes.len()/T::BITS_PER_PIXEL);
+        let out : Vec<u8> = T::encode_iter(pixels.into_iter()).collect();
+        assert_eq!(bytes, out.as_slice());
+    }
+
+    #[test]
+    fn pixel_round_trip() {
+        let bytes = [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF1];
+        round_trip::<CI4>(&bytes);
+        round_trip::<CI8>(&bytes);
+        round_trip::<I4>(&bytes);
+        round_trip::<I8>(&bytes);
+        round_trip::<IA4>(&bytes);
+        round_trip::<IA8>(&bytes);
+        round_trip::<IA16>(&bytes);
+        round_trip::<RGBA16>(&bytes);
+        round_trip::<RGBA32>(&bytes);
+    }
+
+    #[test]
+    fn pixel_from_rgba32_rounds() {
+        let grey = RGBA32{r: 0x88, g: 0x88, b: 0x88, a: 0xFF};
+        assert_eq!(I4::from_rgba32(&grey, &[]), I4{i: 8});
+        assert_eq!(IA8::from_rgba32(&grey, &[]), IA8{i: 8, a: 0xF});
+        assert_eq!(IA4::from_rgba32(&grey, &[]), IA4{i: 4, a: 1});
+
+        let palette = [RGBA16{r: 0, g: 0, b: 0, a: 1}, RGBA16{r: 0x11, g: 0x11, b: 0x11, a: 1}];
+        assert_eq!(CI4::from_rgba32(&grey, &palette), CI4(1));
+        assert_eq!(CI4(1).to_rgba32(&palette), RGBA32::from(&palette[1]));
+    }
+}
@@ -26,7 +26,7 @@ impl RGBA16{
         } 
     }
     pub fn to_be_bytes(&self)->[u8; 2]{ 
-        let val = (((self.r as u16) & 0x1F) << 11) | (((self.g as u16) & 0x1F) << 5) | (((self.b as u16) & 0x1F) << 1) | (self.a as u16) & 1;
+        let val = (((self.r as u16) & 0x1F) << 11) | (((self.g as u16) & 0x1F) << 6) | (((self.b as u16) & 0x1F) << 1) | (self.a as u16) & 1;
         val.to_be_bytes()
     }
 }
@@ -150,4 +150,13 @@ mod tests {
         assert_eq!(Some(RGBA16{r:0, g: 16, b: 2, a: 1}), rgba16_iter.next());
         assert_eq!(None, rgba16_iter.next());
     }
+
+    #[test]
+    fn rgba16_round_trip() {
+        let input : Vec<u8> = vec![0xF8, 0x01, 0x07, 0xC0, 0x00, 0x3E, 0x12, 0x35];
+        let output : Vec<u8> = input.clone().into_iter().rgba16_iter()
+            .flat_map(|px| px.to_be_bytes())
+            .collect();
+        assert_eq!(input, output);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use super::bkmodel::BKTextureList;
+use super::bktexture::*;
+use super::pixels::*;
+use super::error::{ModelNameError, TextureDimensionsError, TextureFormatError, TextureIndexError, TextureSizeError};
+use super::AssetFolder;
+
+/* Shared texture report
+    exact: same format, size and bytes (palette and every level)
+    near:  same size and same image once decoded and reduced to 4 bits per channel,
+           catches one image stored in several formats
+    hashes are FNV-1a so CSV output is stable between runs and builds
+*/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureOwner{
+    pub model: String,
+    pub texture_index: usize,
+    pub format: BKTextureFormat,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicateKind{
+    Exact,
+    Near,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextureGroup{
+    pub kind: DuplicateKind,
+    pub hash: u64,
+    pub owners: Vec<TextureOwner>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TextureReport{
+    pub groups: Vec<TextureGroup>,
+}
+
+fn fnv1a(bytes: impl Iterator<Item = u8>)->u64{
+    bytes.fold(0xcbf29ce484222325, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+fn format_name(format: &BKTextureFormat)->String{
+    match format {
+        BKTextureFormat::Unknown(x) => format!("Unknown(0x{:X})", x),
+        _ => format!("{:?}", format),
+    }
+}
+
+impl TextureReport{
+    pub fn from_texture_lists<'a>(lists: impl Iterator<Item = (&'a str, &'a BKTextureList)>)->TextureReport{
+        let mut exact : BTreeMap<u64, Vec<TextureOwner>> = BTreeMap::new();
+        let mut near : BTreeMap<u64, Vec<TextureOwner>> = BTreeMap::new();
+
+        for (model, list) in lists {
+            for (texture_index, header) in list.texture_headers.iter().enumerate() {
+                let owner = TextureOwner{
+                    model: model.to_string(),
+                    texture_index,
+                    format: header.format,
+                    width: header.width,
+                    height: header.height,
+                };
+
+                let span = list.texture_span(texture_index);
+                let data = list.texture_data.get(header.offset .. header.offset + span).unwrap_or(&[]);
+                let header_bytes = header.to_be_bytes();
+                let key = [header.width as u8, header.height as u8].into_iter()
+                    .chain(header_bytes[4..6].iter().cloned())
+                    .chain(data.iter().cloned());
+                exact.entry(fnv1a(key)).or_default().push(owner.clone());
+
+                if let Some(image) = list.texture(texture_index, 0) {
+                    let key = [header.width as u8, header.height as u8].into_iter()
+                        .chain(image.tmem.iter().flatten().flat_map(|px| [px.r >> 4, px.g >> 4, px.b >> 4, px.a >> 4]));
+                    near.entry(fnv1a(key)).or_default().push(owner);
+                }
+            }
+        }
+
+        let groups = |map: BTreeMap<u64, Vec<TextureOwner>>, kind: DuplicateKind| map.into_iter()
+            .filter(|(_, owners)| owners.len() > 1)
+            .map(move |(hash, owners)| TextureGroup{kind, hash, owners});
+
+        //near groups that only restate an exact group add nothing
+        let exact_groups : Vec<TextureGroup> = groups(exact, DuplicateKind::Exact).collect();
+        let near_groups = groups(near, DuplicateKind::Near)
+            .filter(|near| !exact_groups.iter().any(|exact| exact.owners == near.owners));
+        TextureReport{groups: exact_groups.iter().cloned().chain(near_groups).collect()}
+    }
+
+    pub fn to_csv(&self)->String{
+        let mut out = String::from("group,kind,hash,model,texture_index,format,width,height\n");
+        for (i, group) in self.groups.iter().enumerate() {
+            for owner in group.owners.iter() {
+                out += &format!("{},{:?},{:016X},\"{}\",{},{},{},{}\n",
+                    i, group.kind, group.hash, owner.model.replace('"', "\"\""),
+                    owner.texture_index, format_name(&owner.format), owner.width, owner.height
+                );
+            }
+        }
+        out
+    }
+}
+
+impl AssetFolder{
+    pub fn texture_report(&self)->TextureReport{
+        TextureReport::from_texture_lists(self.models.iter()
+            .filter_map(|(name, model)| model.texture_list.as_ref().map(|list| (name.as_str(), list)))
+        )
+    }
+
+    /* writes one image into every owner of a group, re-encoded in each owner's format.
+        every owner is checked and encoded first, nothing is written unless all of them fit
+    */
+    pub fn propagate_texture(&mut self, group: &TextureGroup, image: &BKTexture<RGBA32>)->Result<usize, Box<dyn Error>>{
+        let mut writes = Vec::with_capacity(group.owners.len());
+        for owner in group.owners.iter() {
+            let model = self.models.iter().position(|(name, _)| *name == owner.model).ok_or(ModelNameError)?;
+            let list = self.models[model].1.texture_list.as_ref().ok_or(TextureIndexError)?;
+            let header = list.texture_headers.get(owner.texture_index).ok_or(TextureIndexError)?;
+            if image.width() != header.width || image.height() != header.height { return Err(Box::new(TextureDimensionsError)) }
+
+            let bytes = header.format.encode(image, None).ok_or(TextureFormatError)?;
+            if bytes.len() > list.texture_span(owner.texture_index) { return Err(Box::new(TextureSizeError)) }
+            writes.push((model, owner.texture_index, header.offset, bytes));
+        }
+
+        for (model, texture_index, offset, bytes) in writes.iter() {
+            if let Some(list) = self.models[*model].1.texture_list.as_mut() {
+                list.texture_data[*offset .. *offset + bytes.len()].copy_from_slice(bytes);
+                list.regenerate_levels(*texture_index);
+            }
+        }
+        Ok(writes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::bkmodel::BKModel;
+
+    fn texture_list(format: BKTextureFormat, data: Vec<u8>)->BKTextureList{
+        BKTextureList{
+            texture_headers: vec![BKTextureHeader{offset: 0, format, flags: 0, level_count: 0, width: 2, height: 1, padding: [0; 6]}],
+            texture_data: data,
+        }
+    }
+
+    #[test]
+    fn texture_report_groups() {
+        let i8_a = texture_list(BKTextureFormat::I8, vec![0x00, 0xFF]);
+        let i8_b = texture_list(BKTextureFormat::I8, vec![0x00, 0xFF]);
+        let ia16 = texture_list(BKTextureFormat::IA16, vec![0x00, 0xFF, 0xFF, 0xFF]);
+        let other = texture_list(BKTextureFormat::I8, vec![0x10, 0x20]);
+        let lists = [("a", &i8_a), ("b", &i8_b), ("c", &ia16), ("d", &other)];
+
+        let report = TextureReport::from_texture_lists(lists.into_iter());
+        assert_eq!(report.groups.len(), 2);
+        assert_eq!(report.groups[0].kind, DuplicateKind::Exact);
+        assert_eq!(report.groups[0].owners.iter().map(|o| o.model.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(report.groups[1].kind, DuplicateKind::Near);
+        assert_eq!(report.groups[1].owners.len(), 3);
+
+        let csv = report.to_csv();
+        assert_eq!(csv.lines().count(), 1 + 2 + 3);
+        assert!(csv.contains(",Near,") && csv.contains(",\"c\",0,IA16,2,1\n"));
+    }
+
+    #[test]
+    fn texture_report_propagate() {
+        let model = |format, data| BKModel{texture_list: Some(texture_list(format, data)), ..Default::default()};
+        let mut folder = AssetFolder{models: vec![
+            ("a".to_string(), model(BKTextureFormat::I8, vec![0x00, 0xFF])),
+            ("b".to_string(), model(BKTextureFormat::IA16, vec![0x00, 0xFF, 0xFF, 0xFF])),
+        ]};
+        let group = folder.texture_report().groups.remove(0);
+        let white = RGBA32{r: 0xFF, g: 0xFF, b: 0xFF, a: 0xFF};
+        let image = BKTexture::<RGBA32>{palette: None, tmem: vec![vec![white; 2]]};
+        assert_eq!(folder.propagate_texture(&group, &image).unwrap(), 2);
+        assert_eq!(folder.models[1].1.texture_list.as_ref().unwrap().texture_data, [0xFF; 4]);
+
+        //a later owner that can't take the image leaves the earlier ones untouched
+        let mut missing = group.clone();
+        missing.owners[1].texture_index = 3;
+        let black = BKTexture::<RGBA32>{palette: None, tmem: vec![vec![RGBA32{r: 0, g: 0, b: 0, a: 0xFF}; 2]]};
+        assert!(folder.propagate_texture(&missing, &black).unwrap_err().is::<TextureIndexError>());
+        assert_eq!(folder.models[0].1.texture_list.as_ref().unwrap().texture_data, [0xFF; 2]);
+        missing.owners[1].model = "c".to_string();
+        assert!(folder.propagate_texture(&missing, &black).unwrap_err().is::<ModelNameError>());
+    }
+}